@@ -0,0 +1,26 @@
+//! An internal Vyper compilation service. Compiles with the `vyper` in PATH, or the one at
+//! `VYPER_PATH`, sandboxed on Linux when bwrap or unshare is installed.
+//!
+//! `cargo run --example compile-server --features server -- 127.0.0.1:8080`, then compile
+//! against it with `vyper_rs::remote::RemoteBackend::new("http://127.0.0.1:8080/compile")` or
+//! `curl -X POST --data @input.json http://127.0.0.1:8080/compile`.
+
+use std::time::Duration;
+use vyper_rs::{backend::Backend, server::CompileServer, vyper_errors::VyperErrors};
+
+#[tokio::main]
+async fn main() -> Result<(), VyperErrors> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:8080".to_owned());
+    let mut server = CompileServer::new().set_timeout(Duration::from_secs(60));
+    #[cfg(target_os = "linux")]
+    if let Ok(sandbox) = Backend::sandbox() {
+        server = server.set_backend(sandbox);
+    }
+    println!(
+        "compiling on http://{}/compile with {:?}",
+        addr, server.backend
+    );
+    server.serve(addr).await
+}
@@ -9,8 +9,7 @@ use ethers::{
 };
 
 use std::{
-    convert::TryFrom, error::Error, fs::File, path::PathBuf, str::FromStr, sync::Arc,
-    time::Duration,
+    convert::TryFrom, error::Error, fs::File, path::PathBuf, sync::Arc, time::Duration,
 };
 use vyper_rs::vyper::Vyper;
 
@@ -31,7 +30,7 @@ pub async fn deploy() -> Result<(), Box<dyn Error>> {
 
     let factory = ContractFactory::new(
         ethers::abi::Contract::load(File::open(contract.abi)?)?,
-        Bytes::from_str(&contract.bytecode.unwrap())?,
+        Bytes::from(contract.bytecode.unwrap().0),
         client,
     );
     println!("{:#?}", factory);
@@ -0,0 +1,590 @@
+//! Typed view of the ABI emitted by the compiler, and conformance checks against common token
+//! and vault standards so CI can assert a contract exposes the full interface before it's
+//! deployed.
+
+use crate::vyper_errors::VyperErrors;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha3::{Digest, Keccak256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Abi(pub Vec<AbiEntry>);
+
+/// A function, event, constructor or fallback of the ABI
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct AbiEntry {
+    #[serde(rename = "type")]
+    pub ty: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub inputs: Vec<AbiParam>,
+    #[serde(default)]
+    pub outputs: Vec<AbiParam>,
+    #[serde(default, rename = "stateMutability")]
+    pub state_mutability: Option<String>,
+    /// Whether the event is emitted without its signature as first topic
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub anonymous: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct AbiParam {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Members of a `tuple` type
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<AbiParam>,
+    /// Whether the event parameter is a topic rather than part of the data
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub indexed: bool,
+}
+
+impl AbiParam {
+    /// The canonical type used in signatures, with tuples expanded to their members
+    pub fn canonical_type(&self) -> String {
+        match self.ty.strip_prefix("tuple") {
+            Some(suffix) => format!("({}){}", canonical_types(&self.components), suffix),
+            None => self.ty.clone(),
+        }
+    }
+
+    /// Splits `T[2]` into `T` and `2`, and `T[]` into `T` and an empty length. `None` for types
+    /// that aren't arrays.
+    pub(crate) fn array_element(&self) -> Option<(AbiParam, &str)> {
+        let open = self.ty.strip_suffix(']')?.rfind('[')?;
+        let element = AbiParam {
+            ty: self.ty[..open].to_owned(),
+            ..self.clone()
+        };
+        Some((element, &self.ty[open + 1..self.ty.len() - 1]))
+    }
+
+    /// Whether the encoding of the type has a variable size, so it's encoded after the heads
+    pub fn is_dynamic(&self) -> bool {
+        match self.array_element() {
+            Some((_, "")) => true,
+            Some((element, _)) => element.is_dynamic(),
+            None if self.ty == "tuple" => {
+                self.components.iter().any(AbiParam::is_dynamic)
+            }
+            None => self.ty == "bytes" || self.ty == "string",
+        }
+    }
+
+    /// Bytes taken by the type in the heads of an encoding
+    pub(crate) fn head_size(&self) -> usize {
+        if self.is_dynamic() {
+            return 32;
+        }
+        match self.array_element() {
+            Some((element, len)) => {
+                element.head_size() * len.parse::<usize>().unwrap_or(0)
+            }
+            None if self.ty == "tuple" => {
+                self.components.iter().map(AbiParam::head_size).sum()
+            }
+            None => 32,
+        }
+    }
+}
+
+fn canonical_types(params: &[AbiParam]) -> String {
+    params
+        .iter()
+        .map(AbiParam::canonical_type)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+impl AbiEntry {
+    /// Canonical signature, e.g. `transfer(address,uint256)`. `None` for entries without a name
+    /// such as the constructor.
+    pub fn signature(&self) -> Option<String> {
+        let name = self.name.as_deref()?;
+        Some(format!("{}({})", name, canonical_types(&self.inputs)))
+    }
+}
+
+impl Abi {
+    /// Reads an ABI file written by `Vyper::gen_abi`
+    pub fn read(path: &Path) -> Result<Abi, VyperErrors> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    fn signatures(&self, ty: &str) -> BTreeSet<String> {
+        self.0
+            .iter()
+            .filter(|entry| entry.ty == ty)
+            .filter_map(AbiEntry::signature)
+            .collect()
+    }
+
+    /// Signatures of every external function
+    pub fn functions(&self) -> BTreeSet<String> {
+        self.signatures("function")
+    }
+
+    /// Signatures of every event
+    pub fn events(&self) -> BTreeSet<String> {
+        self.signatures("event")
+    }
+
+    /// The first topic of every event's logs, keyed by signature, e.g.
+    /// `Transfer(address,address,uint256)` to `0xddf252ad...`. Anonymous events have none.
+    pub fn event_signatures(&self) -> BTreeMap<String, String> {
+        self.0
+            .iter()
+            .filter(|entry| entry.ty == "event" && !entry.anonymous)
+            .filter_map(AbiEntry::signature)
+            .map(|signature| {
+                let topic = event_topic(&signature);
+                (signature, topic)
+            })
+            .collect()
+    }
+
+    /// The first topic of the logs of event `name`, to filter logs by. `name` can also be a
+    /// signature, to pick one of several events sharing a name. `None` when there's no such
+    /// event or it's anonymous.
+    pub fn event_topic0(&self, name: &str) -> Option<String> {
+        self.0
+            .iter()
+            .filter(|entry| entry.ty == "event" && !entry.anonymous)
+            .filter_map(AbiEntry::signature)
+            .find(|signature| {
+                signature == name || signature.split('(').next() == Some(name)
+            })
+            .map(|signature| event_topic(&signature))
+    }
+
+    /// Functions and events of `standard` missing from this ABI, functions first
+    pub fn missing(&self, standard: Standard) -> Vec<String> {
+        let (functions, events) = (self.functions(), self.events());
+        let functions = standard
+            .functions()
+            .into_iter()
+            .map(|f| f.signature)
+            .filter(|f| !functions.contains(*f));
+        let events = standard
+            .events()
+            .into_iter()
+            .filter(|e| !events.contains(*e));
+        functions.chain(events).map(str::to_owned).collect()
+    }
+
+    pub fn conforms_to(&self, standard: Standard) -> bool {
+        self.missing(standard).is_empty()
+    }
+
+    /// Same as `conforms_to`, but fails with `NonConformingInterface` listing what's missing
+    pub fn check(&self, standard: Standard) -> Result<(), VyperErrors> {
+        let missing = self.missing(standard);
+        if !missing.is_empty() {
+            Err(VyperErrors::NonConformingInterface { standard, missing })?
+        }
+        Ok(())
+    }
+}
+
+/// Keccak-256 of an event signature, hex encoded with a `0x` prefix: the first topic of the
+/// event's logs
+pub fn event_topic(signature: &str) -> String {
+    format!("0x{}", hex::encode(Keccak256::digest(signature)))
+}
+
+/// ABI decodes `data` as values of `params`. Integers and decimals are decimal strings, as
+/// JSON numbers can't hold 256 bits, addresses and byte strings are `0x` prefixed hex, and
+/// arrays and tuples are JSON arrays.
+pub fn decode(params: &[AbiParam], data: &[u8]) -> Result<Vec<Value>, VyperErrors> {
+    let mut head = 0;
+    let mut values = Vec::with_capacity(params.len());
+    for param in params {
+        let encoded = match param.is_dynamic() {
+            true => {
+                let offset = read_len(data, head)?;
+                data.get(offset..).ok_or_else(|| out_of_bounds(param))?
+            }
+            false => data.get(head..).ok_or_else(|| out_of_bounds(param))?,
+        };
+        values.push(decode_single(param, encoded)?);
+        head += param.head_size();
+    }
+    Ok(values)
+}
+
+fn decode_single(param: &AbiParam, data: &[u8]) -> Result<Value, VyperErrors> {
+    match param.array_element() {
+        Some((element, "")) => {
+            let len = read_len(data, 0)?;
+            // every element takes at least a word, which bounds `len` before allocating
+            if len > data.len() / 32 {
+                Err(out_of_bounds(param))?
+            }
+            Ok(Value::Array(decode(&vec![element; len], &data[32..])?))
+        }
+        Some((element, len)) => {
+            let len = len.parse::<usize>().map_err(|_| unsupported(param))?;
+            Ok(Value::Array(decode(&vec![element; len], data)?))
+        }
+        None if param.ty == "tuple" => Ok(Value::Array(decode(&param.components, data)?)),
+        None if param.ty == "bytes" || param.ty == "string" => {
+            let len = read_len(data, 0)?;
+            let bytes = data
+                .get(32..32usize.saturating_add(len))
+                .ok_or_else(|| out_of_bounds(param))?;
+            match param.ty.as_str() {
+                "string" => String::from_utf8(bytes.to_vec())
+                    .map(Value::String)
+                    .map_err(|_| {
+                        VyperErrors::AbiDecodeError(format!(
+                            "invalid UTF-8 in {}",
+                            param.ty
+                        ))
+                    }),
+                _ => Ok(Value::String(format!("0x{}", hex::encode(bytes)))),
+            }
+        }
+        None => {
+            let word: [u8; 32] = data
+                .get(..32)
+                .ok_or_else(|| out_of_bounds(param))?
+                .try_into()
+                .expect("32 bytes");
+            decode_word(param, word)
+        }
+    }
+}
+
+fn decode_word(param: &AbiParam, word: [u8; 32]) -> Result<Value, VyperErrors> {
+    let ty = param.ty.as_str();
+    let dirty = |clean: usize| word[..clean].iter().any(|b| *b != 0);
+    if ty == "address" {
+        if dirty(12) {
+            Err(VyperErrors::AbiDecodeError(format!(
+                "dirty address 0x{}",
+                hex::encode(word)
+            )))?
+        }
+        return Ok(Value::String(format!("0x{}", hex::encode(&word[12..]))));
+    }
+    if ty == "bool" {
+        return match (dirty(31), word[31]) {
+            (false, 0) => Ok(Value::Bool(false)),
+            (false, 1) => Ok(Value::Bool(true)),
+            _ => Err(VyperErrors::AbiDecodeError(format!(
+                "invalid bool 0x{}",
+                hex::encode(word)
+            ))),
+        };
+    }
+    if let Some(size) = ty.strip_prefix("bytes") {
+        let size = size
+            .parse::<usize>()
+            .ok()
+            .filter(|size| (1..=32).contains(size));
+        let size = size.ok_or_else(|| unsupported(param))?;
+        return Ok(Value::String(format!("0x{}", hex::encode(&word[..size]))));
+    }
+    // `decimal` is `fixed168x10`
+    let (signed, decimals) = if ty.starts_with("uint") {
+        (false, 0)
+    } else if ty.starts_with("int") {
+        (true, 0)
+    } else if let Some((_, decimals)) =
+        ty.strip_prefix("fixed").and_then(|t| t.split_once('x'))
+    {
+        (true, decimals.parse().map_err(|_| unsupported(param))?)
+    } else if let Some((_, decimals)) =
+        ty.strip_prefix("ufixed").and_then(|t| t.split_once('x'))
+    {
+        (false, decimals.parse().map_err(|_| unsupported(param))?)
+    } else {
+        Err(unsupported(param))?
+    };
+    let negative = signed && word[0] & 0x80 != 0;
+    let mut magnitude = word;
+    if negative {
+        // two's complement: invert and add one
+        let mut carry = true;
+        for b in magnitude.iter_mut().rev() {
+            let (sum, overflow) = (!*b).overflowing_add(carry as u8);
+            *b = sum;
+            carry = overflow;
+        }
+    }
+    let mut digits = to_decimal(magnitude);
+    if decimals > 0 {
+        if digits.len() <= decimals {
+            digits = format!("{}{}", "0".repeat(decimals + 1 - digits.len()), digits);
+        }
+        digits.insert(digits.len() - decimals, '.');
+        digits = digits
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_owned();
+    }
+    Ok(Value::String(match negative {
+        true => format!("-{}", digits),
+        false => digits,
+    }))
+}
+
+/// Decimal digits of a big endian unsigned word
+fn to_decimal(mut word: [u8; 32]) -> String {
+    let mut digits = vec![];
+    while word.iter().any(|b| *b != 0) || digits.is_empty() {
+        let mut rem = 0u32;
+        for b in word.iter_mut() {
+            let acc = rem << 8 | *b as u32;
+            *b = (acc / 10) as u8;
+            rem = acc % 10;
+        }
+        digits.push(char::from(b'0' + rem as u8));
+    }
+    digits.iter().rev().collect()
+}
+
+/// The word at `at` as an offset or length into `data`
+fn read_len(data: &[u8], at: usize) -> Result<usize, VyperErrors> {
+    let word = data.get(at..at.saturating_add(32)).ok_or_else(|| {
+        VyperErrors::AbiDecodeError(format!("no word at offset {}", at))
+    })?;
+    if word[..24].iter().any(|b| *b != 0) {
+        Err(VyperErrors::AbiDecodeError(format!(
+            "offset or length 0x{} is too large",
+            hex::encode(word)
+        )))?
+    }
+    let len = u64::from_be_bytes(word[24..].try_into().expect("8 bytes"));
+    usize::try_from(len).map_err(|_| {
+        VyperErrors::AbiDecodeError(format!("offset or length {} is too large", len))
+    })
+}
+
+fn out_of_bounds(param: &AbiParam) -> VyperErrors {
+    VyperErrors::AbiDecodeError(format!("{} {} runs past the data", param.ty, param.name))
+}
+
+fn unsupported(param: &AbiParam) -> VyperErrors {
+    VyperErrors::AbiDecodeError(format!("unsupported type {}", param.ty))
+}
+
+impl TryFrom<Value> for Abi {
+    type Error = VyperErrors;
+
+    fn try_from(abi: Value) -> Result<Self, Self::Error> {
+        Ok(serde_json::from_value(abi)?)
+    }
+}
+
+/// A function exposing a selector, see `SelectorReport`
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct SelectorUse {
+    pub contract: PathBuf,
+    pub signature: String,
+}
+
+/// Function selectors of every contract of a workspace, built by `Vypers::selector_report`.
+///
+/// A selector shared by functions with different signatures is a collision: any router or
+/// diamond dispatching on selectors can't tell them apart. The same function in several
+/// contracts is listed in `selectors` but isn't a collision.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SelectorReport {
+    /// Keyed by selector, e.g. `0xa9059cbb`
+    pub selectors: BTreeMap<String, BTreeSet<SelectorUse>>,
+    /// The selectors shared by more than one signature, with every function using them
+    pub collisions: BTreeMap<String, BTreeSet<SelectorUse>>,
+}
+
+impl SelectorReport {
+    /// Builds the report from each contract's method identifiers, as output by
+    /// `vyper -f method_identifiers`
+    pub fn new(contracts: BTreeMap<PathBuf, BTreeMap<String, String>>) -> SelectorReport {
+        let mut selectors: BTreeMap<String, BTreeSet<SelectorUse>> = BTreeMap::new();
+        for (contract, identifiers) in contracts {
+            for (signature, selector) in identifiers {
+                selectors
+                    .entry(selector.to_lowercase())
+                    .or_default()
+                    .insert(SelectorUse {
+                        contract: contract.clone(),
+                        signature,
+                    });
+            }
+        }
+        let collisions = selectors
+            .iter()
+            .filter(|(_, uses)| {
+                let signatures: BTreeSet<&String> =
+                    uses.iter().map(|u| &u.signature).collect();
+                signatures.len() > 1
+            })
+            .map(|(selector, uses)| (selector.clone(), uses.clone()))
+            .collect();
+        SelectorReport {
+            selectors,
+            collisions,
+        }
+    }
+
+    pub fn has_collisions(&self) -> bool {
+        !self.collisions.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, VyperErrors> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// A function of a standard interface
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Function {
+    pub signature: &'static str,
+    /// The 4 byte selector, hex encoded with a `0x` prefix as in the compiler's
+    /// `method_identifiers` output
+    pub selector: &'static str,
+}
+
+const fn function(signature: &'static str, selector: &'static str) -> Function {
+    Function {
+        signature,
+        selector,
+    }
+}
+
+const ERC165: &[Function] = &[function("supportsInterface(bytes4)", "0x01ffc9a7")];
+
+const ERC20: &[Function] = &[
+    function("totalSupply()", "0x18160ddd"),
+    function("balanceOf(address)", "0x70a08231"),
+    function("transfer(address,uint256)", "0xa9059cbb"),
+    function("transferFrom(address,address,uint256)", "0x23b872dd"),
+    function("approve(address,uint256)", "0x095ea7b3"),
+    function("allowance(address,address)", "0xdd62ed3e"),
+];
+
+const ERC721: &[Function] = &[
+    function("balanceOf(address)", "0x70a08231"),
+    function("ownerOf(uint256)", "0x6352211e"),
+    function(
+        "safeTransferFrom(address,address,uint256,bytes)",
+        "0xb88d4fde",
+    ),
+    function("safeTransferFrom(address,address,uint256)", "0x42842e0e"),
+    function("transferFrom(address,address,uint256)", "0x23b872dd"),
+    function("approve(address,uint256)", "0x095ea7b3"),
+    function("setApprovalForAll(address,bool)", "0xa22cb465"),
+    function("getApproved(uint256)", "0x081812fc"),
+    function("isApprovedForAll(address,address)", "0xe985e9c5"),
+];
+
+const ERC1155: &[Function] = &[
+    function(
+        "safeTransferFrom(address,address,uint256,uint256,bytes)",
+        "0xf242432a",
+    ),
+    function(
+        "safeBatchTransferFrom(address,address,uint256[],uint256[],bytes)",
+        "0x2eb2c2d6",
+    ),
+    function("balanceOf(address,uint256)", "0x00fdd58e"),
+    function("balanceOfBatch(address[],uint256[])", "0x4e1273f4"),
+    function("setApprovalForAll(address,bool)", "0xa22cb465"),
+    function("isApprovedForAll(address,address)", "0xe985e9c5"),
+];
+
+const ERC4626: &[Function] = &[
+    function("asset()", "0x38d52e0f"),
+    function("totalAssets()", "0x01e1d114"),
+    function("convertToShares(uint256)", "0xc6e6f592"),
+    function("convertToAssets(uint256)", "0x07a2d13a"),
+    function("maxDeposit(address)", "0x402d267d"),
+    function("previewDeposit(uint256)", "0xef8b30f7"),
+    function("deposit(uint256,address)", "0x6e553f65"),
+    function("maxMint(address)", "0xc63d75b6"),
+    function("previewMint(uint256)", "0xb3d7f6b9"),
+    function("mint(uint256,address)", "0x94bf804d"),
+    function("maxWithdraw(address)", "0xce96cb77"),
+    function("previewWithdraw(uint256)", "0x0a28a477"),
+    function("withdraw(uint256,address,address)", "0xb460af94"),
+    function("maxRedeem(address)", "0xd905777e"),
+    function("previewRedeem(uint256)", "0x4cdad506"),
+    function("redeem(uint256,address,address)", "0xba087652"),
+];
+
+/// Interfaces `Abi::conforms_to` can check against. Only the required members of each standard
+/// are checked; optional extensions such as ERC-20 metadata are not.
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Standard {
+    Erc20,
+    /// Includes ERC-165 `supportsInterface`
+    Erc721,
+    /// Includes ERC-165 `supportsInterface`
+    Erc1155,
+    /// Includes ERC-20, which every ERC-4626 vault implements for its shares
+    Erc4626,
+}
+
+impl Standard {
+    /// Functions the standard requires
+    pub fn functions(&self) -> Vec<Function> {
+        let parts: &[&[Function]] = match self {
+            Standard::Erc20 => &[ERC20],
+            Standard::Erc721 => &[ERC721, ERC165],
+            Standard::Erc1155 => &[ERC1155, ERC165],
+            Standard::Erc4626 => &[ERC20, ERC4626],
+        };
+        parts.concat()
+    }
+
+    /// Signatures of the events the standard requires
+    pub fn events(&self) -> Vec<&'static str> {
+        match self {
+            Standard::Erc20 => vec![
+                "Transfer(address,address,uint256)",
+                "Approval(address,address,uint256)",
+            ],
+            Standard::Erc721 => vec![
+                "Transfer(address,address,uint256)",
+                "Approval(address,address,uint256)",
+                "ApprovalForAll(address,address,bool)",
+            ],
+            Standard::Erc1155 => vec![
+                "TransferSingle(address,address,address,uint256,uint256)",
+                "TransferBatch(address,address,address,uint256[],uint256[])",
+                "ApprovalForAll(address,address,bool)",
+                "URI(string,uint256)",
+            ],
+            Standard::Erc4626 => vec![
+                "Transfer(address,address,uint256)",
+                "Approval(address,address,uint256)",
+                "Deposit(address,address,uint256,uint256)",
+                "Withdraw(address,address,address,uint256,uint256)",
+            ],
+        }
+    }
+}
+
+impl Display for Standard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Standard::Erc20 => write!(f, "ERC-20"),
+            Standard::Erc721 => write!(f, "ERC-721"),
+            Standard::Erc1155 => write!(f, "ERC-1155"),
+            Standard::Erc4626 => write!(f, "ERC-4626"),
+        }
+    }
+}
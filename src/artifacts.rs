@@ -0,0 +1,547 @@
+//! Build artifacts: everything known about a compiled contract, written as JSON so deployment
+//! tooling can pick up the bytecode and ABI without invoking the compiler again.
+//!
+//! Artifacts are written deterministically, with sorted keys and `\n` line endings, so
+//! committing them to git only ever shows real changes.
+
+use crate::{bytecode::Bytecode, settings::Optimize, utils, vyper_errors::VyperErrors};
+#[cfg(feature = "native")]
+use crate::{project::Project, utils::FileLock};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Directory below the project root artifacts are written to
+pub const ARTIFACTS_DIR: &str = "artifacts";
+
+/// Manifest of the files generated by the crate in a directory, see `Manifest`
+pub const MANIFEST: &str = ".vyper-rs-manifest.json";
+
+/// Package manifest Ape collects the contract types of a project in, below its root
+pub const APE_MANIFEST: &str = ".build/__local__.json";
+
+/// Directory below the project root Brownie reads contract build files from
+pub const BROWNIE_BUILD_DIR: &str = "build/contracts";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Path of the contract, as passed to the compiler
+    pub contract: PathBuf,
+    /// Where the ABI is written by `Vyper::gen_abi`
+    pub abi_path: PathBuf,
+    pub bytecode: Option<Bytecode>,
+    /// `Value::Null` when the ABI wasn't generated
+    pub abi: Value,
+    /// Output of `vyper --version`
+    pub compiler: Option<String>,
+    /// EVM version the bytecode targets
+    pub evm_version: Option<String>,
+    /// Optimization mode passed with `--optimize`, `None` for the compiler's default
+    #[serde(default)]
+    pub optimize: Option<String>,
+    /// Hash of the contract's source, `None` when it couldn't be read
+    #[serde(default)]
+    pub source_sha256: Option<String>,
+    /// Hash of the compiler version and settings, see `settings_fingerprint`
+    #[serde(default)]
+    pub fingerprint: String,
+    /// Output of `vyper -f metadata`, as required by verification tools. `None` with compilers
+    /// without it, before 0.3.10.
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+impl Artifact {
+    /// An artifact of `contract`, hashing its source as it is on disk now
+    pub fn new(
+        contract: PathBuf,
+        abi_path: PathBuf,
+        bytecode: Option<Bytecode>,
+        abi: Value,
+        compiler: Option<String>,
+        evm_version: Option<String>,
+    ) -> Artifact {
+        let compiler = compiler.map(|c| c.trim().to_owned());
+        Artifact {
+            source_sha256: utils::sha256_file(&contract).ok(),
+            fingerprint: settings_fingerprint(
+                compiler.as_deref(),
+                evm_version.as_deref(),
+                None,
+            ),
+            contract,
+            abi_path,
+            bytecode,
+            abi,
+            compiler,
+            evm_version,
+            optimize: None,
+            metadata: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: Value) -> Artifact {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Records that the bytecode was built with `--optimize optimize`
+    pub fn with_optimize(mut self, optimize: Optimize) -> Artifact {
+        self.optimize = Some(optimize.to_string());
+        self.fingerprint = settings_fingerprint(
+            self.compiler.as_deref(),
+            self.evm_version.as_deref(),
+            self.optimize.as_deref(),
+        );
+        self
+    }
+
+    /// Whether the artifact was built from the contract's current source with `compiler`,
+    /// `evm_version` and `optimize`
+    pub fn is_fresh(
+        &self,
+        compiler: Option<&str>,
+        evm_version: Option<&str>,
+        optimize: Option<&str>,
+    ) -> bool {
+        self.source_sha256.is_some()
+            && self.source_sha256 == utils::sha256_file(&self.contract).ok()
+            && self.fingerprint == settings_fingerprint(compiler, evm_version, optimize)
+    }
+
+    /// The artifact as written to disk: pretty printed, with sorted keys and a trailing
+    /// newline
+    pub fn to_json(&self) -> Result<String, VyperErrors> {
+        // `Value` keeps its keys sorted, unlike serializing the struct directly
+        let value = serde_json::to_value(self)?;
+        let mut json = serde_json::to_string_pretty(&value)?.replace("\r\n", "\n");
+        json.push('\n');
+        Ok(json)
+    }
+
+    pub fn read(path: &Path) -> Result<Artifact, VyperErrors> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    /// Writes the artifact to `path`, creating its parent directories. An identical file is
+    /// left untouched.
+    pub fn write(&self, path: &Path) -> Result<(), VyperErrors> {
+        let json = self.to_json()?;
+        if fs::read_to_string(path).map_or(false, |existing| existing == json) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// All outputs of a contract, produced in a single compiler invocation by `Vyper::build`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompiledContract {
+    pub contract: PathBuf,
+    pub bytecode: String,
+    pub runtime_bytecode: String,
+    pub abi: Value,
+    /// Function signatures mapped to their selector, i.e. `transfer(address,uint256)` to
+    /// `0xa9059cbb`
+    pub method_identifiers: BTreeMap<String, String>,
+    pub storage_layout: Value,
+    pub userdoc: Value,
+    pub devdoc: Value,
+    pub source_sha256: String,
+}
+
+/// Output formats requested by `Vyper::build`, in the order the compiler prints them
+#[cfg(feature = "native")]
+pub(crate) const BUILD_FORMATS: &str =
+    "bytecode,bytecode_runtime,abi,method_identifiers,layout,userdoc,devdoc";
+
+impl CompiledContract {
+    /// Parses the output of the compiler run with `BUILD_FORMATS`, one output per line
+    #[cfg(feature = "native")]
+    pub(crate) fn from_output(
+        contract: &Path,
+        stdout: &str,
+    ) -> Result<CompiledContract, VyperErrors> {
+        let mut lines = stdout.lines().map(str::trim).filter(|l| !l.is_empty());
+        let mut next = || lines.next().ok_or(VyperErrors::StringParsingError);
+        let bytecode = next()?.to_owned();
+        let runtime_bytecode = next()?.to_owned();
+        let abi = serde_json::from_str(next()?)?;
+        let method_identifiers = serde_json::from_str(next()?)?;
+        let storage_layout = serde_json::from_str(next()?)?;
+        let userdoc = serde_json::from_str(next()?)?;
+        let devdoc = serde_json::from_str(next()?)?;
+        Ok(CompiledContract {
+            contract: contract.to_path_buf(),
+            bytecode,
+            runtime_bytecode,
+            abi,
+            method_identifiers,
+            storage_layout,
+            userdoc,
+            devdoc,
+            source_sha256: utils::sha256_file(contract)?,
+        })
+    }
+}
+
+/// Build folder layouts of python frameworks, so contracts compiled by vyper-rs can be used
+/// from Ape or Brownie without compiling them again. See `Vyper::write_python_build`.
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PythonBuild {
+    /// The contract type is added to `APE_MANIFEST`, next to the project's other contracts
+    Ape,
+    /// The contract is written to `{name}.json` in `BROWNIE_BUILD_DIR`
+    Brownie,
+}
+
+impl PythonBuild {
+    /// The file the contract `name` is written to in the project at `root`
+    pub fn path(&self, root: &Path, name: &str) -> PathBuf {
+        match self {
+            PythonBuild::Ape => root.join(APE_MANIFEST),
+            PythonBuild::Brownie => {
+                root.join(BROWNIE_BUILD_DIR).join(format!("{}.json", name))
+            }
+        }
+    }
+
+    /// Writes `compiled`, built with vyper `compiler`, below `root` and returns the written
+    /// file. An existing Ape manifest keeps its other contract types.
+    pub fn write(
+        &self,
+        root: &Path,
+        compiled: &CompiledContract,
+        compiler: &str,
+    ) -> Result<PathBuf, VyperErrors> {
+        let name = utils::contract_name(&compiled.contract).unwrap_or_default();
+        let source_id = compiled
+            .contract
+            .strip_prefix(root)
+            .unwrap_or(&compiled.contract)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let path = self.path(root, &name);
+        let json = match self {
+            PythonBuild::Ape => {
+                let mut manifest: Value = match File::open(&path) {
+                    Ok(file) => serde_json::from_reader(file)?,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => json!({}),
+                    Err(e) => Err(e)?,
+                };
+                let contract_type = json!({
+                    "contractName": name,
+                    "sourceId": source_id,
+                    "deploymentBytecode": { "bytecode": compiled.bytecode },
+                    "runtimeBytecode": { "bytecode": compiled.runtime_bytecode },
+                    "abi": compiled.abi,
+                    "userdoc": compiled.userdoc,
+                    "devdoc": compiled.devdoc,
+                    "methodIdentifiers": compiled.method_identifiers,
+                });
+                if !manifest["contractTypes"].is_object() {
+                    manifest["contractTypes"] = json!({});
+                }
+                manifest["contractTypes"][name.as_str()] = contract_type;
+                manifest
+            }
+            PythonBuild::Brownie => json!({
+                "abi": compiled.abi,
+                "allSourcePaths": { "0": source_id },
+                "bytecode": compiled.bytecode.trim_start_matches("0x"),
+                "compiler": { "version": compiler.trim() },
+                "contractName": name,
+                "dependencies": [],
+                "deployedBytecode": compiled.runtime_bytecode.trim_start_matches("0x"),
+                "language": "Vyper",
+                "natspec": { "userdoc": compiled.userdoc, "devdoc": compiled.devdoc },
+                "source": fs::read_to_string(&compiled.contract)?,
+                "sourcePath": source_id,
+                "type": "contract",
+            }),
+        };
+        let mut contents = serde_json::to_string_pretty(&json)?;
+        contents.push('\n');
+        write_output(&path, contents.as_bytes())?;
+        Ok(path)
+    }
+}
+
+/// A compiled contract in the layout titanoboa's `load_partial` and its compilation cache
+/// work with: the source alongside the compiler's outputs and the settings they were built
+/// with. Python tests can deploy `bytecode` or wrap a deployment with `abi` through
+/// `boa.loads_abi` instead of compiling the contract again. See `Vyper::boa_export`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoaExport {
+    pub contract_name: String,
+    /// Path of the contract, as passed to the compiler
+    pub filename: PathBuf,
+    pub source_code: String,
+    /// Output of `vyper --version`
+    pub compiler_version: String,
+    pub bytecode: String,
+    pub bytecode_runtime: String,
+    pub abi: Value,
+    pub method_identifiers: BTreeMap<String, String>,
+    pub settings: BoaSettings,
+}
+
+/// Settings a `BoaExport` was compiled with, `None` for the compiler's defaults
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct BoaSettings {
+    pub evm_version: Option<String>,
+    pub optimize: Option<Optimize>,
+}
+
+impl BoaExport {
+    pub fn new(
+        compiled: CompiledContract,
+        compiler_version: &str,
+        settings: BoaSettings,
+    ) -> Result<BoaExport, VyperErrors> {
+        Ok(BoaExport {
+            contract_name: utils::contract_name(&compiled.contract).unwrap_or_default(),
+            source_code: fs::read_to_string(&compiled.contract)?,
+            filename: compiled.contract,
+            compiler_version: compiler_version.trim().to_owned(),
+            bytecode: compiled.bytecode,
+            bytecode_runtime: compiled.runtime_bytecode,
+            abi: compiled.abi,
+            method_identifiers: compiled.method_identifiers,
+            settings,
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<BoaExport, VyperErrors> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    /// Writes the export to `path` with sorted keys, creating its parent directories
+    pub fn write(&self, path: &Path) -> Result<(), VyperErrors> {
+        let value = serde_json::to_value(self)?;
+        let mut json = serde_json::to_string_pretty(&value)?;
+        json.push('\n');
+        write_output(path, json.as_bytes())
+    }
+}
+
+/// The artifacts of a project, see `Project::load_artifacts`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Artifacts(pub Vec<Artifact>);
+
+#[cfg(feature = "native")]
+impl Artifacts {
+    /// Whether every contract of `project` has an artifact built from its current source
+    /// with the project's current compiler and settings
+    pub fn is_fresh(&self, project: &Project) -> Result<bool, VyperErrors> {
+        Ok(self.stale(project)?.is_empty())
+    }
+
+    /// The contracts of `project` whose artifact is missing or out of date
+    pub fn stale(&self, project: &Project) -> Result<Vec<PathBuf>, VyperErrors> {
+        let targets = project.targets()?;
+        let optimize = project.contracts.optimize.map(|o| o.to_string());
+        let mut out = vec![];
+        for (path, (compiler, evm_version)) in
+            project.contracts.path_to_code.iter().zip(targets)
+        {
+            let fresh = self.0.iter().any(|artifact| {
+                &artifact.contract == path
+                    && artifact.is_fresh(
+                        Some(&compiler),
+                        evm_version.as_deref(),
+                        optimize.as_deref(),
+                    )
+            });
+            if !fresh {
+                out.push(path.clone());
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Hash identifying the compiler and settings an artifact was built with
+pub fn settings_fingerprint(
+    compiler: Option<&str>,
+    evm_version: Option<&str>,
+    optimize: Option<&str>,
+) -> String {
+    utils::sha256_hex(
+        format!(
+            "compiler={}\nevm_version={}\noptimize={}\n",
+            compiler.unwrap_or_default().trim(),
+            evm_version.unwrap_or_default(),
+            optimize.unwrap_or_default()
+        )
+        .as_bytes(),
+    )
+}
+
+/// Where the artifact of `contract` lives in `dir`, mirroring the contract's path below `root`
+pub fn artifact_path(dir: &Path, root: &Path, contract: &Path) -> PathBuf {
+    let relative = contract.strip_prefix(root).unwrap_or(contract);
+    let relative: PathBuf = relative
+        .components()
+        .filter(|c| matches!(c, std::path::Component::Normal(_)))
+        .collect();
+    dir.join(relative).with_extension("json")
+}
+
+/// What the methods writing compiler outputs to files, like `Vyper::gen_abi`, do when the file
+/// already exists
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwritePolicy {
+    /// Replace the file
+    #[default]
+    Overwrite,
+    /// Fail with `VyperErrors::OutputExists`
+    Error,
+    /// Keep the file, without running the compiler, when it was modified after the contract's
+    /// source. Changes to imported contracts aren't noticed.
+    SkipIfFresh,
+}
+
+impl OverwritePolicy {
+    /// Whether `output`, generated from the contract at `source`, should be written
+    pub fn should_write(
+        &self,
+        source: &Path,
+        output: &Path,
+    ) -> Result<bool, VyperErrors> {
+        let Ok(existing) = fs::metadata(output) else {
+            return Ok(true);
+        };
+        match self {
+            OverwritePolicy::Overwrite => Ok(true),
+            OverwritePolicy::Error => {
+                Err(VyperErrors::OutputExists(output.to_path_buf()))
+            }
+            OverwritePolicy::SkipIfFresh => {
+                let source = fs::metadata(source)?.modified()?;
+                Ok(existing.modified()? < source)
+            }
+        }
+    }
+}
+
+/// Writes `contents` to `path`, creating its parent directories
+pub(crate) fn write_output(path: &Path, contents: &[u8]) -> Result<(), VyperErrors> {
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Every file the crate generated, mapped to the contract it was generated for, so cleaning up
+/// never deletes a file the crate didn't write. `Vyper` records its outputs in the manifest of
+/// the current directory when it tracks them, `Project` its artifacts in the one of the project
+/// root.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: BTreeMap<PathBuf, PathBuf>,
+}
+
+impl Manifest {
+    /// Reads the manifest of `dir`, empty when there is none
+    pub fn read(dir: &Path) -> Result<Manifest, VyperErrors> {
+        match File::open(dir.join(MANIFEST)) {
+            Ok(file) => Ok(serde_json::from_reader(file)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(e) => Err(e)?,
+        }
+    }
+
+    /// Writes the manifest of `dir`, removing it when no files are left
+    pub fn write(&self, dir: &Path) -> Result<(), VyperErrors> {
+        let path = dir.join(MANIFEST);
+        if self.files.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        // replaced in one go, so concurrent readers never see a partial manifest
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        let tmp = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, json)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "native")]
+    /// Records `files` as generated for `contract` in the manifest of `dir`
+    pub fn record<'p>(
+        dir: &Path,
+        contract: &Path,
+        files: impl IntoIterator<Item = &'p Path>,
+    ) -> Result<(), VyperErrors> {
+        let _lock = FileLock::acquire(&dir.join(MANIFEST))?;
+        let mut manifest = Manifest::read(dir)?;
+        for file in files {
+            manifest
+                .files
+                .insert(file.to_path_buf(), contract.to_path_buf());
+        }
+        manifest.write(dir)
+    }
+
+    #[cfg(feature = "native")]
+    /// Deletes the files in the manifest of `dir` generated for contracts matching `filter`,
+    /// and returns the ones that were still there
+    pub fn clean(
+        dir: &Path,
+        filter: impl Fn(&Path) -> bool,
+    ) -> Result<Vec<PathBuf>, VyperErrors> {
+        let _lock = FileLock::acquire(&dir.join(MANIFEST))?;
+        let mut manifest = Manifest::read(dir)?;
+        let mut removed = vec![];
+        let mut kept = BTreeMap::new();
+        for (file, contract) in manifest.files {
+            if !filter(&contract) {
+                kept.insert(file, contract);
+                continue;
+            }
+            match fs::remove_file(&file) {
+                Ok(()) => removed.push(file),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => Err(e)?,
+            }
+        }
+        manifest.files = kept;
+        manifest.write(dir)?;
+        Ok(removed)
+    }
+}
@@ -0,0 +1,76 @@
+//! The compiler's assembly output (`vyper -f asm`), split into the parts that end up in the
+//! deployed contract and the parts that don't, for attributing code size to its sources.
+//!
+//! The compiler prints the constructor's assembly with the runtime code nested in braces,
+//! `... RETURN { <runtime> }`. Data, such as the jump tables of the dense selector table, is
+//! introduced by `DATA <label>`.
+
+use serde::{Deserialize, Serialize};
+
+/// `vyper -f asm` output, see the module docs
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Assembly {
+    /// The compiler's output, as is
+    pub raw: String,
+    /// Instructions and labels of the constructor, run once on deployment
+    pub constructor: Vec<String>,
+    /// Instructions and labels of the runtime code, i.e. the deployed contract
+    pub runtime: Vec<String>,
+    /// Data sections of either segment, in order of appearance
+    pub data: Vec<DataSection>,
+}
+
+/// A `DATA <label>` section and its items
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct DataSection {
+    pub label: String,
+    /// Labels and byte strings, as printed by the compiler
+    pub items: Vec<String>,
+    /// Whether the section belongs to the runtime code
+    pub runtime: bool,
+}
+
+impl Assembly {
+    /// Splits `raw` into its segments. Anything nested deeper than the runtime code is
+    /// counted as runtime code.
+    pub fn parse(raw: &str) -> Assembly {
+        let mut assembly = Assembly {
+            raw: raw.to_owned(),
+            ..Default::default()
+        };
+        let mut depth = 0usize;
+        let mut section: Option<DataSection> = None;
+        let mut tokens = raw.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "{" | "}" => {
+                    assembly.data.extend(section.take());
+                    depth = match token {
+                        "{" => depth + 1,
+                        _ => depth.saturating_sub(1),
+                    };
+                }
+                "DATA" => {
+                    assembly.data.extend(section.take());
+                    section = Some(DataSection {
+                        label: tokens.next().unwrap_or_default().to_owned(),
+                        items: Vec::new(),
+                        runtime: depth > 0,
+                    });
+                }
+                _ => match (&mut section, depth) {
+                    (Some(section), _) => section.items.push(token.to_owned()),
+                    (None, 0) => assembly.constructor.push(token.to_owned()),
+                    (None, _) => assembly.runtime.push(token.to_owned()),
+                },
+            }
+        }
+        assembly.data.extend(section);
+        assembly
+    }
+
+    /// Data sections of the runtime code
+    pub fn runtime_data(&self) -> impl Iterator<Item = &DataSection> {
+        self.data.iter().filter(|section| section.runtime)
+    }
+}
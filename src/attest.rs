@@ -0,0 +1,141 @@
+//! Build attestations, written by `Project::attest`: for every contract, a hash of its source,
+//! the compiler and settings it was built with, and a hash of the resulting bytecode, for
+//! deployment and audit pipelines to archive next to what they ship.
+//!
+//! An attestation holds nothing that changes between two builds of the same sources, such as
+//! timestamps or absolute paths, and `canonical_json` always serializes it to the same bytes,
+//! so it can be signed and the signature checked against a rebuild. Signatures are detached,
+//! kept in a `.sig` file next to the attestation, see `Attestation::write_signatures`. With the
+//! `signing` feature, `Attestation::sign` makes them and `Attestation::read_verified` checks
+//! them on load.
+
+use crate::{utils, vyper_errors::VyperErrors};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+/// Version of the attestation format, bumped on incompatible changes
+pub const ATTESTATION_VERSION: u32 = 1;
+
+/// A signature of an attestation's or a registry entry's digest, made with `signing::Signer`
+/// or outside of this crate
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct ArtifactSignature {
+    /// Who signed, e.g. a public key or an address
+    pub signer: String,
+    /// The signature scheme, e.g. `secp256k1` or `ed25519`
+    pub scheme: String,
+    pub signature: String,
+}
+
+/// Where the signatures of the attestation at `path` are kept, `path` with `.sig` appended
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Attestation {
+    pub version: u32,
+    /// The vyper-rs version that built the contracts
+    pub created_by: String,
+    /// Keyed by contract path, relative to the project root
+    pub contracts: BTreeMap<PathBuf, AttestedContract>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct AttestedContract {
+    pub source_sha256: String,
+    /// Output of `vyper --version`
+    pub compiler: String,
+    /// Hash of the installed vyper distribution (see `venv::distribution_sha256`), or of the
+    /// binary for a standalone compiler. `None` when it can't be hashed the same way on every
+    /// machine, like a pip entry point outside of a venv, or doesn't run on this machine, like
+    /// with the docker and remote backends.
+    pub compiler_sha256: Option<String>,
+    /// EVM version the contract was compiled for, `None` when the compiler's default couldn't
+    /// be determined
+    pub evm_version: Option<String>,
+    /// Optimization mode passed with `--optimize`, `None` for the compiler's default
+    pub optimize: Option<String>,
+    /// Hash of the bytecode, metadata included
+    pub bytecode_sha256: String,
+}
+
+impl Attestation {
+    pub fn new(contracts: BTreeMap<PathBuf, AttestedContract>) -> Attestation {
+        Attestation {
+            version: ATTESTATION_VERSION,
+            created_by: format!("vyper-rs {}", env!("CARGO_PKG_VERSION")),
+            contracts,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Attestation, VyperErrors> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    /// Writes the attestation as `canonical_json`, so the file itself can be signed
+    pub fn write(&self, path: &Path) -> Result<(), VyperErrors> {
+        std::fs::write(path, self.canonical_json()?)?;
+        Ok(())
+    }
+
+    /// Compact JSON with fields in declaration order and contracts sorted by path, the bytes to
+    /// sign
+    pub fn canonical_json(&self) -> Result<String, VyperErrors> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Lowercase hex sha256 of `canonical_json`
+    pub fn digest(&self) -> Result<String, VyperErrors> {
+        Ok(utils::sha256_hex(self.canonical_json()?.as_bytes()))
+    }
+
+    /// Writes `signatures` of the attestation at `path` to its `signature_path`
+    pub fn write_signatures(
+        path: &Path,
+        signatures: &[ArtifactSignature],
+    ) -> Result<(), VyperErrors> {
+        std::fs::write(
+            signature_path(path),
+            serde_json::to_string_pretty(signatures)?,
+        )?;
+        Ok(())
+    }
+
+    /// The signatures of the attestation at `path`, none when it has no `signature_path`
+    pub fn read_signatures(path: &Path) -> Result<Vec<ArtifactSignature>, VyperErrors> {
+        match File::open(signature_path(path)) {
+            Ok(file) => Ok(serde_json::from_reader(file)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e)?,
+        }
+    }
+
+    /// Signs the digest of the attestation
+    #[cfg(feature = "signing")]
+    pub fn sign(
+        &self,
+        signer: &crate::signing::Signer,
+    ) -> Result<ArtifactSignature, VyperErrors> {
+        Ok(signer.sign(&self.digest()?))
+    }
+
+    /// Reads the attestation at `path`, failing unless its signatures include a valid one by
+    /// `signer`, a public key as returned by `signing::Signer::public_key`
+    #[cfg(feature = "signing")]
+    pub fn read_verified(path: &Path, signer: &str) -> Result<Attestation, VyperErrors> {
+        let attestation = Attestation::read(path)?;
+        crate::signing::verify_signature(
+            &attestation.digest()?,
+            &Attestation::read_signatures(path)?,
+            signer,
+        )?;
+        Ok(attestation)
+    }
+}
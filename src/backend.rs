@@ -0,0 +1,274 @@
+//! Where the compiler runs. Every compiler invocation in `Vyper`, `Vypers` and `VyperStack` is
+//! built by a `CompilerBackend`, so a new execution environment (a remote build service, a
+//! sandbox, ...) only needs to implement that trait and be passed in with `Backend::custom`.
+//!
+//! By default the native binary resolved by `Vyper::get_vyper` (an explicit compiler path,
+//! the venv, `VYPER_PATH` or PATH) is invoked directly; with the `docker` feature it can instead
+//! run inside one of the official `vyperlang/vyper` images for reproducible builds, and with the
+//! `remote` feature on a compile server over HTTP. On Linux it can be sandboxed with `bwrap` or
+//! `unshare` for compiling untrusted contracts, see `Sandbox`.
+
+#[cfg(target_os = "linux")]
+use crate::vyper_errors::VyperErrors;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    process::Command,
+    sync::Arc,
+};
+
+/// Builds the command that invokes the compiler. The returned command gets the compiler's
+/// arguments (output formats, the contract path, ...) appended and is then run as is.
+pub trait CompilerBackend: Debug + Send + Sync {
+    fn command(&self) -> Command;
+
+    /// Whether the compiler can write files on this machine with `-o`. Otherwise
+    /// `Vyper::compile_to_file` writes the compiler's stdout itself.
+    fn writes_files(&self) -> bool {
+        true
+    }
+}
+
+/// Runs a compiler binary on this machine
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Native {
+    pub bin: String,
+}
+
+impl CompilerBackend for Native {
+    fn command(&self) -> Command {
+        Command::new(&self.bin)
+    }
+}
+
+/// Runs the compiler in a container from `image`. The current directory is mounted at the same
+/// path and used as the working directory, so contract paths must be relative to it or below
+/// it.
+#[cfg(feature = "docker")]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Docker {
+    pub image: String,
+}
+
+#[cfg(feature = "docker")]
+impl CompilerBackend for Docker {
+    fn command(&self) -> Command {
+        let mut cmd = Command::new("docker");
+        cmd.arg("run").arg("--rm");
+        if let Ok(cwd) = std::env::current_dir() {
+            let cwd = cwd.to_string_lossy();
+            cmd.arg("-v")
+                .arg(format!("{0}:{0}", cwd))
+                .arg("-w")
+                .arg(cwd.as_ref());
+        }
+        cmd.arg(&self.image);
+        cmd
+    }
+
+    /// Only the current directory is mounted
+    fn writes_files(&self) -> bool {
+        false
+    }
+}
+
+/// Environment variables passed into the sandbox, every other one is cleared
+#[cfg(target_os = "linux")]
+pub const SANDBOX_ENV: [&str; 3] = ["PATH", "LANG", "LC_ALL"];
+
+/// How `Sandbox` isolates the compiler
+#[cfg(target_os = "linux")]
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxTool {
+    /// bubblewrap: no network, the filesystem mounted read-only, sources included, and a
+    /// private `/tmp`
+    Bwrap,
+    /// util-linux `unshare` in new user and network namespaces: no network, but the
+    /// filesystem is only protected by the permissions of the user
+    Unshare,
+}
+
+#[cfg(target_os = "linux")]
+impl SandboxTool {
+    /// `bwrap` if it's installed. `unshare` doesn't protect the filesystem, so it's only
+    /// considered as a fallback with `allow_unshare`.
+    pub fn detect(allow_unshare: bool) -> Option<SandboxTool> {
+        let tools: &[SandboxTool] = match allow_unshare {
+            true => &[SandboxTool::Bwrap, SandboxTool::Unshare],
+            false => &[SandboxTool::Bwrap],
+        };
+        tools
+            .iter()
+            .copied()
+            .find(|tool| crate::utils::which(tool.bin()).is_some())
+    }
+
+    pub fn bin(&self) -> &'static str {
+        match self {
+            SandboxTool::Bwrap => "bwrap",
+            SandboxTool::Unshare => "unshare",
+        }
+    }
+}
+
+/// Runs the native binary `bin` in a sandbox with `tool`, with the environment reduced to
+/// `SANDBOX_ENV`. The working directory is kept, so relative contract paths still resolve.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Sandbox {
+    pub tool: SandboxTool,
+    pub bin: String,
+}
+
+#[cfg(target_os = "linux")]
+impl CompilerBackend for Sandbox {
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(self.tool.bin());
+        match self.tool {
+            SandboxTool::Bwrap => cmd.args([
+                "--ro-bind",
+                "/",
+                "/",
+                "--dev",
+                "/dev",
+                "--proc",
+                "/proc",
+                "--tmpfs",
+                "/tmp",
+                "--unshare-all",
+                "--die-with-parent",
+                "--new-session",
+            ]),
+            SandboxTool::Unshare => cmd.args(["--map-root-user", "--net", "--ipc"]),
+        };
+        cmd.arg("--").arg(&self.bin).env_clear();
+        for name in SANDBOX_ENV {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+        cmd
+    }
+
+    /// bubblewrap mounts the filesystem read-only
+    fn writes_files(&self) -> bool {
+        self.tool == SandboxTool::Unshare
+    }
+}
+
+/// The backend selected for a contract
+#[derive(
+    Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub enum Backend {
+    /// See `Native`
+    #[default]
+    Native,
+    /// See `Docker`
+    #[cfg(feature = "docker")]
+    Docker { image: String },
+    /// See `remote::RemoteBackend`
+    #[cfg(feature = "remote")]
+    Remote { url: String },
+    /// The native binary in a sandbox, see `Sandbox`
+    #[cfg(target_os = "linux")]
+    Sandbox { tool: SandboxTool },
+    /// A user provided backend. Not serializable.
+    #[serde(skip)]
+    Custom(SharedBackend),
+}
+
+impl Backend {
+    /// The official image for compiler `version`, e.g. `vyperlang/vyper:0.3.10`
+    #[cfg(feature = "docker")]
+    pub fn docker(version: &str) -> Backend {
+        Backend::Docker {
+            image: format!("vyperlang/vyper:{}", version),
+        }
+    }
+
+    /// Compiles with the server at `url`, see `remote::RemoteBackend`
+    #[cfg(feature = "remote")]
+    pub fn remote(url: impl Into<String>) -> Backend {
+        Backend::Remote { url: url.into() }
+    }
+
+    /// The native binary in a `bwrap` sandbox, failing with `SandboxUnavailable` when
+    /// `bwrap` isn't installed
+    #[cfg(target_os = "linux")]
+    pub fn sandbox() -> Result<Backend, VyperErrors> {
+        let tool = SandboxTool::detect(false).ok_or(VyperErrors::SandboxUnavailable)?;
+        Ok(Backend::Sandbox { tool })
+    }
+
+    /// Like `sandbox`, but falls back to the weaker `unshare` sandbox when `bwrap` isn't
+    /// installed
+    #[cfg(target_os = "linux")]
+    pub fn sandbox_or_unshare() -> Result<Backend, VyperErrors> {
+        let tool = SandboxTool::detect(true).ok_or(VyperErrors::SandboxUnavailable)?;
+        Ok(Backend::Sandbox { tool })
+    }
+
+    pub fn custom(backend: impl CompilerBackend + 'static) -> Backend {
+        Backend::Custom(SharedBackend(Arc::new(backend)))
+    }
+
+    /// The backend to invoke, with `bin` being the native binary resolved for the contract
+    pub(crate) fn resolve(&self, bin: String) -> Arc<dyn CompilerBackend> {
+        match self {
+            Backend::Native => Arc::new(Native { bin }),
+            #[cfg(feature = "docker")]
+            Backend::Docker { image } => Arc::new(Docker {
+                image: image.clone(),
+            }),
+            #[cfg(feature = "remote")]
+            Backend::Remote { url } => {
+                Arc::new(crate::remote::RemoteBackend::new(url.clone()))
+            }
+            #[cfg(target_os = "linux")]
+            Backend::Sandbox { tool } => Arc::new(Sandbox { tool: *tool, bin }),
+            Backend::Custom(backend) => Arc::clone(&backend.0),
+        }
+    }
+}
+
+/// A shared `CompilerBackend`. Two of these are equal when they point to the same backend.
+#[derive(Debug, Clone)]
+pub struct SharedBackend(pub Arc<dyn CompilerBackend>);
+
+impl SharedBackend {
+    fn addr(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+}
+
+impl PartialEq for SharedBackend {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
+
+impl Eq for SharedBackend {}
+
+impl PartialOrd for SharedBackend {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SharedBackend {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.addr().cmp(&other.addr())
+    }
+}
+
+impl Hash for SharedBackend {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state)
+    }
+}
@@ -0,0 +1,9 @@
+//! Client for `vyper_rs::remote::RemoteBackend`: compiles on a remote server, taking the same
+//! arguments as the vyper CLI plus `--remote-url <url>`.
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let code =
+        vyper_rs::remote::run(&args, &mut std::io::stdout(), &mut std::io::stderr());
+    std::process::exit(code);
+}
@@ -0,0 +1,201 @@
+//! ERC-5202 blueprint parsing and CBOR metadata stripping. Only `core` and `alloc` are needed,
+//! so the module builds without the default `std` feature, together with `bytecode`, for
+//! on-chain indexers and embedded verifiers that share the parsing code with the rest of the
+//! crate. `utils` re-exports everything here.
+
+use alloc::vec::Vec;
+use core::fmt::Display;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// Parses the ERC-5202 bytecode container format for indexing blueprint contracts.
+///
+/// "A blueprint contract MUST use the preamble 0xFE71<version bits><length encoding bits>. 6 bits are allocated to the version, and 2 bits to the length encoding. The first version begins at 0 (0b000000), and versions increment by 1. The value 0b11 for <length encoding bits> is reserved. In the case that the length bits are 0b11, the third byte is considered a continuation byte (that is, the version requires multiple bytes to encode). The exact encoding of a multi-byte version is left to a future ERC.
+/// A blueprint contract MUST contain at least one byte of initcode.
+/// A blueprint contract MAY insert any bytes (data or code) between the version byte(s) and the initcode. If such variable length data is used, the preamble must be 0xFE71<version bits><length encoding bits><length bytes><data>. The <length encoding bits> represent a number between 0 and 2 (inclusive) describing how many bytes <length bytes> takes, and <length bytes> is the big-endian encoding of the number of bytes that <data> takes."
+///
+/// "ERC-5202: Blueprint contract format," Ethereum Improvement Proposals, no. 5202, June 2022. [Online serial].
+/// Available: https://eips.ethereum.org/EIPS/eip-5202.
+///
+/// Bytes are serialized as `0x` prefixed hex, like `Bytecode`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub erc_version: u8,
+    #[serde(with = "hex_bytes_opt")]
+    pub preamble_data: Option<Vec<u8>>,
+    #[serde(with = "hex_bytes")]
+    pub initcode: Vec<u8>,
+}
+
+impl Blueprint {
+    /// Keccak-256 of the initcode, as used to verify the contracts deployed from the blueprint
+    pub fn initcode_hash(&self) -> [u8; 32] {
+        Keccak256::digest(&self.initcode).into()
+    }
+}
+
+impl Display for Blueprint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ERC-5202 blueprint v{}", self.erc_version)?;
+        if let Some(data) = &self.preamble_data {
+            write!(f, ", preamble data 0x{}", hex::encode(data))?;
+        }
+        write!(f, ", initcode 0x{}", hex::encode(&self.initcode))
+    }
+}
+
+/// Why bytes aren't a valid blueprint. Converted to `VyperErrors::BlueprintError` with the
+/// `std` feature.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum BlueprintError {
+    Empty,
+    /// The `0xFE71` preamble is missing
+    NotABlueprint,
+    /// The length encoding bits are `0b11`
+    ReservedBits,
+    /// The bytes end inside the preamble or its data
+    Truncated,
+    EmptyInitcode,
+}
+
+impl Display for BlueprintError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BlueprintError::Empty => write!(f, "Empty Bytecode"),
+            BlueprintError::NotABlueprint => write!(f, "Not a blueprint!"),
+            BlueprintError::ReservedBits => write!(f, "Reserved bits are set"),
+            BlueprintError::Truncated => write!(f, "Truncated blueprint"),
+            BlueprintError::EmptyInitcode => write!(f, "Empty Initcode!"),
+        }
+    }
+}
+
+mod hex_bytes {
+    use crate::bytecode::Bytecode;
+    use alloc::vec::Vec;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Bytecode(bytes.to_vec()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        Ok(Bytecode::deserialize(deserializer)?.0)
+    }
+}
+
+mod hex_bytes_opt {
+    use crate::bytecode::Bytecode;
+    use alloc::vec::Vec;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        bytes.clone().map(Bytecode).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Ok(Option::<Bytecode>::deserialize(deserializer)?.map(|b| b.0))
+    }
+}
+
+/// Parses `bytecode` as a blueprint, see `Blueprint`
+pub fn parse_blueprint(bytecode: &[u8]) -> Result<Blueprint, BlueprintError> {
+    if bytecode.is_empty() {
+        return Err(BlueprintError::Empty);
+    }
+    if bytecode.len() < 3 || &bytecode[0..2] != b"\xFE\x71" {
+        return Err(BlueprintError::NotABlueprint);
+    }
+
+    let erc_version = (bytecode[2] & 0b11111100) >> 2;
+    let n_length_bytes = (bytecode[2] & 0b11) as usize;
+    if n_length_bytes == 0b11 {
+        return Err(BlueprintError::ReservedBits);
+    }
+
+    let data_start = 3 + n_length_bytes;
+    let data_length = bytecode
+        .get(3..data_start)
+        .ok_or(BlueprintError::Truncated)?
+        .iter()
+        .fold(0usize, |acc, byte| acc << 8 | *byte as usize);
+    let preamble_data = match data_length {
+        0 => None,
+        _ => Some(
+            bytecode
+                .get(data_start..data_start + data_length)
+                .ok_or(BlueprintError::Truncated)?
+                .to_vec(),
+        ),
+    };
+
+    let initcode = bytecode
+        .get(data_start + data_length..)
+        .ok_or(BlueprintError::Truncated)?
+        .to_vec();
+    if initcode.is_empty() {
+        return Err(BlueprintError::EmptyInitcode);
+    }
+    Ok(Blueprint {
+        erc_version,
+        preamble_data,
+        initcode,
+    })
+}
+
+/// `bytecode` without the CBOR metadata the compiler appends to it, which changes with the
+/// compiler version even when the code doesn't. The metadata ends with its length as 2 big
+/// endian bytes: including those 2 bytes since vyper 0.3.10, excluding them before. It's
+/// only recognized when it's a CBOR array or map ending in the `{"vyper": [major, minor,
+/// patch]}` version map; bytecode without it is returned as is.
+pub fn strip_metadata(bytecode: &[u8]) -> &[u8] {
+    let Some(end) = bytecode.len().checked_sub(2) else {
+        return bytecode;
+    };
+    let len = u16::from_be_bytes([bytecode[end], bytecode[end + 1]]) as usize;
+    [len, len + 2]
+        .into_iter()
+        .filter(|len| *len > 2 && *len <= bytecode.len())
+        .map(|len| bytecode.len() - len)
+        // a CBOR array (0.3.10 and later) or map (before)
+        .filter(|start| matches!(bytecode[*start], 0x80..=0xbf))
+        .find(|start| ends_with_vyper_version(&bytecode[*start..end]))
+        .map_or(bytecode, |start| &bytecode[..start])
+}
+
+/// Whether `metadata` ends with the CBOR map `{"vyper": [major, minor, patch]}`
+fn ends_with_vyper_version(metadata: &[u8]) -> bool {
+    // a 1 entry map, the 5 byte text "vyper" and a 3 element array
+    const KEY: &[u8] = b"\xa1\x65vyper\x83";
+    let Some(at) = metadata
+        .windows(KEY.len())
+        .rposition(|window| window == KEY)
+    else {
+        return false;
+    };
+    let mut version = &metadata[at + KEY.len()..];
+    for _ in 0..3 {
+        // unsigned integers, inline below 24 or followed by 1 or 2 bytes
+        let size = match version.first() {
+            Some(0x00..=0x17) => 1,
+            Some(0x18) => 2,
+            Some(0x19) => 3,
+            _ => return false,
+        };
+        let Some(rest) = version.get(size..) else {
+            return false;
+        };
+        version = rest;
+    }
+    version.is_empty()
+}
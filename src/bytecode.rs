@@ -0,0 +1,108 @@
+//! Hex validated EVM bytecode. Parsing accepts hex with or without the `0x` prefix, while
+//! displaying and serializing always produce it, so the prefix never has to be guessed. Built
+//! without the `std` feature too, see `blueprint`.
+
+use crate::blueprint;
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{Debug, Display},
+    ops::{Deref, Range},
+    str::FromStr,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+
+#[derive(Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Bytecode(pub Vec<u8>);
+
+impl Bytecode {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Size in bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Keccak-256 of the bytecode, i.e. the code hash of a contract with this runtime code
+    pub fn code_hash(&self) -> [u8; 32] {
+        Keccak256::digest(&self.0).into()
+    }
+
+    /// The bytes in `range`, `None` when it's out of bounds
+    pub fn slice(&self, range: Range<usize>) -> Option<&[u8]> {
+        self.0.get(range)
+    }
+
+    /// The bytecode without its CBOR metadata, see `blueprint::strip_metadata`
+    pub fn without_metadata(&self) -> &[u8] {
+        blueprint::strip_metadata(&self.0)
+    }
+}
+
+impl FromStr for Bytecode {
+    /// Converted to `VyperErrors::StringParsingError` with the `std` feature
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        hex::decode(s.strip_prefix("0x").unwrap_or(s)).map(Bytecode)
+    }
+}
+
+impl Display for Bytecode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "0x{}", hex::encode(&self.0))
+    }
+}
+
+impl Debug for Bytecode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Bytecode({})", self)
+    }
+}
+
+impl Deref for Bytecode {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Bytecode {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Bytecode {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytecode(bytes)
+    }
+}
+
+impl From<Bytecode> for Vec<u8> {
+    fn from(bytecode: Bytecode) -> Self {
+        bytecode.0
+    }
+}
+
+impl Serialize for Bytecode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytecode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,130 @@
+//! Where the runtime bytecode of a contract goes, for deciding what to trim when it exceeds
+//! the 24KB contract size limit (EIP-170). Run with `Vyper::code_size_report`.
+//!
+//! Code after a halting instruction or jump is unreachable unless a `JUMPDEST` follows, like
+//! the `UnreachableCode` lint. The bytes of an external function are counted from the
+//! dispatcher's check of its selector to the check of the next selector, which matches the
+//! linear dispatcher the compiler emits without jump tables. With jump tables (`-O codesize`
+//! or many functions) the sizes are only an estimate.
+
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, ops::Range};
+
+/// EIP-170 limit on the size of runtime code
+pub const MAX_CODE_SIZE: usize = 24576;
+
+const JUMPDEST: u8 = 0x5b;
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CodeSizeReport {
+    /// Size of the runtime code without its metadata
+    pub total: usize,
+    /// Bytes before the first selector check: the dispatcher and code shared by functions
+    pub dispatcher: usize,
+    /// Bytes per external function, by signature. Functions whose selector check wasn't found
+    /// are left out.
+    pub functions: BTreeMap<String, usize>,
+    /// Byte ranges following a halting instruction or jump that no jump lands on
+    pub unreachable: Vec<Range<usize>>,
+}
+
+impl CodeSizeReport {
+    /// A report of `runtime`, the runtime bytecode, whose functions have the selectors
+    /// `method_identifiers`, i.e. `transfer(address,uint256)` to `0xa9059cbb`
+    pub fn new(
+        runtime: &[u8],
+        method_identifiers: &BTreeMap<String, String>,
+    ) -> CodeSizeReport {
+        let code = utils::strip_metadata(runtime);
+        let instructions = instructions(code);
+        let selectors: BTreeMap<u32, &String> = method_identifiers
+            .iter()
+            .filter_map(|(signature, selector)| {
+                let selector = selector.trim_start_matches("0x");
+                Some((u32::from_str_radix(selector, 16).ok()?, signature))
+            })
+            .collect();
+
+        // first check of each selector, in code order
+        let mut entries: Vec<(usize, &String)> = vec![];
+        for (offset, op, push) in &instructions {
+            if !(0x60..=0x63).contains(op) {
+                continue;
+            }
+            let value = push.iter().fold(0u32, |acc, b| acc << 8 | *b as u32);
+            if let Some(signature) = selectors.get(&value) {
+                if entries.iter().all(|(_, s)| s != signature) {
+                    entries.push((*offset, signature));
+                }
+            }
+        }
+        let mut functions = BTreeMap::new();
+        for (i, (start, signature)) in entries.iter().enumerate() {
+            let end = entries.get(i + 1).map_or(code.len(), |(next, _)| *next);
+            functions.insert(signature.to_string(), end - start);
+        }
+
+        let mut unreachable = vec![];
+        let mut dead: Option<usize> = None;
+        let mut halted = false;
+        for (offset, op, _) in &instructions {
+            if *op == JUMPDEST {
+                if let Some(start) = dead.take() {
+                    unreachable.push(start..*offset);
+                }
+                halted = false;
+            } else if halted {
+                dead.get_or_insert(*offset);
+            } else {
+                // STOP, JUMP, RETURN, REVERT, INVALID, SELFDESTRUCT
+                halted = matches!(op, 0x00 | 0x56 | 0xf3 | 0xfd | 0xfe | 0xff);
+            }
+        }
+
+        CodeSizeReport {
+            total: code.len(),
+            dispatcher: entries.first().map_or(code.len(), |(offset, _)| *offset),
+            functions,
+            unreachable,
+        }
+    }
+
+    /// Bytes left before the EIP-170 limit, negative when the contract exceeds it
+    pub fn headroom(&self) -> isize {
+        MAX_CODE_SIZE as isize - self.total as isize
+    }
+
+    /// Functions from the largest to the smallest
+    pub fn largest(&self) -> Vec<(&str, usize)> {
+        let mut functions: Vec<(&str, usize)> = self
+            .functions
+            .iter()
+            .map(|(signature, size)| (signature.as_str(), *size))
+            .collect();
+        functions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        functions
+    }
+
+    /// Total size of the unreachable ranges
+    pub fn unreachable_bytes(&self) -> usize {
+        self.unreachable.iter().map(|range| range.len()).sum()
+    }
+}
+
+/// Offset, opcode and push data of every instruction of `code`
+pub(crate) fn instructions(code: &[u8]) -> Vec<(usize, u8, &[u8])> {
+    let mut out = vec![];
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = code[offset];
+        let n = match op {
+            0x60..=0x7f => (op - 0x5f) as usize,
+            _ => 0,
+        };
+        let end = (offset + 1 + n).min(code.len());
+        out.push((offset, op, &code[offset + 1..end]));
+        offset = end;
+    }
+    out
+}
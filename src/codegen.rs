@@ -0,0 +1,351 @@
+//! Parameterized contracts: `.vy` templates with `{{NAME}}` placeholders, substituted with
+//! compile-time constants before compiling, e.g. `FEE_BPS: constant(uint256) = {{FEE_BPS}}`.
+//!
+//! Substitution is strict: every placeholder needs a value and every value a placeholder, so a
+//! typo fails the build instead of deploying a contract with a missing parameter. Names are
+//! made of ASCII letters, digits and underscores, and may be padded with spaces.
+//!
+//! Also generates Vyper interface files (`.vyi`) from the ABI of any contract, see
+//! `vyi_from_abi`.
+
+use crate::{
+    abi::{Abi, AbiParam},
+    utils,
+    vyper_errors::VyperErrors,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directory in the system's temp directory rendered templates are written to
+pub const RENDER_DIR: &str = "vyper-rs-codegen";
+
+/// Directory below the project root generated interfaces are written to by default
+pub const INTERFACES_DIR: &str = "interfaces";
+
+/// Maximum length given to `Bytes`, `String` and `DynArray` in generated interfaces, as the ABI
+/// doesn't record one
+pub const DYNAMIC_BOUND: usize = 1024;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Template {
+    /// Where the template was read from, `None` for templates built from a string
+    pub path: Option<PathBuf>,
+    pub source: String,
+}
+
+impl Template {
+    pub fn new(source: impl Into<String>) -> Template {
+        Template {
+            path: None,
+            source: source.into(),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Template, VyperErrors> {
+        Ok(Template {
+            path: Some(path.to_path_buf()),
+            source: fs::read_to_string(path)?,
+        })
+    }
+
+    /// Every placeholder of the template
+    pub fn placeholders(&self) -> BTreeSet<&str> {
+        placeholders(&self.source)
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    /// The source with every placeholder replaced by its value in `values`
+    pub fn render(
+        &self,
+        values: &BTreeMap<String, String>,
+    ) -> Result<String, VyperErrors> {
+        let declared = self.placeholders();
+        let missing: Vec<String> = declared
+            .iter()
+            .filter(|name| !values.contains_key(**name))
+            .map(|name| name.to_string())
+            .collect();
+        let unknown: Vec<String> = values
+            .keys()
+            .filter(|name| !declared.contains(name.as_str()))
+            .cloned()
+            .collect();
+        if !missing.is_empty() || !unknown.is_empty() {
+            Err(VyperErrors::TemplateError { missing, unknown })?
+        }
+        let mut out = String::with_capacity(self.source.len());
+        let mut rest = 0;
+        for (range, name) in placeholders(&self.source) {
+            out.push_str(&self.source[rest..range.start]);
+            out.push_str(&values[name]);
+            rest = range.end;
+        }
+        out.push_str(&self.source[rest..]);
+        Ok(out)
+    }
+
+    /// Renders the template to a file that can be compiled like any other contract. Files are
+    /// named after the hash of their source in `RENDER_DIR`, so rendering the same values
+    /// twice reuses the file. Imports relative to the template don't resolve from there.
+    pub fn render_to_file(
+        &self,
+        values: &BTreeMap<String, String>,
+    ) -> Result<PathBuf, VyperErrors> {
+        let source = self.render(values)?;
+        let name = self
+            .path
+            .as_deref()
+            .and_then(Path::file_name)
+            .map_or_else(|| "contract.vy".into(), |name| name.to_os_string());
+        let dir = std::env::temp_dir()
+            .join(RENDER_DIR)
+            .join(&utils::sha256_hex(source.as_bytes())[..16]);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        if fs::read_to_string(&path).ok().as_deref() != Some(source.as_str()) {
+            fs::write(&path, source)?;
+        }
+        Ok(path)
+    }
+}
+
+/// Byte range and name of every `{{NAME}}` in `source`
+fn placeholders(source: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut out = vec![];
+    let mut from = 0;
+    while let Some(start) = source[from..].find("{{").map(|i| from + i) {
+        let Some(len) = source[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + len + 2;
+        let name = source[start + 2..end - 2].trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            out.push((start..end, name));
+            from = end;
+        } else {
+            from = start + 2;
+        }
+    }
+    out
+}
+
+/// A Vyper interface file declaring the events and external functions of `abi`, so contracts
+/// compiled with any language can be called from vyper. Tuples become structs named after the
+/// parameter, dynamic types are bounded by `DYNAMIC_BOUND` and names that are reserved in vyper,
+/// such as `from`, get a trailing underscore. Constructors and fallbacks are left out. Vyper
+/// has no overloading, so only the first event or function of a name is declared; the others
+/// are replaced by a comment with their signature.
+pub fn vyi_from_abi(abi: &Abi) -> String {
+    let mut structs = Structs::default();
+    let mut events = vec![];
+    let mut functions = vec![];
+    let mut declared = std::collections::HashSet::new();
+    for entry in &abi.0 {
+        let Some(name) = entry.name.as_deref() else {
+            continue;
+        };
+        if matches!(entry.ty.as_str(), "event" | "function")
+            && !declared.insert((entry.ty.as_str(), name))
+        {
+            let overloads = match entry.ty.as_str() {
+                "event" => &mut events,
+                _ => &mut functions,
+            };
+            overloads.push(format!(
+                "# overload {} left out\n",
+                entry.signature().unwrap_or_else(|| name.to_owned())
+            ));
+            continue;
+        }
+        match entry.ty.as_str() {
+            "event" => {
+                let mut out = format!("event {}:\n", name);
+                if entry.inputs.is_empty() {
+                    out.push_str("    pass\n");
+                }
+                for (i, input) in entry.inputs.iter().enumerate() {
+                    let ty = match input.indexed {
+                        true => format!("indexed({})", structs.vyper_type(input)),
+                        false => structs.vyper_type(input),
+                    };
+                    out.push_str(&format!("    {}: {}\n", param_name(input, i), ty));
+                }
+                events.push(out);
+            }
+            "function" => {
+                let mutability =
+                    entry.state_mutability.as_deref().unwrap_or("nonpayable");
+                let inputs: Vec<String> = entry
+                    .inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, input)| {
+                        format!("{}: {}", param_name(input, i), structs.vyper_type(input))
+                    })
+                    .collect();
+                let outputs: Vec<String> = entry
+                    .outputs
+                    .iter()
+                    .map(|o| structs.vyper_type(o))
+                    .collect();
+                let returns = match outputs.len() {
+                    0 => String::new(),
+                    1 => format!(" -> {}", outputs[0]),
+                    _ => format!(" -> ({})", outputs.join(", ")),
+                };
+                functions.push(format!(
+                    "@external\n@{}\ndef {}({}){}:\n    ...\n",
+                    mutability,
+                    name,
+                    inputs.join(", "),
+                    returns
+                ));
+            }
+            _ => {}
+        }
+    }
+    let declarations: Vec<String> = structs
+        .0
+        .into_iter()
+        .map(|(name, members)| format!("struct {}:\n{}", name, members))
+        .chain(events)
+        .chain(functions)
+        .collect();
+    declarations.join("\n")
+}
+
+/// Structs declared for the tuples of an ABI, in the order they must be declared in
+#[derive(Default)]
+struct Structs(Vec<(String, String)>);
+
+impl Structs {
+    fn vyper_type(&mut self, param: &AbiParam) -> String {
+        if let Some((element, len)) = param.array_element() {
+            let element = self.vyper_type(&element);
+            return match len {
+                "" => format!("DynArray[{}, {}]", element, DYNAMIC_BOUND),
+                len => format!("{}[{}]", element, len),
+            };
+        }
+        match param.ty.as_str() {
+            "tuple" => self.declare(param),
+            "bytes" => format!("Bytes[{}]", DYNAMIC_BOUND),
+            "string" => format!("String[{}]", DYNAMIC_BOUND),
+            "fixed168x10" => "decimal".to_owned(),
+            ty => ty.to_owned(),
+        }
+    }
+
+    /// Name of the struct for a tuple, declaring it along with the structs of its members
+    /// unless a struct with the same members was declared already
+    fn declare(&mut self, param: &AbiParam) -> String {
+        let members: String = param
+            .components
+            .iter()
+            .enumerate()
+            .map(|(i, member)| {
+                format!(
+                    "    {}: {}\n",
+                    param_name(member, i),
+                    self.vyper_type(member)
+                )
+            })
+            .collect();
+        if let Some((name, _)) = self.0.iter().find(|(_, m)| *m == members) {
+            return name.clone();
+        }
+        let mut base: String = param
+            .name
+            .trim_start_matches('_')
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            })
+            .collect();
+        if base.is_empty() {
+            base = "Tuple".to_owned();
+        }
+        let mut name = base.clone();
+        let mut n = 1;
+        while self.0.iter().any(|(taken, _)| *taken == name) {
+            n += 1;
+            name = format!("{}{}", base, n);
+        }
+        self.0.push((name.clone(), members));
+        name
+    }
+}
+
+/// Words that can't name a parameter in vyper but commonly do in other languages' ABIs
+const RESERVED: &[&str] = &[
+    "and",
+    "as",
+    "assert",
+    "block",
+    "break",
+    "chain",
+    "class",
+    "constant",
+    "continue",
+    "def",
+    "del",
+    "elif",
+    "else",
+    "empty",
+    "event",
+    "except",
+    "extcall",
+    "finally",
+    "flag",
+    "for",
+    "from",
+    "global",
+    "if",
+    "immutable",
+    "import",
+    "in",
+    "indexed",
+    "interface",
+    "is",
+    "lambda",
+    "len",
+    "log",
+    "max",
+    "min",
+    "msg",
+    "nonlocal",
+    "not",
+    "or",
+    "pass",
+    "public",
+    "raise",
+    "range",
+    "return",
+    "self",
+    "staticcall",
+    "struct",
+    "try",
+    "tx",
+    "while",
+    "with",
+    "yield",
+];
+
+/// The name of the `i`th parameter, `arg{i}` when it has none
+fn param_name(param: &AbiParam, i: usize) -> String {
+    match param.name.as_str() {
+        "" => format!("arg{}", i),
+        name if RESERVED.contains(&name) => format!("{}_", name),
+        name => name.to_owned(),
+    }
+}
@@ -0,0 +1,126 @@
+//! Source lines of the runtime bytecode, the groundwork for coverage tooling: a `CoverageMap`
+//! maps the program counters of a contract's instructions to the source they were compiled
+//! from, so the program counters of a trace, e.g. recorded while replaying transactions
+//! through revm, can be turned into line hits with `CoverageMap::coverage`.
+//!
+//! Locations come from the `pc_pos_map` of the compiler's `source_map` output. Program counters
+//! inside push data or the metadata are left out, as are instructions the compiler generated
+//! without a location, like most of the dispatcher.
+
+use crate::{code_size, utils, vyper::Vyper, vyper_errors::VyperErrors};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A span of the source, lines counted from 1 and columns from 0 as in the compiler's AST
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CoverageMap {
+    /// Source location of each instruction of the runtime code, by program counter
+    pub locations: BTreeMap<usize, SourceLocation>,
+}
+
+/// Hits per line, every line with code included
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LineCoverage {
+    pub hits: BTreeMap<usize, u64>,
+}
+
+impl LineCoverage {
+    /// Lines with code that never ran
+    pub fn uncovered(&self) -> Vec<usize> {
+        self.hits
+            .iter()
+            .filter(|(_, hits)| **hits == 0)
+            .map(|(line, _)| *line)
+            .collect()
+    }
+
+    /// Share of the lines with code that ran, from 0 to 1. A contract without code is fully
+    /// covered.
+    pub fn ratio(&self) -> f64 {
+        if self.hits.is_empty() {
+            return 1.0;
+        }
+        let covered = self.hits.values().filter(|hits| **hits > 0).count();
+        covered as f64 / self.hits.len() as f64
+    }
+}
+
+impl CoverageMap {
+    /// Maps the instructions of `runtime`, the runtime bytecode, to the locations of
+    /// `source_map`, the compiler's `source_map` output for it
+    pub fn new(runtime: &[u8], source_map: &Value) -> Result<CoverageMap, VyperErrors> {
+        let pc_pos_map = source_map["pc_pos_map"]
+            .as_object()
+            .ok_or(VyperErrors::StringParsingError)?;
+        let instructions: BTreeSet<usize> =
+            code_size::instructions(utils::strip_metadata(runtime))
+                .into_iter()
+                .map(|(offset, _, _)| offset)
+                .collect();
+        let locations = pc_pos_map
+            .iter()
+            .filter_map(|(pc, position)| {
+                let pc: usize = pc.parse().ok()?;
+                let position: Vec<usize> =
+                    serde_json::from_value(position.clone()).ok()?;
+                let [line, column, end_line, end_column] = position[..] else {
+                    return None;
+                };
+                instructions.contains(&pc).then_some((
+                    pc,
+                    SourceLocation {
+                        line,
+                        column,
+                        end_line,
+                        end_column,
+                    },
+                ))
+            })
+            .collect();
+        Ok(CoverageMap { locations })
+    }
+
+    /// Location of the instruction at `pc`
+    pub fn location(&self, pc: usize) -> Option<&SourceLocation> {
+        self.locations.get(&pc)
+    }
+
+    /// Lines with code, i.e. that instructions were compiled from
+    pub fn lines(&self) -> BTreeSet<usize> {
+        self.locations.values().map(|l| l.line).collect()
+    }
+
+    /// Line hits of the executed program counters `pcs`, an instruction executed twice
+    /// counting twice
+    pub fn coverage(&self, pcs: impl IntoIterator<Item = usize>) -> LineCoverage {
+        let mut hits: BTreeMap<usize, u64> =
+            self.lines().into_iter().map(|line| (line, 0)).collect();
+        for pc in pcs {
+            if let Some(location) = self.location(pc) {
+                *hits.entry(location.line).or_default() += 1;
+            }
+        }
+        LineCoverage { hits }
+    }
+}
+
+impl TryFrom<&Vyper<'_>> for CoverageMap {
+    type Error = VyperErrors;
+
+    /// Compiles the contract for its runtime bytecode and source map
+    fn try_from(vyper: &Vyper<'_>) -> Result<Self, Self::Error> {
+        let (runtime, source_map) = vyper.runtime_source_map()?;
+        CoverageMap::new(&runtime, &source_map)
+    }
+}
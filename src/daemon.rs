@@ -0,0 +1,235 @@
+//! Experimental: compiling with long-lived compiler workers instead of a new process per
+//! contract. Starting the python interpreter and importing vyper dominates the time it takes to
+//! compile a small contract, which adds up in interactive tooling such as language servers and
+//! watch modes.
+//!
+//! A worker is a python process that imports vyper once, then reads one standard-json input per
+//! line on stdin and writes one standard-json output per line on stdout (see `WORKER_SCRIPT`).
+//! A `Daemon` keeps idle workers around and starts new ones when every worker is busy. Workers
+//! exit when the daemon is dropped.
+//!
+//! Jobs are sent as standard-json, so the limitations of `standard_json` apply. The contract's
+//! timeout, retry policy and backend aren't used.
+
+use crate::{
+    artifacts::{CompiledContract, BUILD_FORMATS},
+    audit,
+    bytecode::Bytecode,
+    process::Exit,
+    standard_json::{render_output, standard_json_input},
+    venv::Venv,
+    vyper::Vyper,
+    vyper_errors::VyperErrors,
+};
+use serde_json::Value;
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::Mutex,
+};
+
+/// The program run by every worker. It first writes `{"version": ...}`, or `{"error": ...}`
+/// if vyper can't be imported, then answers every input line with an output line.
+pub const WORKER_SCRIPT: &str = r#"import json, sys
+try:
+    import vyper
+    from vyper.cli.vyper_json import compile_json
+except Exception as e:
+    print(json.dumps({"error": "%s: %s" % (type(e).__name__, e)}), flush=True)
+    sys.exit(1)
+print(json.dumps({"version": vyper.__version__}), flush=True)
+for line in sys.stdin:
+    try:
+        out = compile_json(json.loads(line))
+    except Exception as e:
+        message = "%s: %s" % (type(e).__name__, e)
+        out = {"errors": [{"severity": "error", "type": type(e).__name__, "message": str(e), "formattedMessage": message}]}
+    print(json.dumps(out), flush=True)
+"#;
+
+/// A running worker, see the module docs
+#[derive(Debug)]
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    version: String,
+    /// Written to the audit log when the worker exits
+    audit: Option<audit::PendingRecord>,
+}
+
+impl Worker {
+    fn spawn(python: &Path) -> Result<Worker, VyperErrors> {
+        let mut cmd = Command::new(python);
+        cmd.arg("-u")
+            .arg("-c")
+            .arg(WORKER_SCRIPT)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        let audit = audit::start(&cmd);
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                if let Some(audit) = audit {
+                    audit.finish(None, Some(e.to_string()));
+                }
+                return Err(e.into());
+            }
+        };
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take())
+        else {
+            Err(VyperErrors::CompilerError(
+                "Failed to connect to the compiler worker".to_owned(),
+            ))?
+        };
+        let mut worker = Worker {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            version: String::new(),
+            audit,
+        };
+        let hello = worker.read()?;
+        match hello["version"].as_str() {
+            Some(version) => worker.version = version.to_owned(),
+            None => Err(VyperErrors::CompilerError(format!(
+                "The compiler worker failed to start: {}",
+                hello["error"].as_str().unwrap_or("no version reported")
+            )))?,
+        }
+        Ok(worker)
+    }
+
+    /// Sends a standard-json input and waits for its output
+    fn compile(&mut self, input: &Value) -> Result<Value, VyperErrors> {
+        writeln!(self.stdin, "{}", serde_json::to_string(input)?)?;
+        self.stdin.flush()?;
+        self.read()
+    }
+
+    fn read(&mut self) -> Result<Value, VyperErrors> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            Err(VyperErrors::CompilerError(
+                "The compiler worker exited unexpectedly".to_owned(),
+            ))?
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let status = self.child.wait();
+        if let Some(audit) = self.audit.take() {
+            match status {
+                Ok(status) => audit.finish(Some((&status).into()), None),
+                Err(e) => audit.finish(None, Some(e.to_string())),
+            }
+        }
+    }
+}
+
+/// A pool of compiler workers running `python`, see the module docs
+#[derive(Debug)]
+pub struct Daemon {
+    python: PathBuf,
+    idle: Mutex<Vec<Worker>>,
+}
+
+impl Daemon {
+    /// A daemon running the vyper installed for `python`. No worker is started until the first
+    /// job, see `warm`.
+    pub fn new(python: impl Into<PathBuf>) -> Daemon {
+        Daemon {
+            python: python.into(),
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A daemon running the vyper installed in the venv at `venv`
+    pub fn for_venv(venv: &Path) -> Daemon {
+        Daemon::new(Venv::new(venv).python_bin())
+    }
+
+    /// Starts workers until `workers` are idle, so the first jobs don't wait for them
+    pub fn warm(&self, workers: usize) -> Result<(), VyperErrors> {
+        let mut idle = self.idle.lock().unwrap();
+        while idle.len() < workers {
+            idle.push(Worker::spawn(&self.python)?);
+        }
+        Ok(())
+    }
+
+    /// Number of workers waiting for a job
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// The vyper version the workers run, e.g. `0.3.10`
+    pub fn version(&self) -> Result<String, VyperErrors> {
+        let worker = self.take()?;
+        let version = worker.version.clone();
+        self.idle.lock().unwrap().push(worker);
+        Ok(version)
+    }
+
+    /// Compiles `contract` like `Vyper::compile`, returning its bytecode
+    pub fn compile(&self, contract: &Vyper) -> Result<Bytecode, VyperErrors> {
+        Ok(self.output(contract, &["bytecode"])?.parse()?)
+    }
+
+    /// Compiles `contract` like `Vyper::build`
+    pub fn build(&self, contract: &Vyper) -> Result<CompiledContract, VyperErrors> {
+        let formats: Vec<&str> = BUILD_FORMATS.split(',').collect();
+        CompiledContract::from_output(
+            contract.path_to_code,
+            &self.output(contract, &formats)?,
+        )
+    }
+
+    /// The outputs `formats` of `contract`, one per line, as printed by `vyper -f`
+    pub fn output(
+        &self,
+        contract: &Vyper,
+        formats: &[&str],
+    ) -> Result<String, VyperErrors> {
+        let path = contract.path_to_code;
+        let formats: Vec<String> = formats.iter().map(|f| f.to_string()).collect();
+        let input = standard_json_input(
+            path,
+            &std::fs::read_to_string(path)?,
+            &formats,
+            contract.evm_version.as_ref().map(|evm| evm.as_str()),
+            contract.optimize.map(|o| o.to_string()).as_deref(),
+        )
+        .map_err(VyperErrors::CompilerError)?;
+        let mut worker = self.take()?;
+        // A worker that failed mid job is dropped rather than returned to the pool
+        let output = worker.compile(&input)?;
+        let version = worker.version.clone();
+        self.idle.lock().unwrap().push(worker);
+        render_output(&output, path, &formats).map_err(|stderr| {
+            VyperErrors::CompilationFailed {
+                contract: path.to_path_buf(),
+                command: format!("{} -c <worker>", self.python.display()),
+                version: Some(version),
+                exit: Exit::Unknown,
+                stdout: Box::default(),
+                stderr,
+            }
+        })
+    }
+
+    /// An idle worker, or a new one when every worker is busy
+    fn take(&self) -> Result<Worker, VyperErrors> {
+        let idle = self.idle.lock().unwrap().pop();
+        match idle {
+            Some(worker) => Ok(worker),
+            None => Worker::spawn(&self.python),
+        }
+    }
+}
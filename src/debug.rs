@@ -0,0 +1,150 @@
+//! Human readable calls and reverts: `decode_call` and `decode_revert` translate calldata and
+//! return data observed on-chain, e.g. in a trace or a failed transaction, with the ABI of
+//! the compiled contract. Values are represented as described in `abi::decode`.
+
+use crate::{
+    abi::{self, Abi, AbiEntry, AbiParam},
+    vyper_errors::VyperErrors,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha3::{Digest, Keccak256};
+use std::fmt::Display;
+
+/// Selector of `Error(string)`, raised by `assert` and `raise` with a reason
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`, raised by Solidity contracts on failed checks
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// A call to a function of the ABI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodedCall {
+    /// e.g. `transfer(address,uint256)`
+    pub signature: String,
+    /// Each argument's name and value, in order
+    pub args: Vec<(String, Value)>,
+}
+
+impl Display for DecodedCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.signature.split('(').next().unwrap_or_default();
+        write!(f, "{}(", name)?;
+        for (i, (arg, value)) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            if !arg.is_empty() {
+                write!(f, "{}=", arg)?;
+            }
+            match value {
+                Value::String(s) => write!(f, "{}", s)?,
+                value => write!(f, "{}", value)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/// Why a call reverted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Revert {
+    /// No return data, e.g. an `assert` without a reason or running out of gas
+    Empty,
+    /// `Error(string)` with its reason
+    Reason(String),
+    /// `Panic(uint256)` with its code, as a decimal string
+    Panic(String),
+    /// A custom error of the ABI
+    Custom(DecodedCall),
+    /// Return data matching nothing known, `0x` prefixed hex
+    Unknown(String),
+}
+
+impl Display for Revert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Revert::Empty => write!(f, "reverted without a reason"),
+            Revert::Reason(reason) => write!(f, "reverted: {}", reason),
+            Revert::Panic(code) => write!(f, "panicked with code {}", code),
+            Revert::Custom(error) => write!(f, "reverted with {}", error),
+            Revert::Unknown(data) => write!(f, "reverted with unknown data {}", data),
+        }
+    }
+}
+
+/// Decodes `calldata` as a call to one of the functions of `abi`, picked by its selector
+pub fn decode_call(abi: &Abi, calldata: &[u8]) -> Result<DecodedCall, VyperErrors> {
+    let (selector, data) = split_selector(calldata).ok_or_else(|| {
+        VyperErrors::AbiDecodeError("calldata shorter than a selector".to_owned())
+    })?;
+    let entry = find(abi, "function", selector).ok_or_else(|| {
+        VyperErrors::AbiDecodeError(format!(
+            "no function with selector 0x{} in the ABI",
+            hex::encode(selector)
+        ))
+    })?;
+    decode_entry(entry, data)
+}
+
+/// Decodes `return_data` of a reverted call, recognizing `Error(string)`, `Panic(uint256)` and
+/// the errors of `abi`. Fails only when the data has a known selector but doesn't decode.
+pub fn decode_revert(abi: &Abi, return_data: &[u8]) -> Result<Revert, VyperErrors> {
+    let Some((selector, data)) = split_selector(return_data) else {
+        return Ok(match return_data.is_empty() {
+            true => Revert::Empty,
+            false => Revert::Unknown(format!("0x{}", hex::encode(return_data))),
+        });
+    };
+    let param = |ty: &str| AbiParam {
+        name: String::new(),
+        ty: ty.to_owned(),
+        components: vec![],
+        indexed: false,
+    };
+    if selector == ERROR_SELECTOR {
+        let reason = abi::decode(&[param("string")], data)?.remove(0);
+        return Ok(Revert::Reason(
+            reason.as_str().unwrap_or_default().to_owned(),
+        ));
+    }
+    if selector == PANIC_SELECTOR {
+        let code = abi::decode(&[param("uint256")], data)?.remove(0);
+        return Ok(Revert::Panic(code.as_str().unwrap_or_default().to_owned()));
+    }
+    match find(abi, "error", selector) {
+        Some(entry) => Ok(Revert::Custom(decode_entry(entry, data)?)),
+        None => Ok(Revert::Unknown(format!("0x{}", hex::encode(return_data)))),
+    }
+}
+
+/// The 4 byte selector of a function or error signature
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = Keccak256::digest(signature);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn split_selector(data: &[u8]) -> Option<([u8; 4], &[u8])> {
+    let selector = data.get(..4)?.try_into().ok()?;
+    Some((selector, &data[4..]))
+}
+
+fn find<'a>(abi: &'a Abi, ty: &str, wanted: [u8; 4]) -> Option<&'a AbiEntry> {
+    abi.0.iter().filter(|entry| entry.ty == ty).find(|entry| {
+        entry
+            .signature()
+            .map_or(false, |signature| selector(&signature) == wanted)
+    })
+}
+
+fn decode_entry(entry: &AbiEntry, data: &[u8]) -> Result<DecodedCall, VyperErrors> {
+    let values = abi::decode(&entry.inputs, data)?;
+    Ok(DecodedCall {
+        signature: entry.signature().unwrap_or_default(),
+        args: entry
+            .inputs
+            .iter()
+            .map(|input| input.name.clone())
+            .zip(values)
+            .collect(),
+    })
+}
@@ -0,0 +1,38 @@
+//! Deploying compiled contracts with ethers. Enabled by the `ethers` feature.
+
+use crate::{vyper::Vyper, vyper_errors::VyperErrors};
+use ethers::{
+    abi::{Abi, Tokenize},
+    contract::ContractFactory,
+    providers::Middleware,
+    types::{Address, Bytes},
+};
+use std::{fs::File, sync::Arc};
+
+/// Deploys `contract` with `client`, passing `args` to its constructor, and returns the
+/// address it was deployed at. The contract must be compiled and its ABI generated, i.e. with
+/// `compile` and `gen_abi`.
+pub async fn deploy<M, T>(
+    contract: &Vyper<'_>,
+    client: Arc<M>,
+    args: T,
+) -> Result<Address, VyperErrors>
+where
+    M: Middleware + 'static,
+    T: Tokenize,
+{
+    let abi: Abi = serde_json::from_reader(File::open(&contract.abi)?)?;
+    let Some(bytecode) = &contract.bytecode else {
+        Err(VyperErrors::DeploymentFailed(format!(
+            "{} hasn't been compiled",
+            contract.path_to_code.display()
+        )))?
+    };
+    let deployed = ContractFactory::new(abi, Bytes::from(bytecode.0.clone()), client)
+        .deploy(args)
+        .map_err(|e| VyperErrors::DeploymentFailed(e.to_string()))?
+        .send()
+        .await
+        .map_err(|e| VyperErrors::DeploymentFailed(e.to_string()))?;
+    Ok(deployed.address())
+}
@@ -0,0 +1,289 @@
+//! Structured compiler diagnostics. Errors reported by the vyper compiler are parsed out of its
+//! stderr so that editors and CI annotators can consume them without scraping CLI output.
+
+use crate::vyper_errors::{Exit, VyperErrors};
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single message produced while compiling a contract.
+///
+/// The JSON form produced by `to_json` is stable: fields are only ever added, and optional
+/// fields are always present (as `null` when unknown). `line` is 1-based and `column` 0-based,
+/// exactly as printed by the compiler.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the vyper exception, e.g. `StructureException`
+    pub code: Option<String>,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    /// The compiler output the diagnostic was parsed from
+    pub rendered: String,
+}
+
+impl Diagnostic {
+    /// Parses the first error out of the compiler's stderr for `contract`. Anything that doesn't
+    /// look like a vyper exception is kept as the message verbatim.
+    pub fn from_stderr(contract: &Path, stderr: &str) -> Diagnostic {
+        let exception = stderr.lines().find_map(|line| {
+            let (code, message) = line.trim().split_once(": ")?;
+            let code = code.strip_prefix("vyper.exceptions.")?;
+            Some((code.to_owned(), message.trim().to_owned()))
+        });
+        let (code, message) = match exception {
+            Some((code, message)) => (Some(code), message),
+            None => (None, stderr.trim().to_owned()),
+        };
+        let location = stderr.lines().find_map(location);
+        let (line, column) =
+            (location.map(|(line, _)| line), location.map(|(_, col)| col));
+
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            file: Some(contract.to_path_buf()),
+            line,
+            column,
+            rendered: stderr.to_owned(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, VyperErrors> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Renders the diagnostic against the contract's source, with the offending line excerpted
+    /// and a caret under the reported column. Falls back to the raw compiler output when the
+    /// diagnostic has no file or location.
+    #[cfg(feature = "pretty-errors")]
+    pub fn render_pretty(&self, color: bool) -> Result<String, VyperErrors> {
+        match &self.file {
+            Some(file) if self.line.is_some() => {
+                Ok(self.render_source(&std::fs::read_to_string(file)?, color))
+            }
+            _ => Ok(self.rendered.clone()),
+        }
+    }
+
+    /// Same as `render_pretty`, with the contract's source passed in instead of read from disk
+    #[cfg(feature = "pretty-errors")]
+    pub fn render_source(&self, source: &str, color: bool) -> String {
+        use ariadne::{Color, Config, IndexType, Label, Report, ReportKind, Source};
+
+        let (Some(line), Some(column)) = (self.line, self.column) else {
+            return self.rendered.clone();
+        };
+        let name = self
+            .file
+            .as_deref()
+            .map(|f| f.display().to_string())
+            .unwrap_or_default();
+        let Range { start, end } = span(source, line, column);
+        let (kind, label_color) = match self.severity {
+            Severity::Error => (ReportKind::Error, Color::Red),
+            Severity::Warning => (ReportKind::Warning, Color::Yellow),
+        };
+
+        let mut report = Report::build(kind, name.as_str(), start)
+            .with_config(
+                Config::default()
+                    .with_color(color)
+                    .with_index_type(IndexType::Byte),
+            )
+            .with_message(&self.message)
+            .with_label(
+                Label::new((name.as_str(), start..end))
+                    .with_message(&self.message)
+                    .with_color(label_color),
+            );
+        if let Some(code) = &self.code {
+            report = report.with_code(code);
+        }
+        let mut out = Vec::new();
+        match report
+            .finish()
+            .write((name.as_str(), Source::from(source)), &mut out)
+        {
+            Ok(()) => String::from_utf8_lossy(&out).to_string(),
+            Err(_) => self.rendered.clone(),
+        }
+    }
+}
+
+impl From<&VyperErrors> for Diagnostic {
+    fn from(err: &VyperErrors) -> Self {
+        match err {
+            VyperErrors::CompilationFailed {
+                contract,
+                exit: Exit::Signal(signal),
+                ..
+            } => Diagnostic {
+                severity: Severity::Error,
+                code: None,
+                message: format!("the compiler was killed by signal {}", signal),
+                file: Some(contract.clone()),
+                line: None,
+                column: None,
+                rendered: err.to_string(),
+            },
+            VyperErrors::CompilationFailed {
+                contract, stderr, ..
+            } => Diagnostic::from_stderr(contract, stderr),
+            other => Diagnostic {
+                severity: Severity::Error,
+                code: None,
+                message: other.to_string(),
+                file: None,
+                line: None,
+                column: None,
+                rendered: other.to_string(),
+            },
+        }
+    }
+}
+
+/// A zero-based position as defined by the Language Server Protocol. `character` counts UTF-16
+/// code units.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+pub struct LspRange {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// A `Diagnostic` in the shape of the LSP `Diagnostic` interface, so it can be forwarded to an
+/// editor as-is.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct LspDiagnostic {
+    pub range: LspRange,
+    /// 1 for errors and 2 for warnings, per `DiagnosticSeverity`
+    pub severity: u8,
+    pub code: Option<String>,
+    /// Always `vyper`
+    pub source: String,
+    pub message: String,
+}
+
+impl LspDiagnostic {
+    /// Converts `diagnostic`, using `source` (the contract's text) to translate byte columns to
+    /// UTF-16 and to extend the range over the offending identifier. Diagnostics without a
+    /// location are reported at the start of the file.
+    pub fn new(diagnostic: &Diagnostic, source: &str) -> LspDiagnostic {
+        let range = match (diagnostic.line, diagnostic.column) {
+            (Some(line), Some(column)) => {
+                let Range { start, end } = span(source, line, column);
+                LspRange {
+                    start: position(source, start),
+                    end: position(source, end),
+                }
+            }
+            _ => LspRange::default(),
+        };
+        LspDiagnostic {
+            range,
+            severity: match diagnostic.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+            },
+            code: diagnostic.code.clone(),
+            source: "vyper".to_owned(),
+            message: diagnostic.message.clone(),
+        }
+    }
+}
+
+/// Serializes `diagnostics` as newline-delimited JSON, one diagnostic per line.
+pub fn to_json_lines(diagnostics: &[Diagnostic]) -> Result<String, VyperErrors> {
+    let mut out = String::new();
+    for diagnostic in diagnostics {
+        out.push_str(&diagnostic.to_json()?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Byte range of the identifier at 1-based `line` and 0-based byte `column` in `source`, or a
+/// single byte when the location doesn't point at an identifier.
+fn span(source: &str, line: u32, column: u32) -> Range<usize> {
+    let start = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1) as usize)
+        .map(str::len)
+        .sum::<usize>()
+        + column as usize;
+    let mut start = start.min(source.len());
+    while !source.is_char_boundary(start) {
+        start -= 1;
+    }
+    let len = source[start..]
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(source.len() - start)
+        .max(1);
+    let mut end = (start + len).min(source.len());
+    while !source.is_char_boundary(end) {
+        end += 1;
+    }
+    start..end
+}
+
+/// LSP position of the byte `offset` in `source`
+fn position(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// Finds `line <line>:<column>` in a line of compiler output.
+fn location(line: &str) -> Option<(u32, u32)> {
+    let (_, rest) = line.split_once("line ")?;
+    let rest = rest.split(|c: char| c.is_whitespace() || c == ',').next()?;
+    let (line, column) = rest.split_once(':')?;
+    Some((line.parse().ok()?, column.parse().ok()?))
+}
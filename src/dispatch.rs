@@ -0,0 +1,216 @@
+//! The order in which the dispatcher checks function selectors and what reaching each
+//! function costs, for putting hot functions first. Run with
+//! `Vyper::estimate_selector_gas_overhead`.
+//!
+//! Selector checks are found in the runtime bytecode like in the `code_size` module. Every
+//! selector checked before a function's own costs the comparison and a jump to the next check,
+//! so with the linear dispatcher the compiler emits without jump tables, functions checked
+//! first are the cheapest to call. With jump tables (`-O codesize` or many functions) only the
+//! checks within a bucket are counted: a check that isn't the target of the jump taken when
+//! the previous one fails starts a new bucket.
+//!
+//! Gas is the static cost of the instructions, memory expansion aside. Calldata decoding is
+//! estimated from the ABI: loading every head word, validating types narrower than a word and
+//! the offsets of dynamic types, and checking the calldata size.
+
+use crate::{
+    abi::{Abi, AbiParam},
+    code_size, utils,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// `PUSH` of the offset and `CALLDATALOAD` of a head word
+pub const LOAD_GAS: u64 = 6;
+/// Validating an argument: `DUP1 PUSH1 SHR ISZERO PUSH2 JUMPI`
+pub const CLAMP_GAS: u64 = 25;
+/// Checking the calldata holds every head word: `PUSH CALLDATASIZE LT PUSH2 JUMPI`
+pub const SIZE_CHECK_GAS: u64 = 21;
+
+const JUMPDEST: u8 = 0x5b;
+const JUMPI: u8 = 0x57;
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DispatchReport {
+    /// Gas spent before the first selector check, loading and shifting the selector
+    pub dispatcher_gas: u64,
+    /// External functions in the order the dispatcher checks them. Functions whose selector
+    /// check wasn't found are left out.
+    pub selectors: Vec<SelectorDispatch>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SelectorDispatch {
+    pub signature: String,
+    /// e.g. `0xa9059cbb`
+    pub selector: String,
+    /// Offset of the selector check in the runtime code
+    pub offset: usize,
+    /// Gas spent from the start of the code until the dispatcher jumps into the function
+    pub dispatch_gas: u64,
+    /// Words of the calldata after the selector, dynamic data aside
+    pub head_words: usize,
+    /// Estimated gas of decoding the arguments
+    pub decoding_gas: u64,
+}
+
+impl SelectorDispatch {
+    /// Gas spent before the function's own code runs
+    pub fn overhead(&self) -> u64 {
+        self.dispatch_gas + self.decoding_gas
+    }
+}
+
+impl DispatchReport {
+    /// A report of `runtime`, the runtime bytecode, whose functions have the selectors
+    /// `method_identifiers` and the arguments in `abi`
+    pub fn new(
+        runtime: &[u8],
+        method_identifiers: &BTreeMap<String, String>,
+        abi: &Abi,
+    ) -> DispatchReport {
+        let instructions = code_size::instructions(utils::strip_metadata(runtime));
+        let selectors: BTreeMap<u32, (&String, &String)> = method_identifiers
+            .iter()
+            .filter_map(|(signature, selector)| {
+                let value = u32::from_str_radix(selector.trim_start_matches("0x"), 16);
+                Some((value.ok()?, (signature, selector)))
+            })
+            .collect();
+        let inputs: BTreeMap<String, &[AbiParam]> = abi
+            .0
+            .iter()
+            .filter(|entry| entry.ty == "function")
+            .filter_map(|entry| Some((entry.signature()?, entry.inputs.as_slice())))
+            .collect();
+
+        let mut report = DispatchReport::default();
+        // gas of the checks of the selectors before the current one in its bucket, all failing
+        let mut failed_checks = 0;
+        // where the last failed check jumps to, and the last jump destination seen
+        let (mut next_check, mut jumpdest) = (None, None);
+        let mut check: Option<(usize, u64, &String, &String)> = None;
+        // the last value pushed by the current check, the destination of its jump
+        let mut target = None;
+        for (offset, op, push) in &instructions {
+            if *op == JUMPDEST {
+                jumpdest = Some(*offset);
+            }
+            if let Some((start, gas, signature, selector)) = check.as_mut() {
+                *gas += static_gas(*op);
+                if (0x60..=0x7f).contains(op) {
+                    target =
+                        Some(push.iter().fold(0usize, |acc, b| acc << 8 | *b as usize));
+                }
+                if *op == JUMPI {
+                    let params = inputs.get(signature.as_str()).copied().unwrap_or(&[]);
+                    let head_words = params.iter().map(|p| p.head_size() / 32).sum();
+                    report.selectors.push(SelectorDispatch {
+                        signature: signature.to_string(),
+                        selector: selector.to_lowercase(),
+                        offset: *start,
+                        dispatch_gas: report.dispatcher_gas + failed_checks + *gas,
+                        head_words,
+                        decoding_gas: decoding_gas(params, head_words),
+                    });
+                    failed_checks += *gas + static_gas(JUMPDEST);
+                    next_check = target.take();
+                    check = None;
+                }
+                continue;
+            }
+            if (0x60..=0x63).contains(op) {
+                let value = push.iter().fold(0u32, |acc, b| acc << 8 | *b as u32);
+                if let Some((signature, selector)) = selectors.get(&value) {
+                    if report.selectors.iter().all(|s| &s.signature != *signature) {
+                        if !report.selectors.is_empty() && next_check != jumpdest {
+                            failed_checks = 0;
+                        }
+                        check = Some((*offset, static_gas(*op), signature, selector));
+                        continue;
+                    }
+                }
+            }
+            if report.selectors.is_empty() {
+                report.dispatcher_gas += static_gas(*op);
+            }
+        }
+        report
+    }
+
+    /// Functions from the most to the least expensive to reach, the candidates for being
+    /// moved up when they're called often
+    pub fn costliest(&self) -> Vec<&SelectorDispatch> {
+        let mut selectors: Vec<&SelectorDispatch> = self.selectors.iter().collect();
+        selectors.sort_by(|a, b| {
+            b.overhead()
+                .cmp(&a.overhead())
+                .then(a.signature.cmp(&b.signature))
+        });
+        selectors
+    }
+}
+
+fn decoding_gas(params: &[AbiParam], head_words: usize) -> u64 {
+    if params.is_empty() {
+        return 0;
+    }
+    let clamps: u64 = params.iter().map(clamps).sum();
+    head_words as u64 * LOAD_GAS + clamps * CLAMP_GAS + SIZE_CHECK_GAS
+}
+
+/// Number of validations decoding the type takes: one per value narrower than a word and
+/// one per offset of dynamic data
+fn clamps(param: &AbiParam) -> u64 {
+    if param.is_dynamic() {
+        return 1;
+    }
+    if let Some((element, len)) = param.array_element() {
+        return clamps(&element) * len.parse::<u64>().unwrap_or(0);
+    }
+    if param.ty == "tuple" {
+        return param.components.iter().map(clamps).sum();
+    }
+    let bits = |prefix: &str| param.ty.strip_prefix(prefix)?.parse::<u32>().ok();
+    let narrow = match param.ty.as_str() {
+        "address" | "bool" => true,
+        _ => {
+            bits("uint").or(bits("int")).map_or(false, |n| n < 256)
+                || bits("bytes").map_or(false, |n| n < 32)
+        }
+    };
+    narrow as u64
+}
+
+/// Fixed gas of the instruction `op`, the base cost of those with a dynamic part
+fn static_gas(op: u8) -> u64 {
+    match op {
+        0x00 | 0xf3 | 0xfd => 0,
+        JUMPDEST => 1,
+        // ADDRESS, ORIGIN, CALLER, CALLVALUE, CALLDATASIZE, CODESIZE, GASPRICE, COINBASE to
+        // BASEFEE, POP, PC, MSIZE, GAS, RETURNDATASIZE, PUSH0
+        0x30
+        | 0x32..=0x34
+        | 0x36
+        | 0x38
+        | 0x3a
+        | 0x3d
+        | 0x41..=0x48
+        | 0x50
+        | 0x58..=0x5a
+        | 0x5f => 2,
+        // MUL, DIV, SDIV, MOD, SMOD, SIGNEXTEND
+        0x02..=0x07 | 0x0b => 5,
+        0x08 | 0x09 | 0x56 => 8,
+        0x0a | 0x57 => 10,
+        0x20 => 30,
+        // account and storage access, warm, and calls
+        0x31 | 0x3b | 0x3c | 0x3f | 0x54 | 0x55 | 0x5c | 0x5d | 0xf1 | 0xf2 | 0xf4
+        | 0xfa => 100,
+        0x40 => 20,
+        0xf0 | 0xf5 => 32000,
+        0xa0..=0xa4 => 375,
+        0xff => 5000,
+        _ => 3,
+    }
+}
@@ -0,0 +1,211 @@
+//! Running compiled contracts against the state of a live chain, behind the `rpc` and `revm`
+//! features. `ForkFixture` deploys contracts into a local revm whose accounts and storage are
+//! fetched from a node at a fixed block, giving Rust integration tests mainnet context without
+//! external tools. Also exported from the `testing` module.
+
+use crate::{
+    artifacts::CompiledContract, bytecode, gas::GAS_LIMIT, utils,
+    vyper_errors::VyperErrors,
+};
+use revm::{
+    db::CacheDB,
+    primitives::{
+        AccountInfo, Address, BlockEnv, Bytecode, ExecutionResult, Output, TxKind, B256,
+        U256,
+    },
+    DatabaseRef, Evm,
+};
+use serde_json::{json, Value};
+
+/// Accounts, storage and block hashes of the chain behind `rpc_url` at `block`, fetched with
+/// JSON-RPC as revm reads them. Wrapped in a `CacheDB` by `ForkFixture`, so each is fetched
+/// once.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ForkDB {
+    pub rpc_url: String,
+    pub block: u64,
+}
+
+impl ForkDB {
+    fn request(&self, calls: &[(&str, Value)]) -> Result<Vec<Value>, VyperErrors> {
+        utils::rpc_batch(&self.rpc_url, calls)?
+            .into_iter()
+            .collect()
+    }
+
+    fn block_tag(&self) -> String {
+        format!("{:#x}", self.block)
+    }
+}
+
+impl DatabaseRef for ForkDB {
+    type Error = VyperErrors;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, VyperErrors> {
+        let params = json!([format!("{:#x}", address), self.block_tag()]);
+        let values = self.request(&[
+            ("eth_getBalance", params.clone()),
+            ("eth_getTransactionCount", params.clone()),
+            ("eth_getCode", params),
+        ])?;
+        let code = Bytecode::new_raw(decode_hex(&values[2])?.into());
+        Ok(Some(AccountInfo {
+            balance: decode_u256(&values[0])?,
+            nonce: decode_u256(&values[1])?.to(),
+            code_hash: code.hash_slow(),
+            code: Some(code),
+        }))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, VyperErrors> {
+        // `basic_ref` always returns the code, so revm never looks it up by hash
+        Err(VyperErrors::RpcError(format!(
+            "code {} can't be fetched by its hash",
+            code_hash
+        )))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, VyperErrors> {
+        let params = json!([
+            format!("{:#x}", address),
+            format!("{:#x}", index),
+            self.block_tag()
+        ]);
+        decode_u256(&self.request(&[("eth_getStorageAt", params)])?[0])
+    }
+
+    fn block_hash_ref(&self, number: U256) -> Result<B256, VyperErrors> {
+        let params = json!([format!("{:#x}", number), false]);
+        let block = &self.request(&[("eth_getBlockByNumber", params)])?[0];
+        let hash = block["hash"].as_str().unwrap_or_default();
+        hash.parse()
+            .map_err(|e| VyperErrors::RpcError(format!("invalid hash {}: {}", hash, e)))
+    }
+}
+
+/// Compiled contracts deployed into a local revm whose state is forked from a live chain at a
+/// block. The block's number, timestamp, coinbase and gas limit are those of the fork, while
+/// the base fee is zero so transactions need no funded sender.
+///
+/// ```no_run
+/// # use vyper_rs::{testing::ForkFixture, vyper::Vyper};
+/// # use std::path::Path;
+/// let compiled = Vyper::new(Path::new("./multisig.vy")).build()?;
+/// let mut fork = ForkFixture::new("http://localhost:8545", 19_000_000)?;
+/// let multisig = fork.deploy(&compiled, &[])?;
+/// # Ok::<(), vyper_rs::vyper_errors::VyperErrors>(())
+/// ```
+pub struct ForkFixture {
+    pub evm: Evm<'static, (), CacheDB<ForkDB>>,
+}
+
+impl ForkFixture {
+    /// Sender of the fixture's transactions
+    pub const SENDER: Address = Address::repeat_byte(0x10);
+
+    pub fn new(rpc_url: &str, block: u64) -> Result<ForkFixture, VyperErrors> {
+        let db = ForkDB {
+            rpc_url: rpc_url.to_owned(),
+            block,
+        };
+        let values = db.request(&[
+            ("eth_chainId", json!([])),
+            ("eth_getBlockByNumber", json!([db.block_tag(), false])),
+        ])?;
+        let (chain_id, header) = (decode_u256(&values[0])?.to(), &values[1]);
+        if header.is_null() {
+            Err(VyperErrors::RpcError(format!("block {} not found", block)))?
+        }
+        let block_env = BlockEnv {
+            number: U256::from(block),
+            coinbase: header["miner"]
+                .as_str()
+                .and_then(|miner| miner.parse().ok())
+                .unwrap_or_default(),
+            timestamp: decode_u256(&header["timestamp"])?,
+            gas_limit: decode_u256(&header["gasLimit"])?,
+            basefee: U256::ZERO,
+            prevrandao: header["mixHash"]
+                .as_str()
+                .and_then(|hash| hash.parse().ok()),
+            ..Default::default()
+        };
+        let evm = Evm::builder()
+            .with_db(CacheDB::new(db))
+            .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
+            .modify_block_env(|env| *env = block_env)
+            .modify_tx_env(|tx| {
+                tx.caller = ForkFixture::SENDER;
+                tx.gas_limit = GAS_LIMIT;
+                tx.gas_price = U256::ZERO;
+            })
+            .build();
+        Ok(ForkFixture { evm })
+    }
+
+    /// Deploys `contract` with the ABI encoded `constructor_args`, returning its address
+    pub fn deploy(
+        &mut self,
+        contract: &CompiledContract,
+        constructor_args: &[u8],
+    ) -> Result<Address, VyperErrors> {
+        let bytecode: bytecode::Bytecode = contract.bytecode.parse()?;
+        let initcode = [bytecode.as_bytes(), constructor_args].concat();
+        match self.run(TxKind::Create, initcode, true)? {
+            ExecutionResult::Success {
+                output: Output::Create(_, Some(address)),
+                ..
+            } => Ok(address),
+            other => Err(VyperErrors::SimulationFailed(format!(
+                "deployment of {} failed: {:?}",
+                contract.contract.display(),
+                other
+            ))),
+        }
+    }
+
+    /// Calls `to` with `calldata`, keeping the changes to the state
+    pub fn transact(
+        &mut self,
+        to: Address,
+        calldata: Vec<u8>,
+    ) -> Result<ExecutionResult, VyperErrors> {
+        self.run(TxKind::Call(to), calldata, true)
+    }
+
+    /// Calls `to` with `calldata` without changing the state, like `eth_call`
+    pub fn call(
+        &mut self,
+        to: Address,
+        calldata: Vec<u8>,
+    ) -> Result<ExecutionResult, VyperErrors> {
+        self.run(TxKind::Call(to), calldata, false)
+    }
+
+    fn run(
+        &mut self,
+        kind: TxKind,
+        data: Vec<u8>,
+        commit: bool,
+    ) -> Result<ExecutionResult, VyperErrors> {
+        self.evm.tx_mut().transact_to = kind;
+        self.evm.tx_mut().data = data.into();
+        let result = match commit {
+            true => self.evm.transact_commit(),
+            false => self.evm.transact().map(|result| result.result),
+        };
+        result.map_err(|e| VyperErrors::SimulationFailed(e.to_string()))
+    }
+}
+
+fn decode_hex(value: &Value) -> Result<Vec<u8>, VyperErrors> {
+    let hex = value.as_str().unwrap_or_default();
+    hex::decode(hex.trim_start_matches("0x"))
+        .map_err(|e| VyperErrors::RpcError(format!("invalid hex {}: {}", hex, e)))
+}
+
+fn decode_u256(value: &Value) -> Result<U256, VyperErrors> {
+    let hex = value.as_str().unwrap_or_default();
+    U256::from_str_radix(hex.trim_start_matches("0x"), 16)
+        .map_err(|e| VyperErrors::RpcError(format!("invalid quantity {}: {}", hex, e)))
+}
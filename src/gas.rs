@@ -0,0 +1,178 @@
+//! Gas estimates for every external function of a contract, simulated with revm. The contract is
+//! deployed into an empty in-memory chain and each function is called once with default-valued
+//! (zero, empty) arguments, so a report is a baseline to track regressions against rather than
+//! a prediction of what a real transaction costs.
+
+use crate::{
+    abi::{Abi, AbiParam},
+    artifacts::CompiledContract,
+    vyper_errors::VyperErrors,
+};
+use revm::{
+    primitives::{Address, Bytes, ExecutionResult, Output, TxKind},
+    Evm, InMemoryDB,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Gas limit of every simulated transaction
+pub const GAS_LIMIT: u64 = 30_000_000;
+
+/// Base cost of every transaction, paid before any code runs
+const TX_BASE_GAS: u64 = 21_000;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct GasReport {
+    pub contract: PathBuf,
+    /// Gas used by the deployment transaction
+    pub deployment: u64,
+    /// Sorted by signature
+    pub functions: Vec<GasEstimate>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct GasEstimate {
+    /// e.g. `transfer(address,uint256)`
+    pub signature: String,
+    pub selector: String,
+    /// Gas used by the whole transaction
+    pub gas: u64,
+    /// `gas` without the transaction's base cost and calldata, i.e. what the function itself
+    /// spends
+    pub execution_gas: u64,
+    /// Whether the call reverted or halted, e.g. on an access check failing for the zero
+    /// arguments. The estimate is then only a lower bound.
+    pub reverted: bool,
+}
+
+impl GasReport {
+    /// Deploys `contract` and calls each of its external functions, see the module docs
+    pub fn simulate(contract: &CompiledContract) -> Result<GasReport, VyperErrors> {
+        let abi = Abi::try_from(contract.abi.clone())?;
+        let mut evm = Evm::builder()
+            .with_db(InMemoryDB::default())
+            .modify_tx_env(|tx| {
+                tx.caller = Address::repeat_byte(0x10);
+                tx.gas_limit = GAS_LIMIT;
+            })
+            .build();
+
+        let mut initcode = decode_hex(&contract.bytecode)?;
+        if let Some(constructor) = abi.0.iter().find(|entry| entry.ty == "constructor") {
+            initcode.extend(encode_zero(&constructor.inputs));
+        }
+        evm.tx_mut().transact_to = TxKind::Create;
+        evm.tx_mut().data = Bytes::from(initcode);
+        let (deployment, address) =
+            match evm.transact_commit().map_err(simulation_failed)? {
+                ExecutionResult::Success {
+                    gas_used,
+                    output: Output::Create(_, Some(address)),
+                    ..
+                } => (gas_used, address),
+                other => Err(VyperErrors::SimulationFailed(format!(
+                    "deployment of {} failed: {:?}",
+                    contract.contract.display(),
+                    other
+                )))?,
+            };
+
+        let mut functions = Vec::new();
+        for entry in abi.0.iter().filter(|entry| entry.ty == "function") {
+            let Some(signature) = entry.signature() else {
+                continue;
+            };
+            let Some(selector) = contract.method_identifiers.get(&signature) else {
+                continue;
+            };
+            let mut calldata = decode_hex(selector)?;
+            calldata.extend(encode_zero(&entry.inputs));
+            let intrinsic = TX_BASE_GAS + calldata_gas(&calldata);
+            evm.tx_mut().transact_to = TxKind::Call(address);
+            evm.tx_mut().data = Bytes::from(calldata);
+            // Not committed, so every function runs against the freshly deployed state
+            let result = evm.transact().map_err(simulation_failed)?.result;
+            let gas = result.gas_used();
+            functions.push(GasEstimate {
+                signature,
+                selector: selector.clone(),
+                gas,
+                execution_gas: gas.saturating_sub(intrinsic),
+                reverted: !result.is_success(),
+            });
+        }
+        functions.sort();
+
+        Ok(GasReport {
+            contract: contract.contract.clone(),
+            deployment,
+            functions,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, VyperErrors> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn simulation_failed(err: impl std::fmt::Display) -> VyperErrors {
+    VyperErrors::SimulationFailed(err.to_string())
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, VyperErrors> {
+    hex::decode(hex.trim().trim_start_matches("0x"))
+        .map_err(|e| VyperErrors::SimulationFailed(format!("invalid hex {}: {}", hex, e)))
+}
+
+/// Cost of the calldata since Istanbul: 4 gas per zero byte, 16 per other byte
+fn calldata_gas(data: &[u8]) -> u64 {
+    data.iter().map(|b| if *b == 0 { 4 } else { 16 }).sum()
+}
+
+/// ABI encoding of default-valued `params`: zero for numbers, addresses and fixed bytes, empty
+/// for dynamic arrays, bytes and strings
+fn encode_zero(params: &[AbiParam]) -> Vec<u8> {
+    let mut heads = Vec::new();
+    let mut tails = Vec::new();
+    let heads_len: usize = params
+        .iter()
+        .map(|p| {
+            if p.is_dynamic() {
+                32
+            } else {
+                encode_zero_single(p).len()
+            }
+        })
+        .sum();
+    for param in params {
+        let encoded = encode_zero_single(param);
+        if param.is_dynamic() {
+            heads.extend(word((heads_len + tails.len()) as u64));
+            tails.extend(encoded);
+        } else {
+            heads.extend(encoded);
+        }
+    }
+    heads.extend(tails);
+    heads
+}
+
+fn encode_zero_single(param: &AbiParam) -> Vec<u8> {
+    match param.array_element() {
+        // empty dynamic array: a zero length
+        Some((_, "")) => vec![0; 32],
+        Some((element, len)) => {
+            let len = len.parse().unwrap_or(0);
+            encode_zero(&vec![element; len])
+        }
+        None if param.ty == "tuple" => encode_zero(&param.components),
+        // zero value, or an empty `bytes` / `string`
+        None => vec![0; 32],
+    }
+}
+
+fn word(n: u64) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[24..].copy_from_slice(&n.to_be_bytes());
+    word
+}
@@ -0,0 +1,121 @@
+//! The import graph of a project, built by `Project::dependency_graph`, and exports to DOT
+//! (Graphviz) and Mermaid for visualizing how contracts, modules and interfaces depend on
+//! each other.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// Contracts of the project first, in the order of `Vypers::path_to_code`, then the
+    /// modules imported from outside the project
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct GraphNode {
+    /// Path relative to the project root for contracts, the module name otherwise, e.g.
+    /// `ethereum.ercs.IERC20`
+    pub name: String,
+    pub kind: NodeKind,
+}
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Contract,
+    /// A module outside the project, such as the compiler's builtin interfaces
+    External,
+}
+
+/// An import of `to` by `from`, both indices in `DependencyGraph::nodes`
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    /// Whether the importing contract declares it `implements:` the imported interface
+    pub implements: bool,
+}
+
+impl DependencyGraph {
+    /// Index of the node `name`, added with `kind` if missing
+    #[cfg(feature = "native")]
+    pub(crate) fn node(&mut self, name: &str, kind: NodeKind) -> usize {
+        match self.nodes.iter().position(|node| node.name == name) {
+            Some(i) => i,
+            None => {
+                self.nodes.push(GraphNode {
+                    name: name.to_owned(),
+                    kind,
+                });
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Nodes imported by the node at `index`
+    pub fn dependencies(&self, index: usize) -> Vec<&GraphNode> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.from == index)
+            .map(|edge| &self.nodes[edge.to])
+            .collect()
+    }
+
+    /// Nodes importing the node at `index`
+    pub fn dependents(&self, index: usize) -> Vec<&GraphNode> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.to == index)
+            .map(|edge| &self.nodes[edge.from])
+            .collect()
+    }
+
+    /// The graph in Graphviz's DOT language, external modules drawn as dashed boxes
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+        for node in &self.nodes {
+            let _ = match node.kind {
+                NodeKind::Contract => writeln!(dot, "    {:?};", node.name),
+                NodeKind::External => {
+                    writeln!(dot, "    {:?} [shape=box, style=dashed];", node.name)
+                }
+            };
+        }
+        for edge in &self.edges {
+            let (from, to) = (&self.nodes[edge.from].name, &self.nodes[edge.to].name);
+            let _ = match edge.implements {
+                true => {
+                    writeln!(dot, "    {:?} -> {:?} [label=\"implements\"];", from, to)
+                }
+                false => writeln!(dot, "    {:?} -> {:?};", from, to),
+            };
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph as a Mermaid flowchart, external modules drawn as stadiums
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("graph LR\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let name = node.name.replace('"', "#quot;");
+            let _ = match node.kind {
+                NodeKind::Contract => writeln!(mermaid, "    n{}[\"{}\"]", i, name),
+                NodeKind::External => writeln!(mermaid, "    n{}([\"{}\"])", i, name),
+            };
+        }
+        for edge in &self.edges {
+            let _ = match edge.implements {
+                true => {
+                    writeln!(mermaid, "    n{} -. implements .-> n{}", edge.from, edge.to)
+                }
+                false => writeln!(mermaid, "    n{} --> n{}", edge.from, edge.to),
+            };
+        }
+        mermaid
+    }
+}
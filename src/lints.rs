@@ -0,0 +1,294 @@
+//! Static safety lints. A first-pass security gate for CI, run with `Vyper::lint`: the source
+//! level rules walk the contract's AST, the control flow rules its runtime opcodes. A finding
+//! is a hint for review, not proof of a vulnerability.
+
+use crate::diagnostics::Severity;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// A call to `selfdestruct`
+    Selfdestruct,
+    /// `raw_call` with `is_delegate_call=True`, which runs foreign code against this
+    /// contract's storage
+    DelegateCall,
+    /// `tx.origin` compared against something, usually an authorization check that a
+    /// malicious contract in the middle of the call chain can pass
+    TxOriginAuth,
+    /// Instructions after a halting instruction or jump that no jump lands on
+    UnreachableCode,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 4] = [
+        Rule::Selfdestruct,
+        Rule::DelegateCall,
+        Rule::TxOriginAuth,
+        Rule::UnreachableCode,
+    ];
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Rule::Selfdestruct | Rule::DelegateCall | Rule::TxOriginAuth => {
+                Severity::Error
+            }
+            Rule::UnreachableCode => Severity::Warning,
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rule::Selfdestruct => write!(f, "selfdestruct"),
+            Rule::DelegateCall => write!(f, "delegate_call"),
+            Rule::TxOriginAuth => write!(f, "tx_origin_auth"),
+            Rule::UnreachableCode => write!(f, "unreachable_code"),
+        }
+    }
+}
+
+/// The rules `Vyper::lint_with` runs. Every rule is enabled by default.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Lints {
+    pub rules: BTreeSet<Rule>,
+}
+
+impl Default for Lints {
+    fn default() -> Self {
+        Lints {
+            rules: Rule::ALL.into_iter().collect(),
+        }
+    }
+}
+
+impl Lints {
+    /// No rules enabled, see `allow` and `deny`
+    pub fn none() -> Lints {
+        Lints {
+            rules: BTreeSet::new(),
+        }
+    }
+
+    pub fn deny(mut self, rule: Rule) -> Lints {
+        self.rules.insert(rule);
+        self
+    }
+
+    pub fn allow(mut self, rule: Rule) -> Lints {
+        self.rules.remove(&rule);
+        self
+    }
+
+    pub fn enabled(&self, rule: Rule) -> bool {
+        self.rules.contains(&rule)
+    }
+
+    /// Whether any rule needs the runtime opcodes, so the compiler isn't invoked for nothing
+    #[cfg(feature = "native")]
+    pub(crate) fn needs_opcodes(&self) -> bool {
+        self.enabled(Rule::UnreachableCode)
+    }
+
+    /// Whether any rule needs the AST
+    #[cfg(feature = "native")]
+    pub(crate) fn needs_ast(&self) -> bool {
+        self.rules.iter().any(|rule| *rule != Rule::UnreachableCode)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct LintFinding {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub contract: PathBuf,
+    pub message: String,
+    /// 1-based, for findings in the source
+    pub line: Option<u32>,
+    /// 0-based, for findings in the source
+    pub column: Option<u32>,
+    /// Index of the instruction in the runtime opcodes, for findings in the bytecode
+    pub instruction: Option<usize>,
+}
+
+impl LintFinding {
+    fn new(rule: Rule, contract: &Path, message: String) -> LintFinding {
+        LintFinding {
+            rule,
+            severity: rule.severity(),
+            contract: contract.to_path_buf(),
+            message,
+            line: None,
+            column: None,
+            instruction: None,
+        }
+    }
+
+    fn at_node(mut self, node: &Value) -> LintFinding {
+        self.line = node["lineno"].as_u64().map(|l| l as u32);
+        self.column = node["col_offset"].as_u64().map(|c| c as u32);
+        self
+    }
+}
+
+impl Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.contract.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": [{}] {}", self.rule, self.message)
+    }
+}
+
+/// Runs the source level rules of `lints` over `ast`, the output of `vyper -f ast`
+pub fn lint_ast(contract: &Path, ast: &Value, lints: &Lints) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    walk(ast, &mut |node| match node["ast_type"].as_str() {
+        Some("Call") => {
+            let func = node["func"]["id"].as_str();
+            if func == Some("selfdestruct") && lints.enabled(Rule::Selfdestruct) {
+                findings.push(
+                        LintFinding::new(
+                            Rule::Selfdestruct,
+                            contract,
+                            "selfdestruct can remove the contract's code and drain its balance"
+                                .to_owned(),
+                        )
+                        .at_node(node),
+                    );
+            }
+            if func == Some("raw_call")
+                && is_delegate_call(node)
+                && lints.enabled(Rule::DelegateCall)
+            {
+                findings.push(
+                        LintFinding::new(
+                            Rule::DelegateCall,
+                            contract,
+                            "raw_call with is_delegate_call=True runs foreign code with this \
+                             contract's storage"
+                                .to_owned(),
+                        )
+                        .at_node(node),
+                    );
+            }
+        }
+        Some("Compare") if lints.enabled(Rule::TxOriginAuth) => {
+            if let Some(origin) = find(node, &is_tx_origin) {
+                findings.push(
+                    LintFinding::new(
+                        Rule::TxOriginAuth,
+                        contract,
+                        "tx.origin is used for authorization, use msg.sender instead"
+                            .to_owned(),
+                    )
+                    .at_node(origin),
+                );
+            }
+        }
+        _ => {}
+    });
+    findings
+}
+
+/// Runs the control flow rules of `lints` over `opcodes`, the output of
+/// `vyper -f opcodes_runtime`
+pub fn lint_opcodes(contract: &Path, opcodes: &str, lints: &Lints) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    if !lints.enabled(Rule::UnreachableCode) {
+        return findings;
+    }
+    let instructions = instructions(opcodes);
+    // Code following the last reachable block is data (metadata, immutables, ...) appended by
+    // the compiler, so only dead code followed by a jump destination is reported.
+    let mut dead: Option<usize> = None;
+    let mut halted = false;
+    for (index, op) in instructions.iter().enumerate() {
+        if *op == "JUMPDEST" {
+            if let Some(start) = dead.take() {
+                findings.push(LintFinding {
+                    instruction: Some(start),
+                    ..LintFinding::new(
+                        Rule::UnreachableCode,
+                        contract,
+                        format!("{} unreachable instructions", index - start),
+                    )
+                })
+            }
+            halted = false;
+        } else if halted {
+            dead.get_or_insert(index);
+        } else {
+            halted = matches!(
+                *op,
+                "STOP" | "RETURN" | "REVERT" | "INVALID" | "SELFDESTRUCT" | "JUMP"
+            );
+        }
+    }
+    findings
+}
+
+/// Instructions of the `opcodes` output, without the immediate arguments of `PUSH`
+fn instructions(opcodes: &str) -> Vec<&str> {
+    let mut tokens = opcodes.split_whitespace();
+    let mut instructions = Vec::new();
+    while let Some(op) = tokens.next() {
+        if let Some(n) = op.strip_prefix("PUSH").and_then(|n| n.parse::<u8>().ok()) {
+            if n > 0 {
+                tokens.next();
+            }
+        }
+        instructions.push(op);
+    }
+    instructions
+}
+
+fn is_delegate_call(call: &Value) -> bool {
+    call["keywords"].as_array().map_or(false, |keywords| {
+        keywords.iter().any(|keyword| {
+            keyword["arg"] == "is_delegate_call" && keyword["value"]["value"] == true
+        })
+    })
+}
+
+fn is_tx_origin(node: &Value) -> bool {
+    node["ast_type"] == "Attribute"
+        && node["attr"] == "origin"
+        && node["value"]["id"] == "tx"
+}
+
+/// Calls `f` on every node of the AST, parents first
+fn walk<'v>(value: &'v Value, f: &mut impl FnMut(&'v Value)) {
+    match value {
+        Value::Object(map) => {
+            if map.contains_key("ast_type") {
+                f(value);
+            }
+            map.values().for_each(|v| walk(v, f));
+        }
+        Value::Array(values) => values.iter().for_each(|v| walk(v, f)),
+        _ => {}
+    }
+}
+
+/// The first node below `value` matching `predicate`
+fn find<'v>(value: &'v Value, predicate: &impl Fn(&Value) -> bool) -> Option<&'v Value> {
+    if predicate(value) {
+        return Some(value);
+    }
+    match value {
+        Value::Object(map) => map.values().find_map(|v| find(v, predicate)),
+        Value::Array(values) => values.iter().find_map(|v| find(v, predicate)),
+        _ => None,
+    }
+}
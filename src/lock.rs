@@ -0,0 +1,158 @@
+//! The `vyper.lock` lockfile. It records, for every contract of a project, the exact compiler
+//! version, the settings and a hash of the source it was built with, so a deployment can be
+//! reproduced on another machine and drift is caught before it ships.
+
+use crate::{utils, vyper_errors::VyperErrors};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+/// Name of the lockfile in the project root
+pub const LOCKFILE: &str = "vyper.lock";
+
+/// Version of the lockfile format, bumped on incompatible changes
+pub const LOCKFILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    /// Keyed by contract path, relative to the project root
+    pub contracts: BTreeMap<PathBuf, LockedContract>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct LockedContract {
+    /// Output of `vyper --version`
+    pub compiler: String,
+    /// EVM version the contract is compiled for, `None` when the compiler's default couldn't be
+    /// determined
+    pub evm_version: Option<String>,
+    /// Optimization mode passed with `--optimize`, `None` for the compiler's default
+    #[serde(default)]
+    pub optimize: Option<String>,
+    pub source_sha256: String,
+}
+
+impl LockedContract {
+    /// Locks the contract at `path` as it is on disk now
+    pub fn new(
+        path: &Path,
+        compiler: String,
+        evm_version: Option<String>,
+        optimize: Option<String>,
+    ) -> Result<LockedContract, VyperErrors> {
+        Ok(LockedContract {
+            compiler,
+            evm_version,
+            optimize,
+            source_sha256: utils::sha256_file(path)?,
+        })
+    }
+}
+
+/// A difference between the lockfile and the project
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Drift {
+    /// The contract isn't in the lockfile
+    Added(PathBuf),
+    /// The contract is in the lockfile, but no longer part of the project
+    Removed(PathBuf),
+    /// `field` of the contract changed from `locked` to `current`
+    Changed {
+        contract: PathBuf,
+        field: String,
+        locked: String,
+        current: String,
+    },
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::Added(path) => write!(f, "{} is not locked", path.display()),
+            Drift::Removed(path) => write!(f, "{} is locked but missing", path.display()),
+            Drift::Changed {
+                contract,
+                field,
+                locked,
+                current,
+            } => write!(
+                f,
+                "{} of {} changed from {} to {}",
+                field,
+                contract.display(),
+                locked,
+                current
+            ),
+        }
+    }
+}
+
+impl Lockfile {
+    pub fn new(contracts: BTreeMap<PathBuf, LockedContract>) -> Lockfile {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            contracts,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Lockfile, VyperErrors> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), VyperErrors> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Everything that differs between this, the locked state, and `current`
+    pub fn diff(&self, current: &Lockfile) -> Vec<Drift> {
+        let mut drift = vec![];
+        for (path, locked) in &self.contracts {
+            let Some(now) = current.contracts.get(path) else {
+                drift.push(Drift::Removed(path.clone()));
+                continue;
+            };
+            let fields = [
+                ("compiler", locked.compiler.clone(), now.compiler.clone()),
+                (
+                    "evm_version",
+                    locked.evm_version.clone().unwrap_or_default(),
+                    now.evm_version.clone().unwrap_or_default(),
+                ),
+                (
+                    "optimize",
+                    locked.optimize.clone().unwrap_or_default(),
+                    now.optimize.clone().unwrap_or_default(),
+                ),
+                (
+                    "source_sha256",
+                    locked.source_sha256.clone(),
+                    now.source_sha256.clone(),
+                ),
+            ];
+            for (field, locked, current) in fields {
+                if locked != current {
+                    drift.push(Drift::Changed {
+                        contract: path.clone(),
+                        field: field.to_owned(),
+                        locked,
+                        current,
+                    });
+                }
+            }
+        }
+        for path in current.contracts.keys() {
+            if !self.contracts.contains_key(path) {
+                drift.push(Drift::Added(path.clone()));
+            }
+        }
+        drift
+    }
+}
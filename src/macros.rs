@@ -38,10 +38,17 @@ macro_rules! vyper {
 ///
 /// Input: any length sequence of expressions that evaluate to a Path.
 ///
-/// Keywords: venv.
+/// Keywords: venv, gas, codesize, none and the name of any EVM version, i.e. shanghai.
 ///
 /// venv - compile contract using an instance of the Vyper compiler inside a venv.
 ///
+/// gas, codesize, none - compile contract with the matching `--optimize` mode.
+///
+/// shanghai, cancun, ... - compile contracts for that EVM version, see `Evm`. Names the
+/// compiler doesn't know fail with `VyperErrors::UnknownEvm`.
+///
+/// Compiling more than one contract awaits `Vypers::compile_many` and needs the `async` feature.
+///
 /// ```rust
 ///  use vyper_rs::venv::*;
 ///  use vyper_rs::vyper::*;
@@ -53,6 +60,10 @@ macro_rules! vyper {
 ///     let _: Vyper = compile!("./multisig.vy");
 ///     let _: Vypers = compile!(venv "./multisig.vy", "./multisig.vy");
 ///     let _: Vypers = compile!("./multisig.vy", "./multisig.vy");
+///     let _: Vyper = compile!(shanghai "./multisig.vy");
+///     let _: Vypers = compile!(cancun "./multisig.vy", "./multisig.vy");
+///     let _: Vyper = compile!(codesize "./multisig.vy");
+///     let _: Vypers = compile!(codesize "./multisig.vy", "./multisig.vy");
 ///     Ok(())
 ///  }
 ///  ```
@@ -72,9 +83,9 @@ macro_rules! compile {
             let mut contract = Venv::default()
                 .init()?
                 .ivyper_venv(None)?
-                .vyper(Path::new("../../multisig.vy"));
-           contract.compile()?;
-           contract
+                .vyper(Path::new($p1));
+            contract.compile()?;
+            contract
         }
     };
     // compile many
@@ -85,7 +96,7 @@ macro_rules! compile {
                 let v = vyper!($p1);
                 contracts.push(v);
             )+
-            let mut cs: Vypers = Vypers::from(contracts);
+            let mut cs: Vypers = Vypers::try_from_contracts(contracts)?;
             cs.compile_many().await?;
             cs
         }
@@ -103,6 +114,74 @@ macro_rules! compile {
             contracts
         }
     };
+    // optimization modes, same names as the `--optimize` flag
+    (gas $p1: expr) => {
+        {
+            let mut vy: Vyper = vyper!($p1).set_optimize($crate::vyper::Optimize::Gas);
+            vy.compile()?;
+            vy
+        }
+    };
+    (codesize $p1: expr) => {
+        {
+            let mut vy: Vyper = vyper!($p1).set_optimize($crate::vyper::Optimize::Codesize);
+            vy.compile()?;
+            vy
+        }
+    };
+    (none $p1: expr) => {
+        {
+            let mut vy: Vyper = vyper!($p1).set_optimize($crate::vyper::Optimize::None);
+            vy.compile()?;
+            vy
+        }
+    };
+    // compile many with an optimization mode
+    (gas $($p1: expr),+) => {
+        $crate::compile!(@optimize Gas $($p1),+)
+    };
+    (codesize $($p1: expr),+) => {
+        $crate::compile!(@optimize Codesize $($p1),+)
+    };
+    (none $($p1: expr),+) => {
+        $crate::compile!(@optimize None $($p1),+)
+    };
+    (@optimize $mode: ident $($p1: expr),+) => {
+        {
+            let mut paths: Vec<PathBuf> = vec![];
+            $(
+                let v = PathBuf::from($p1);
+                paths.push(v);
+            )+
+            let mut contracts =
+                Vypers::new(paths).set_optimize($crate::vyper::Optimize::$mode);
+            contracts.compile_many().await?;
+            contracts
+        }
+    };
+    // any other keyword is taken as the EVM version to compile for
+    ($evm: ident $p1: expr) => {
+        {
+            let evm: $crate::vyper::Evm = stringify!($evm).parse()?;
+            let mut vy: Vyper = vyper!($p1);
+            vy.compile_ver(&evm)?;
+            vy
+        }
+    };
+    // compile many for an EVM version
+    ($evm: ident $($p1: expr),+) => {
+        {
+            let evm: $crate::vyper::Evm = stringify!($evm).parse()?;
+            let mut paths: Vec<PathBuf> = vec![];
+            $(
+                let v = PathBuf::from($p1);
+                paths.push(v);
+            )+
+            let mut contracts = Vypers::new(paths);
+            contracts.compile_many_ver(evm).await?;
+            contracts
+        }
+    };
 }
 
 /// The `abi!` macro is used to compile one more more Vyper contracts and get or generate the ABI.
@@ -113,6 +192,10 @@ macro_rules! compile {
 ///
 /// venv - compile contract using an instance of the Vyper compiler inside a venv.
 ///
+/// `"./c.vy" => "./abi.json"` - also write the ABI of each contract to the given path.
+///
+/// Like `compile!`, more than one contract needs the `async` feature.
+///
 /// ```rust
 ///  use vyper_rs::venv::*;
 ///  use vyper_rs::vyper::*;
@@ -125,11 +208,38 @@ macro_rules! compile {
 ///     let _: Value = abi!(venv "./multisig.vy");
 ///     let _: Vec<Value> = abi!("./multisig.vy", "./multisig.vy");   
 ///     let _: Vec<Value> = abi!(venv "./multisig.vy", "./multisig.vy");   
+///     let _: Value = abi!("./multisig.vy" => "./abi.json");
+///     let _: Vec<Value> = abi!("./multisig.vy" => "./a.json", "./multisig.vy" => "./b.json");
 ///     Ok(())
 /// }
 /// ```
 #[macro_export]
 macro_rules! abi {
+    // write the ABI to the given path and return it
+    ($p1: expr => $abi: expr) => {
+        {
+            let c = $crate::vyper::Vyper::with_abi(
+                ::std::path::Path::new($p1),
+                ::std::path::PathBuf::from($abi),
+            );
+            c.gen_abi()?;
+            c.read_abi()?
+        }
+    };
+    // write each ABI to its path and return them
+    ($($p1: expr => $abi: expr),+) => {
+        {
+            let mut paths: Vec<::std::path::PathBuf> = vec![];
+            let mut abis: Vec<::std::path::PathBuf> = vec![];
+            $(
+                paths.push(::std::path::PathBuf::from($p1));
+                abis.push(::std::path::PathBuf::from($abi));
+            )+
+            let mut cs = $crate::vyper::Vypers::with_all(paths, abis, None);
+            cs.gen_abi_many().await?;
+            cs.read_abi_many()?
+        }
+    };
     // OG matcher
     // return the ABI as json instead of creating a file
     ($p1: expr) => {
@@ -141,7 +251,7 @@ macro_rules! abi {
     // return the ABI as json instead of creating a file
     (venv $p1: expr) => {
         {
-            let mut c: Vyper = compile!(venv $p1);
+            let c: Vyper = compile!(venv $p1);
             c.get_abi()?
         }
     };
@@ -165,14 +275,91 @@ macro_rules! abi {
                 let v = PathBuf::from($p1);
                 p.push(v);
             )+
-            let mut contracts = Venv::default().init()?.ivyper_venv(None)?.vypers(p);
+            let contracts = Venv::default().init()?.ivyper_venv(None)?.vypers(p);
             contracts.get_abi_many().await?
         }
     };
 }
+/// The `deploy!` macro compiles a contract, generates its ABI and deploys it with an ethers
+/// client, returning the address of the deployed contract. Requires the `ethers` feature.
+///
+/// Input: an expression that evaluates to a Path, an `Arc` of the client and optionally a
+/// tuple of constructor arguments.
+///
+/// ```rust
+///  use ethers::{providers::Middleware, types::Address};
+///  use vyper_rs::*;
+///  use vyper_rs::vyper_errors::VyperErrors;
+///  use std::sync::Arc;
+///  async fn try_me<M: Middleware + 'static>(client: Arc<M>) -> Result<(), VyperErrors> {
+///     let owners: Vec<Address> = vec![Address::zero()];
+///     let _: Address = deploy!("./multisig.vy", client, (owners,));
+///     Ok(())
+///  }
+///  ```
+#[cfg(feature = "ethers")]
+#[macro_export]
+macro_rules! deploy {
+    ($p1: expr, $client: expr) => {
+        $crate::deploy!($p1, $client, ())
+    };
+    ($p1: expr, $client: expr, $args: expr) => {{
+        let mut contract = $crate::vyper::Vyper::new(::std::path::Path::new($p1));
+        contract.compile()?;
+        contract.gen_abi()?;
+        $crate::deploy::deploy(&contract, $client, $args).await?
+    }};
+}
+
+/// The `blueprint!` macro compiles a contract as an ERC-5202 blueprint and returns the parsed
+/// `Blueprint`.
+///
+/// Input: an expression that evaluates to a Path.
+///
+/// ```rust
+///  use vyper_rs::*;
+///  use vyper_rs::utils::Blueprint;
+///  use vyper_rs::vyper_errors::VyperErrors;
+///  fn try_me() -> Result<(), VyperErrors> {
+///     let _: Blueprint = blueprint!("./multisig.vy");
+///     Ok(())
+///  }
+///  ```
+#[macro_export]
+macro_rules! blueprint {
+    ($p1: expr) => {{
+        let mut contract = $crate::vyper::Vyper::new(::std::path::Path::new($p1));
+        contract.get_blueprint()?
+    }};
+}
+
+/// The `layout!` macro returns the storage layout of a contract as a `StorageLayout`.
+///
+/// Input: an expression that evaluates to a Path.
+///
+/// ```rust
+///  use vyper_rs::*;
+///  use vyper_rs::utils::StorageLayout;
+///  use vyper_rs::vyper_errors::VyperErrors;
+///  fn try_me() -> Result<(), VyperErrors> {
+///     let _: StorageLayout = layout!("./multisig.vy");
+///     Ok(())
+///  }
+///  ```
+#[macro_export]
+macro_rules! layout {
+    ($p1: expr) => {{
+        let contract = $crate::vyper::Vyper::new(::std::path::Path::new($p1));
+        contract.get_layout()?
+    }};
+}
+
 /// The `venv!` macro creates a virtual environment with the latest version of the vyper compiler installed.
 /// Optionally, you can pass the desired version of the Vyper compiler you want to install, i.e
 /// "0.3.10", as a &str.
+/// The venv is created at `./venv` unless a `path` is given. Options are passed as
+/// `key = value` pairs, in any order: `path` (a &str or anything `Path::new` accepts),
+/// `version` (a &str) and `backend` (`pip` or `uv`, see `InstallBackend`).
 ///```rust
 ///
 /// use vyper_rs::venv::*;
@@ -182,12 +369,31 @@ macro_rules! abi {
 /// fn try_me() -> Result<(), VyperErrors> {
 ///     let _:  Venv<Ready> = venv!();
 ///     let _: Venv<Ready> = venv!("0.3.10");
+///     let _: Venv<Ready> = venv!(path = "./.vyper-venv", version = "0.4.0", backend = uv);
+///     let _: Venv<Ready> = venv!(backend = pip, path = "./.vyper-venv");
 ///     Ok(())
 /// }
 ///
 ///```
 #[macro_export]
 macro_rules! venv {
+    (@opts $venv:ident $ver:ident;) => {};
+    (@opts $venv:ident $ver:ident; path = $p:expr $(, $($rest:tt)*)?) => {
+        $venv = $venv.at(::std::path::Path::new($p));
+        $crate::venv!(@opts $venv $ver; $($($rest)*)?)
+    };
+    (@opts $venv:ident $ver:ident; version = $v:expr $(, $($rest:tt)*)?) => {
+        $ver = Some($v);
+        $crate::venv!(@opts $venv $ver; $($($rest)*)?)
+    };
+    (@opts $venv:ident $ver:ident; backend = pip $(, $($rest:tt)*)?) => {
+        $venv = $venv.with_installer($crate::venv::InstallBackend::Pip);
+        $crate::venv!(@opts $venv $ver; $($($rest)*)?)
+    };
+    (@opts $venv:ident $ver:ident; backend = uv $(, $($rest:tt)*)?) => {
+        $venv = $venv.with_installer($crate::venv::InstallBackend::Uv);
+        $crate::venv!(@opts $venv $ver; $($($rest)*)?)
+    };
     () => {{
         Venv::default().init()?.ivyper_venv(None)?
     }};
@@ -195,4 +401,12 @@ macro_rules! venv {
         let version: &str = $ver;
         Venv::default().init()?.ivyper_venv(Some(version))?
     }};
+    ($key:ident = $($opts:tt)+) => {{
+        #[allow(unused_mut)]
+        let mut venv = $crate::venv::Venv::default();
+        #[allow(unused_mut, unused_assignments)]
+        let mut version: Option<&str> = None;
+        $crate::venv!(@opts venv version; $key = $($opts)+);
+        venv.init()?.ivyper_venv(version)?
+    }};
 }
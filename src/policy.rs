@@ -0,0 +1,220 @@
+//! Organization wide rules for the sources of a project, checked by `Project::enforce_policy`
+//! without compiling anything: every contract carries an SPDX license, pins a compiler
+//! version within an allowed range and stays away from banned imports. Violations are
+//! reported as `Diagnostic`s, with codes such as `MissingLicense` or `BannedImport`.
+
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    utils::{self, SourceUnit},
+};
+use semver::{Comparator, Op, Version, VersionReq};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    /// Whether contracts need an `SPDX-License-Identifier` comment or `@license` tag
+    pub require_license: bool,
+    /// SPDX identifiers contracts may be licensed under, any when empty
+    pub licenses: Vec<String>,
+    /// Versions the pragma of every contract must stay within, e.g. `>=0.3.10, <0.5`: a
+    /// pragma accepting versions below or above them, or without an upper bound when they
+    /// have one, is a violation. So are contracts without a pragma when set.
+    pub versions: Option<VersionReq>,
+    /// Modules contracts can't import, banning a module bans its submodules too
+    pub banned_imports: Vec<String>,
+}
+
+impl Policy {
+    pub fn require_license(mut self) -> Policy {
+        self.require_license = true;
+        self
+    }
+
+    /// Allows `license`, implies `require_license`
+    pub fn allow_license(mut self, license: &str) -> Policy {
+        self.require_license = true;
+        self.licenses.push(license.to_owned());
+        self
+    }
+
+    pub fn set_versions(mut self, versions: VersionReq) -> Policy {
+        self.versions = Some(versions);
+        self
+    }
+
+    pub fn ban_import(mut self, module: &str) -> Policy {
+        self.banned_imports.push(module.to_owned());
+        self
+    }
+
+    /// Violations of the policy by `source`, the source of the contract at `path`
+    pub fn check(&self, path: &Path, source: &str) -> Vec<Diagnostic> {
+        let unit = SourceUnit::from_source(path, source);
+        let violation = |code: &str, message: String, line: Option<u32>| {
+            let location = line.map(|line| format!(":{}", line)).unwrap_or_default();
+            Diagnostic {
+                severity: Severity::Error,
+                code: Some(code.to_owned()),
+                rendered: format!("{}{}: {}", path.display(), location, message),
+                message,
+                file: Some(path.to_path_buf()),
+                line,
+                column: line.map(|_| 0),
+            }
+        };
+        let mut violations = Vec::new();
+
+        match &unit.license {
+            None if self.require_license => violations.push(violation(
+                "MissingLicense",
+                "no SPDX-License-Identifier or @license".to_owned(),
+                None,
+            )),
+            Some(license)
+                if !self.licenses.is_empty() && !self.licenses.contains(license) =>
+            {
+                violations.push(violation(
+                    "LicenseNotAllowed",
+                    format!("license {} is not allowed", license),
+                    find_line(source, |line| line.contains(license.as_str())),
+                ))
+            }
+            _ => {}
+        }
+
+        if let Some(versions) = &self.versions {
+            let pragma_line = find_line(source, |line| {
+                line.starts_with('#')
+                    && (line.contains("@version") || line.contains("pragma version"))
+            });
+            match &unit.pragma {
+                None => violations.push(violation(
+                    "MissingPragma",
+                    format!("no version pragma, expected one within {}", versions),
+                    None,
+                )),
+                Some(pragma) => {
+                    let req = utils::version_req(pragma).ok();
+                    let message = match req.as_ref().and_then(lowest) {
+                        Some(lowest) if !versions.matches(&lowest) => Some(format!(
+                            "pragma {} accepts {}, outside of {}",
+                            pragma, lowest, versions
+                        )),
+                        Some(_) => {
+                            // the pragma's range must be within `versions`, so it can't
+                            // reach past its upper bound either
+                            let upper = req.as_ref().and_then(upper);
+                            match (upper, self::upper(versions)) {
+                                (None, Some(_)) => Some(format!(
+                                    "pragma {} has no upper bound within {}",
+                                    pragma, versions
+                                )),
+                                (Some(upper), Some(allowed)) if upper > allowed => {
+                                    Some(format!(
+                                        "pragma {} accepts versions up to {}, outside of {}",
+                                        pragma, upper, versions
+                                    ))
+                                }
+                                _ => None,
+                            }
+                        }
+                        None => Some(format!(
+                            "pragma {} has no lower bound within {}",
+                            pragma, versions
+                        )),
+                    };
+                    if let Some(message) = message {
+                        violations.push(violation(
+                            "PragmaNotAllowed",
+                            message,
+                            pragma_line,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for module in &unit.imports {
+            let imported = module.trim_start_matches('.');
+            let banned = self.banned_imports.iter().find(|banned| {
+                imported == banned.as_str()
+                    || imported.starts_with(&format!("{}.", banned))
+            });
+            if let Some(banned) = banned {
+                // `from a import b` is recorded as `a.b`
+                let parent = module.rsplit_once('.').map_or("", |(parent, _)| parent);
+                let line = find_line(source, |line| {
+                    line.starts_with("import ") && line.contains(module.as_str())
+                        || line.starts_with("from ")
+                            && !parent.is_empty()
+                            && line.contains(parent)
+                });
+                violations.push(violation(
+                    "BannedImport",
+                    format!("import of {} is banned by {}", module, banned),
+                    line,
+                ));
+            }
+        }
+        violations
+    }
+}
+
+/// 1-based number of the first line of `source` matching `pred`, given the trimmed line
+fn find_line(source: &str, pred: impl Fn(&str) -> bool) -> Option<u32> {
+    source
+        .lines()
+        .position(|line| pred(line.trim()))
+        .map(|i| i as u32 + 1)
+}
+
+/// The lowest version `req` accepts, `None` when it has no lower bound
+fn lowest(req: &VersionReq) -> Option<Version> {
+    req.comparators.iter().filter_map(lower_bound).max()
+}
+
+/// The first version above every version `req` accepts, `None` when it has no upper bound
+fn upper(req: &VersionReq) -> Option<Version> {
+    req.comparators.iter().filter_map(upper_bound).min()
+}
+
+fn lower_bound(comparator: &Comparator) -> Option<Version> {
+    let minor = comparator.minor.unwrap_or(0);
+    let patch = comparator.patch.unwrap_or(0);
+    let mut version = Version::new(comparator.major, minor, patch);
+    version.pre = comparator.pre.clone();
+    match comparator.op {
+        Op::Exact | Op::GreaterEq | Op::Tilde | Op::Caret | Op::Wildcard => Some(version),
+        Op::Greater => Some(match (comparator.minor, comparator.patch) {
+            (Some(_), Some(_)) => Version::new(comparator.major, minor, patch + 1),
+            (Some(_), None) => Version::new(comparator.major, minor + 1, 0),
+            _ => Version::new(comparator.major + 1, 0, 0),
+        }),
+        _ => None,
+    }
+}
+
+/// The first version above those `comparator` accepts
+fn upper_bound(comparator: &Comparator) -> Option<Version> {
+    let (major, minor, patch) = (comparator.major, comparator.minor, comparator.patch);
+    // the version after the last one matching the given components
+    let next = || match (minor, patch) {
+        (Some(minor), Some(patch)) => Version::new(major, minor, patch + 1),
+        (Some(minor), None) => Version::new(major, minor + 1, 0),
+        _ => Version::new(major + 1, 0, 0),
+    };
+    match comparator.op {
+        Op::Less => Some(Version::new(major, minor.unwrap_or(0), patch.unwrap_or(0))),
+        Op::LessEq | Op::Exact | Op::Wildcard => Some(next()),
+        Op::Tilde => Some(match minor {
+            Some(minor) => Version::new(major, minor + 1, 0),
+            None => Version::new(major + 1, 0, 0),
+        }),
+        Op::Caret => Some(match (major, minor, patch) {
+            (0, Some(0), Some(patch)) => Version::new(0, 0, patch + 1),
+            (0, Some(minor), _) => Version::new(0, minor + 1, 0),
+            _ => Version::new(major + 1, 0, 0),
+        }),
+        _ => None,
+    }
+}
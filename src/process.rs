@@ -0,0 +1,343 @@
+//! Policies for running the compiler and pip as subprocesses: timeouts, cancellation and
+//! retries.
+
+pub use crate::vyper_errors::Exit;
+use crate::{audit, vyper_errors::VyperErrors};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Read,
+    process::{Command, Output, Stdio},
+    thread,
+    time::Duration,
+};
+#[cfg(feature = "async")]
+use tokio_util::sync::CancellationToken;
+use wait_timeout::ChildExt;
+
+/// Emits a `tracing` event at debug level when the `tracing` feature is enabled.
+macro_rules! debug_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+pub(crate) use debug_event;
+
+/// Emits a `tracing` event at info level when the `tracing` feature is enabled.
+macro_rules! info_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::info!($($arg)*);
+    };
+}
+pub(crate) use info_event;
+
+/// How often and how patiently a failed subprocess is retried. The default policy makes a single
+/// attempt. The delay before each retry is `backoff * multiplier^(retry - 1)`.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry
+    pub backoff: Duration,
+    /// Factor the delay grows by after every retry
+    pub multiplier: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            backoff: Duration::from_millis(500),
+            multiplier: 2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(attempts: u32, backoff: Duration) -> Self {
+        Self {
+            attempts,
+            backoff,
+            ..Default::default()
+        }
+    }
+
+    /// Never retries
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn set_multiplier(mut self, multiplier: u32) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Delay before the `retry`th retry, counting from 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(self.multiplier.saturating_pow(retry.saturating_sub(1)))
+    }
+}
+
+/// Renders `cmd` as it would be typed in a shell, quoting arguments that contain whitespace
+pub(crate) fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("{:?}", arg)
+            } else {
+                arg.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether a finished attempt should be retried. IO errors and timeouts are always transient;
+/// an unsuccessful exit status is only worth retrying for commands like pip that talk to the
+/// network, since a compiler error will fail the same way every time.
+fn should_retry(res: &Result<Output, VyperErrors>, retry_status: bool) -> bool {
+    match res {
+        Ok(out) => retry_status && !out.status.success(),
+        Err(VyperErrors::IoError(_)) | Err(VyperErrors::TimeoutError(_)) => true,
+        Err(_) => false,
+    }
+}
+
+pub(crate) trait CommandExt {
+    /// Like `Command::output`, but kills the process and returns `VyperErrors::TimeoutError`
+    /// if it runs for longer than `timeout`.
+    fn output_timeout(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Output, VyperErrors>;
+
+    /// `output_timeout`, retried according to `retry` if the process can't be run or times out
+    fn output_retry(
+        &mut self,
+        timeout: Option<Duration>,
+        retry: &RetryPolicy,
+    ) -> Result<Output, VyperErrors> {
+        self.output_retry_inner(timeout, retry, false)
+    }
+
+    /// `output_retry`, but also retries when the process exits unsuccessfully
+    fn output_retry_status(
+        &mut self,
+        timeout: Option<Duration>,
+        retry: &RetryPolicy,
+    ) -> Result<Output, VyperErrors> {
+        self.output_retry_inner(timeout, retry, true)
+    }
+
+    fn output_retry_inner(
+        &mut self,
+        timeout: Option<Duration>,
+        retry: &RetryPolicy,
+        retry_status: bool,
+    ) -> Result<Output, VyperErrors> {
+        let mut attempt = 1;
+        loop {
+            let res = self.output_timeout(timeout);
+            if attempt >= retry.attempts || !should_retry(&res, retry_status) {
+                return res;
+            }
+            thread::sleep(retry.delay(attempt));
+            attempt += 1;
+        }
+    }
+}
+
+impl CommandExt for Command {
+    fn output_timeout(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Output, VyperErrors> {
+        let trace = Trace::start(self);
+        let res = run_timeout(self, timeout);
+        trace.finish(&res);
+        res
+    }
+}
+
+fn run_timeout(
+    cmd: &mut Command,
+    timeout: Option<Duration>,
+) -> Result<Output, VyperErrors> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => return Ok(cmd.output()?),
+    };
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // drain the pipes while waiting so a chatty process can't block on a full buffer
+    let stdout = child.stdout.take().map(drain);
+    let stderr = child.stderr.take().map(drain);
+    match child.wait_timeout(timeout)? {
+        Some(status) => Ok(Output {
+            status,
+            stdout: join(stdout),
+            stderr: join(stderr),
+        }),
+        None => {
+            child.kill()?;
+            child.wait()?;
+            Err(VyperErrors::TimeoutError(timeout))
+        }
+    }
+}
+
+/// A span around a single subprocess call, recording the binary, arguments, duration and exit
+/// status. The span is only created with the `tracing` feature, and the call is only written
+/// to the audit log when one is installed, see the `audit` module.
+struct Trace {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    start: std::time::Instant,
+    audit: Option<audit::PendingRecord>,
+}
+
+impl Trace {
+    #[cfg(feature = "tracing")]
+    fn start(cmd: &Command) -> Trace {
+        let span = tracing::info_span!(
+            "subprocess",
+            bin = %cmd.get_program().to_string_lossy(),
+            args = ?cmd.get_args().collect::<Vec<_>>(),
+            duration_ms = tracing::field::Empty,
+            status = tracing::field::Empty,
+        );
+        Trace {
+            span,
+            start: std::time::Instant::now(),
+            audit: audit::start(cmd),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn start(cmd: &Command) -> Trace {
+        Trace {
+            audit: audit::start(cmd),
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    fn finish(self, res: &Result<Output, VyperErrors>) {
+        let elapsed = self.start.elapsed();
+        self.span.record("duration_ms", elapsed.as_millis() as u64);
+        self.span.in_scope(|| match res {
+            Ok(out) => {
+                self.span
+                    .record("status", tracing::field::display(out.status));
+                tracing::debug!(status = %out.status, ?elapsed, "subprocess exited");
+            }
+            Err(e) => tracing::warn!(error = %e, ?elapsed, "subprocess failed"),
+        });
+        audit_finish(self.audit, res);
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn finish(self, res: &Result<Output, VyperErrors>) {
+        audit_finish(self.audit, res);
+    }
+}
+
+fn audit_finish(audit: Option<audit::PendingRecord>, res: &Result<Output, VyperErrors>) {
+    if let Some(audit) = audit {
+        match res {
+            Ok(out) => audit.finish(Some((&out.status).into()), None),
+            Err(e) => audit.finish(None, Some(e.to_string())),
+        }
+    }
+}
+
+fn drain<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+fn join(handle: Option<thread::JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    handle.and_then(|h| h.join().ok()).unwrap_or_default()
+}
+
+/// Runs `cmd` to completion on the tokio runtime, retrying according to `retry` if it can't be
+/// run or times out. The process is killed if it outlives `timeout` or if `cancel` is triggered
+/// first.
+#[cfg(feature = "async")]
+pub(crate) async fn output_async(
+    mut cmd: tokio::process::Command,
+    timeout: Option<Duration>,
+    retry: &RetryPolicy,
+    cancel: &CancellationToken,
+) -> Result<Output, VyperErrors> {
+    cmd.kill_on_drop(true);
+    let mut attempt = 1;
+    loop {
+        let trace = Trace::start(cmd.as_std());
+        let run = async {
+            match timeout {
+                Some(t) => tokio::time::timeout(t, cmd.output())
+                    .await
+                    .map_err(|_| VyperErrors::TimeoutError(t))?
+                    .map_err(VyperErrors::from),
+                None => cmd.output().await.map_err(VyperErrors::from),
+            }
+        };
+        let res = tokio::select! {
+            out = run => out,
+            _ = cancel.cancelled() => Err(VyperErrors::Cancelled),
+        };
+        trace.finish(&res);
+        if attempt >= retry.attempts || !should_retry(&res, false) {
+            return res;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(retry.delay(attempt)) => {},
+            _ = cancel.cancelled() => return Err(VyperErrors::Cancelled),
+        }
+        attempt += 1;
+    }
+}
+
+/// Runs `cmd` to completion on the tokio runtime with `input` written to its stdin, killing it
+/// if it outlives `timeout`
+#[cfg(feature = "server")]
+pub(crate) async fn output_async_with_input(
+    mut cmd: tokio::process::Command,
+    input: &[u8],
+    timeout: Option<Duration>,
+) -> Result<Output, VyperErrors> {
+    use tokio::io::AsyncWriteExt;
+    cmd.kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let trace = Trace::start(cmd.as_std());
+    let run = async {
+        let mut child = cmd.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input).await?;
+        }
+        Ok(child.wait_with_output().await?)
+    };
+    let res = match timeout {
+        Some(t) => tokio::time::timeout(t, run)
+            .await
+            .unwrap_or(Err(VyperErrors::TimeoutError(t))),
+        None => run.await,
+    };
+    trace.finish(&res);
+    res
+}
@@ -0,0 +1,889 @@
+//! A vyper project: a root directory and the contracts found below it, compiled with shared
+//! settings.
+
+use crate::{
+    abi::Abi,
+    artifacts::{self, Artifact, Artifacts, Manifest, ARTIFACTS_DIR},
+    codegen::{self, INTERFACES_DIR},
+    diagnostics::Diagnostic,
+    graph::{DependencyGraph, GraphEdge, NodeKind},
+    lock::{LockedContract, Lockfile, LOCKFILE},
+    policy::Policy,
+    utils::{self, HashAlgorithm, SourceUnit},
+    vyper::{Evm, Vypers},
+    vyper_errors::VyperErrors,
+};
+#[cfg(feature = "async")]
+use crate::{
+    attest::{Attestation, AttestedContract},
+    backend::Backend,
+    bytecode::Bytecode,
+    diagnostics::{self, LspDiagnostic},
+    vyper,
+};
+#[cfg(feature = "async")]
+use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+#[cfg(feature = "async")]
+use tokio::task::JoinHandle;
+#[cfg(feature = "async")]
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Project {
+    pub root: PathBuf,
+    pub contracts: Vypers,
+    /// EVM version the contracts are compiled for, `None` for the compiler's default.
+    /// Overridden per contract by `Vypers::settings`.
+    pub evm_version: Option<Evm>,
+    /// Directory generated interfaces are written to, relative to the root
+    pub interfaces: PathBuf,
+    /// Names artifacts after the hash of their contract's build, see `artifact_path`, instead
+    /// of mirroring its path, i.e. `artifacts/{hash}.json`, when set
+    pub content_addressed: Option<HashAlgorithm>,
+}
+
+impl Project {
+    pub fn new(root: PathBuf, contracts: Vypers) -> Project {
+        Project {
+            root,
+            contracts,
+            evm_version: None,
+            interfaces: PathBuf::from(INTERFACES_DIR),
+            content_addressed: None,
+        }
+    }
+
+    pub fn set_evm_version(mut self, evm_version: Evm) -> Project {
+        self.evm_version = Some(evm_version);
+        self
+    }
+
+    pub fn set_interfaces(mut self, interfaces: PathBuf) -> Project {
+        self.interfaces = interfaces;
+        self
+    }
+
+    pub fn set_content_addressed(mut self, algorithm: HashAlgorithm) -> Project {
+        self.content_addressed = Some(algorithm);
+        self
+    }
+
+    /// Where the artifact of `contract` is written by `write_artifacts`. Content addressed
+    /// artifacts are named after a hash of the contract's source, its imports, the compiler
+    /// version and the settings, which asks the compiler for its version.
+    pub fn artifact_path(&self, contract: &Path) -> Result<PathBuf, VyperErrors> {
+        let target = match self.content_addressed {
+            Some(_) => Some(self.target(contract)?),
+            None => None,
+        };
+        self.artifact_path_for(contract, target.as_ref())
+    }
+
+    /// `artifact_path`, with the compiler version and EVM version of `contract`, see
+    /// `targets`, when content addressed
+    fn artifact_path_for(
+        &self,
+        contract: &Path,
+        target: Option<&(String, Option<String>)>,
+    ) -> Result<PathBuf, VyperErrors> {
+        let dir = self.root.join(ARTIFACTS_DIR);
+        Ok(match (self.content_addressed, target) {
+            (Some(algorithm), Some((compiler, evm_version))) => {
+                let optimize = self.contracts.optimize.map(|o| o.to_string());
+                let hash = content_hash(
+                    algorithm,
+                    contract,
+                    &self.root,
+                    compiler,
+                    evm_version.as_deref(),
+                    optimize.as_deref(),
+                )?;
+                dir.join(format!("{}.json", hash))
+            }
+            _ => artifacts::artifact_path(&dir, &self.root, contract),
+        })
+    }
+
+    /// Writes the interface generated from `abi` by `codegen::vyi_from_abi` to `{name}.vyi` in
+    /// the interfaces directory, so contracts can `import` it. Returns the written file.
+    /// Characters that can't be part of a module name, such as path separators, are replaced
+    /// with `_`, and a name that is empty or starts with a digit is prefixed with one.
+    pub fn write_interface(&self, name: &str, abi: &Abi) -> Result<PathBuf, VyperErrors> {
+        let dir = self.root.join(&self.interfaces);
+        fs::create_dir_all(&dir)?;
+        let mut name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            name.insert(0, '_');
+        }
+        let path = dir.join(format!("{}.vyi", name));
+        fs::write(&path, codegen::vyi_from_abi(abi))?;
+        Ok(path)
+    }
+
+    /// Opens the project at `root`, collecting its contracts with `utils::scan_workspace`
+    #[cfg(feature = "async")]
+    pub async fn open(root: PathBuf) -> Result<Project, VyperErrors> {
+        let workspace = utils::scan_workspace(root).await?;
+        Ok(Project {
+            root: workspace.root,
+            contracts: Vypers::new(workspace.contracts),
+            evm_version: None,
+            interfaces: PathBuf::from(INTERFACES_DIR),
+            content_addressed: None,
+        })
+    }
+
+    /// Compiles every contract and collects a diagnostic for each one that fails, rather than
+    /// stopping at the first error like `Vypers::compile_many`
+    #[cfg(feature = "async")]
+    pub async fn diagnostics(&self) -> Result<Vec<Diagnostic>, VyperErrors> {
+        let paths = Arc::new(self.contracts.path_to_code.clone());
+        let vy = self.contracts.resolve_compilers();
+        let timeout = self.contracts.timeout;
+        let retry = self.contracts.retry;
+        let optimize = self.contracts.optimize;
+        let evm_versions = self.contracts.evm_versions(self.evm_version.as_ref());
+        let cancel = CancellationToken::new();
+        let mut threads: Vec<JoinHandle<Option<Diagnostic>>> = vec![];
+        for (i, evm_version) in evm_versions.into_iter().enumerate() {
+            let paths = Arc::clone(&paths);
+            let bin = Arc::clone(&vy[i]);
+            let cancel = cancel.clone();
+            let cthread = tokio::spawn(async move {
+                let evm_version = evm_version.as_deref();
+                vyper::compile_async(
+                    &*bin,
+                    &paths[i],
+                    evm_version,
+                    optimize.as_ref(),
+                    timeout,
+                    &retry,
+                    &cancel,
+                )
+                .await
+                .err()
+                .map(|e| Diagnostic::from(&e))
+            });
+            threads.push(cthread);
+        }
+        let mut out = vec![];
+        for child_thread in threads {
+            out.extend(child_thread.await?);
+        }
+        Ok(out)
+    }
+
+    /// Checks the contract at `path` with the project's settings and reports any error in the
+    /// form a language server publishes, so an editor extension can use vyper-rs as its
+    /// compilation backend. Only the compiler's front end is run, see `Vyper::check`. Failures
+    /// to run the compiler at all are reported as a diagnostic at the start of the file.
+    #[cfg(feature = "async")]
+    pub async fn check(&self, path: &Path) -> Vec<LspDiagnostic> {
+        let result = vyper::check_async(
+            &*self.contracts.resolve_compiler_for(path),
+            path,
+            self.contracts.timeout,
+            &self.contracts.retry,
+            &CancellationToken::new(),
+        )
+        .await;
+        match result {
+            Ok(_) => vec![],
+            Err(e) => {
+                let source = fs::read_to_string(path).unwrap_or_default();
+                vec![LspDiagnostic::new(&Diagnostic::from(&e), &source)]
+            }
+        }
+    }
+
+    /// The compiler version and effective EVM version of each contract, in the order of
+    /// `Vypers::path_to_code`
+    pub(crate) fn targets(&self) -> Result<Vec<(String, Option<String>)>, VyperErrors> {
+        // contracts share a compiler unless assigned their own, so only ask each one once
+        let mut versions: BTreeMap<_, String> = BTreeMap::new();
+        let overrides = self.contracts.evm_versions(self.evm_version.as_ref());
+        let mut out = Vec::with_capacity(overrides.len());
+        for (path, evm_version) in self.contracts.path_to_code.iter().zip(overrides) {
+            let assigned = self
+                .contracts
+                .settings
+                .get(path)
+                .map(|settings| (settings.compiler.clone(), settings.venv.clone()));
+            let compiler = match versions.get(&assigned) {
+                Some(version) => version.clone(),
+                None => {
+                    let version = self.contracts.get_version_for(path)?.trim().to_owned();
+                    versions.insert(assigned, version.clone());
+                    version
+                }
+            };
+            // the effective target, so nothing depends on the compiler's default
+            let evm_version = evm_version.or_else(|| {
+                utils::parse_version(&compiler)
+                    .ok()
+                    .map(|v| Evm::default_for(&v).to_string())
+            });
+            out.push((compiler, evm_version));
+        }
+        Ok(out)
+    }
+
+    /// `targets` of `contract`, which may not be one of the project's contracts
+    fn target(&self, contract: &Path) -> Result<(String, Option<String>), VyperErrors> {
+        let paths = &self.contracts.path_to_code;
+        if let Some(i) = paths.iter().position(|path| path == contract) {
+            return Ok(self.targets()?.swap_remove(i));
+        }
+        let compiler = self.contracts.get_version_for(contract)?.trim().to_owned();
+        let evm_version = self
+            .evm_version
+            .clone()
+            .or_else(|| Some(Evm::default_for(&utils::parse_version(&compiler).ok()?)))
+            .map(|evm| evm.to_string());
+        Ok((compiler, evm_version))
+    }
+
+    /// The lockfile describing the project as it is now
+    pub fn lock(&self) -> Result<Lockfile, VyperErrors> {
+        let mut contracts = BTreeMap::new();
+        let targets = self.targets()?;
+        for (path, (compiler, evm_version)) in
+            self.contracts.path_to_code.iter().zip(targets)
+        {
+            let key = path.strip_prefix(&self.root).unwrap_or(path).to_path_buf();
+            let optimize = self.contracts.optimize.map(|o| o.to_string());
+            let locked = LockedContract::new(path, compiler, evm_version, optimize)?;
+            contracts.insert(key, locked);
+        }
+        Ok(Lockfile::new(contracts))
+    }
+
+    /// Compiles every contract and writes its artifact below `artifacts/` in the project root,
+    /// mirroring the contract's path. Existing artifacts are handled according to the
+    /// contracts' `overwrite` policy, and the ones kept are returned as read.
+    #[cfg(feature = "async")]
+    pub async fn write_artifacts(&self) -> Result<Artifacts, VyperErrors> {
+        self.write_artifacts_as(None).await
+    }
+
+    /// Compiles every contract for each of `evm_versions`, writing the artifacts like
+    /// `write_artifacts` with the EVM version as suffix, i.e. `artifacts/token.cancun.json`.
+    /// The EVM versions take precedence over the ones set per contract.
+    #[cfg(feature = "async")]
+    pub async fn compile_matrix(
+        &self,
+        evm_versions: &[Evm],
+    ) -> Result<BTreeMap<Evm, Artifacts>, VyperErrors> {
+        let mut out = BTreeMap::new();
+        for evm_version in evm_versions {
+            let mut project = self.clone().set_evm_version(evm_version.clone());
+            for settings in project.contracts.settings.values_mut() {
+                settings.evm_version = None;
+            }
+            let artifacts = project
+                .write_artifacts_as(Some(evm_version.as_str()))
+                .await?;
+            out.insert(evm_version.clone(), artifacts);
+        }
+        Ok(out)
+    }
+
+    /// `write_artifacts`, adding `suffix` to the file names
+    #[cfg(feature = "async")]
+    async fn write_artifacts_as(
+        &self,
+        suffix: Option<&str>,
+    ) -> Result<Artifacts, VyperErrors> {
+        let targets = self.targets()?;
+        let mut artifact_paths = Vec::with_capacity(targets.len());
+        for (path, target) in self.contracts.path_to_code.iter().zip(&targets) {
+            let mut artifact_path = self.artifact_path_for(path, Some(target))?;
+            if let Some(suffix) = suffix {
+                artifact_path.set_extension(format!("{}.json", suffix));
+            }
+            let write = self
+                .contracts
+                .overwrite
+                .should_write(path, &artifact_path)?;
+            artifact_paths.push((artifact_path, write));
+        }
+        if artifact_paths.iter().all(|(_, write)| !write) {
+            return artifact_paths
+                .iter()
+                .map(|(path, _)| Artifact::read(path))
+                .collect::<Result<_, _>>()
+                .map(Artifacts);
+        }
+        let mut contracts = self.contracts.clone();
+        match &self.evm_version {
+            Some(evm_version) => contracts.compile_many_ver(evm_version.clone()).await?,
+            None => contracts.compile_many().await?,
+        }
+        let abis = contracts.get_abi_many().await?;
+        // best effort, compilers before 0.3.10 have no metadata output
+        let metadata = match &self.evm_version {
+            Some(evm_version) => {
+                contracts.get_metadata_many_ver(evm_version.clone()).await
+            }
+            None => contracts.get_metadata_many().await,
+        };
+        let mut metadata = metadata.ok().into_iter().flatten();
+        let bytecode = contracts.bytecode.take().unwrap_or_default();
+        let mut out = Vec::with_capacity(abis.len());
+        for (i, (abi, (compiler, evm_version))) in
+            abis.into_iter().zip(targets).enumerate()
+        {
+            let path = &contracts.path_to_code[i];
+            let (artifact_path, write) = &artifact_paths[i];
+            let artifact = Artifact::new(
+                path.clone(),
+                contracts.abi[i].clone(),
+                bytecode.get(i).cloned(),
+                abi,
+                Some(compiler),
+                evm_version,
+            );
+            let artifact = match contracts.optimize {
+                Some(optimize) => artifact.with_optimize(optimize),
+                None => artifact,
+            };
+            let artifact = match metadata.next() {
+                Some(metadata) => artifact.with_metadata(metadata),
+                None => artifact,
+            };
+            if !write {
+                out.push(Artifact::read(artifact_path)?);
+                continue;
+            }
+            artifact.write(artifact_path)?;
+            Manifest::record(&self.root, path, [artifact_path.as_path()])?;
+            out.push(artifact);
+        }
+        Ok(Artifacts(out))
+    }
+
+    /// Reads the artifact of every contract written by `write_artifacts`, for deploying
+    /// without a compiler. See `Vyper::from_artifact`.
+    pub fn load_artifacts(&self) -> Result<Artifacts, VyperErrors> {
+        let targets = match self.content_addressed {
+            Some(_) => Some(self.targets()?),
+            None => None,
+        };
+        self.contracts
+            .path_to_code
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let target = targets.as_ref().map(|targets| &targets[i]);
+                Artifact::read(&self.artifact_path_for(path, target)?)
+            })
+            .collect::<Result<_, _>>()
+            .map(Artifacts)
+    }
+
+    /// Deletes the artifacts written by `write_artifacts` and the outputs tracked for the
+    /// project's contracts in the current directory (see `Vyper::track_outputs`), such as their
+    /// ABIs. Only files recorded in a manifest are deleted. Returns the deleted files.
+    pub fn clean(&self) -> Result<Vec<PathBuf>, VyperErrors> {
+        let contracts = &self.contracts.path_to_code;
+        let mut removed = Manifest::clean(&self.root, |contract| {
+            contracts.iter().any(|c| c == contract)
+        })?;
+        removed.extend(Manifest::clean(Path::new("."), |contract| {
+            contracts.iter().any(|c| c == contract)
+        })?);
+        // drop the directories emptied along the way, up to and including `artifacts/`
+        let dir = self.root.join(ARTIFACTS_DIR);
+        for file in &removed {
+            for parent in file.ancestors().skip(1) {
+                if !parent.starts_with(&dir) || fs::remove_dir(parent).is_err() {
+                    break;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Writes `lock()` to `vyper.lock` in the project root
+    pub fn write_lock(&self) -> Result<Lockfile, VyperErrors> {
+        let lockfile = self.lock()?;
+        lockfile.write(&self.root.join(LOCKFILE))?;
+        Ok(lockfile)
+    }
+
+    /// Fails with `LockDrift` when the compiler version, settings or any source differ from
+    /// what `vyper.lock` records, or contracts were added or removed since it was written
+    pub fn check_lock(&self) -> Result<(), VyperErrors> {
+        let locked = Lockfile::read(&self.root.join(LOCKFILE))?;
+        let drift = locked.diff(&self.lock()?);
+        if !drift.is_empty() {
+            Err(VyperErrors::LockDrift(drift))?
+        }
+        Ok(())
+    }
+
+    /// Compiles every contract and attests the build, see `attest::Attestation`
+    #[cfg(feature = "async")]
+    pub async fn attest(&self) -> Result<Attestation, VyperErrors> {
+        let mut contracts = self.contracts.clone();
+        match &self.evm_version {
+            Some(evm_version) => contracts.compile_many_ver(evm_version.clone()).await?,
+            None => contracts.compile_many().await?,
+        }
+        let bytecode = contracts.bytecode.take().unwrap_or_default();
+        let targets = self.targets()?;
+        let mut attested = BTreeMap::new();
+        for ((path, bytecode), (compiler, evm_version)) in
+            contracts.path_to_code.iter().zip(bytecode).zip(targets)
+        {
+            let compiler_sha256 = match &contracts.backend {
+                Backend::Native => match utils::which(&contracts.get_vyper_for(path)) {
+                    Some(bin) => compiler_sha256(&bin)?,
+                    None => None,
+                },
+                _ => None,
+            };
+            let key = path.strip_prefix(&self.root).unwrap_or(path).to_path_buf();
+            attested.insert(
+                key,
+                AttestedContract {
+                    source_sha256: utils::sha256_file(path)?,
+                    compiler,
+                    compiler_sha256,
+                    evm_version,
+                    optimize: contracts.optimize.map(|o| o.to_string()),
+                    bytecode_sha256: utils::sha256_hex(bytecode.as_bytes()),
+                },
+            );
+        }
+        Ok(Attestation::new(attested))
+    }
+
+    /// Contracts of the project each contract imports, keyed by index in
+    /// `Vypers::path_to_code`. Imports of modules outside the project, such as `ethereum.ercs`,
+    /// are left out.
+    fn imports(&self) -> Result<Vec<BTreeSet<usize>>, VyperErrors> {
+        let indices = self.indices();
+        self.contracts
+            .path_to_code
+            .iter()
+            .map(|path| {
+                let unit = SourceUnit::parse(path)?;
+                let mut deps = BTreeSet::new();
+                for module in &unit.imports {
+                    deps.extend(
+                        self.resolve(&indices, path, module).into_iter().flatten(),
+                    );
+                }
+                Ok(deps)
+            })
+            .collect()
+    }
+
+    /// Indices in `Vypers::path_to_code` of each contract path
+    fn indices(&self) -> BTreeMap<&Path, Vec<usize>> {
+        let mut indices: BTreeMap<&Path, Vec<usize>> = BTreeMap::new();
+        for (i, path) in self.contracts.path_to_code.iter().enumerate() {
+            indices.entry(path).or_default().push(i);
+        }
+        indices
+    }
+
+    /// The contracts `module`, imported by the contract at `path`, resolves to. `None` for
+    /// modules outside the project.
+    fn resolve<'i>(
+        &self,
+        indices: &'i BTreeMap<&Path, Vec<usize>>,
+        path: &Path,
+        module: &str,
+    ) -> Option<&'i Vec<usize>> {
+        module_candidates(path, &self.root, module)
+            .into_iter()
+            .filter(|candidate| candidate != path)
+            .find_map(|candidate| indices.get(candidate.as_path()))
+    }
+
+    /// Which contracts import which, including the modules imported from outside the
+    /// project, see the `graph` module
+    pub fn dependency_graph(&self) -> Result<DependencyGraph, VyperErrors> {
+        let paths = &self.contracts.path_to_code;
+        let name = |path: &Path| {
+            path.strip_prefix(&self.root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let mut graph = DependencyGraph::default();
+        for path in paths {
+            graph.node(&name(path), NodeKind::Contract);
+        }
+        let indices = self.indices();
+        for (i, path) in paths.iter().enumerate() {
+            let unit = SourceUnit::parse(path)?;
+            let from = graph.node(&name(path), NodeKind::Contract);
+            for module in &unit.imports {
+                let implements = module.rsplit('.').next().map_or(false, |last| {
+                    unit.implements.iter().any(|name| name == last)
+                });
+                let targets =
+                    match self.resolve(&indices, path, module) {
+                        Some(found) => found
+                            .iter()
+                            .filter(|&&j| j != i)
+                            .map(|&j| graph.node(&name(&paths[j]), NodeKind::Contract))
+                            .collect(),
+                        None => vec![graph
+                            .node(module.trim_start_matches('.'), NodeKind::External)],
+                    };
+                for to in targets {
+                    let edge = GraphEdge {
+                        from,
+                        to,
+                        implements,
+                    };
+                    if !graph.edges.contains(&edge) {
+                        graph.edges.push(edge);
+                    }
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Checks every contract against `policy`, returning the violations in the order of
+    /// `Vypers::path_to_code`. Contracts are read, not compiled.
+    pub fn enforce_policy(
+        &self,
+        policy: &Policy,
+    ) -> Result<Vec<Diagnostic>, VyperErrors> {
+        let mut violations = Vec::new();
+        for path in &self.contracts.path_to_code {
+            let source = fs::read_to_string(path)?;
+            violations.extend(policy.check(path, &source));
+        }
+        Ok(violations)
+    }
+
+    /// Indices of the contracts grouped so that every contract comes after the modules it
+    /// imports. Contracts of a group don't depend on each other.
+    fn levels(&self) -> Result<Vec<Vec<usize>>, VyperErrors> {
+        let imports = self.imports()?;
+        let mut done = vec![false; imports.len()];
+        let mut levels = Vec::new();
+        while done.iter().any(|d| !d) {
+            let level: Vec<usize> = (0..imports.len())
+                .filter(|&i| !done[i] && imports[i].iter().all(|&dep| done[dep]))
+                .collect();
+            if level.is_empty() {
+                let cycle = (0..imports.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| self.contracts.path_to_code[i].clone())
+                    .collect();
+                Err(VyperErrors::ImportCycle(cycle))?
+            }
+            level.iter().for_each(|&i| done[i] = true);
+            levels.push(level);
+        }
+        Ok(levels)
+    }
+
+    /// The contracts in the order `compile_all` compiles them: modules before the contracts
+    /// importing them. Fails with `ImportCycle` when contracts import each other.
+    pub fn build_order(&self) -> Result<Vec<Vec<PathBuf>>, VyperErrors> {
+        Ok(self
+            .levels()?
+            .into_iter()
+            .map(|level| {
+                level
+                    .into_iter()
+                    .map(|i| self.contracts.path_to_code[i].clone())
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// The contracts among `changed` and every contract importing one of `changed`, directly
+    /// or not, in build order. `changed` can hold any file: imports are resolved against files,
+    /// so a changed interface or module that isn't itself a contract of the project, or a
+    /// deleted one, still affects its importers.
+    pub fn affected(&self, changed: &[PathBuf]) -> Result<Vec<PathBuf>, VyperErrors> {
+        Ok(self
+            .affected_levels(changed)?
+            .into_iter()
+            .flatten()
+            .map(|i| self.contracts.path_to_code[i].clone())
+            .collect())
+    }
+
+    fn affected_levels(
+        &self,
+        changed: &[PathBuf],
+    ) -> Result<Vec<Vec<usize>>, VyperErrors> {
+        let imports = self.imports()?;
+        let changed: BTreeSet<PathBuf> =
+            changed.iter().map(|path| normalize(path)).collect();
+        let mut affected = Vec::with_capacity(imports.len());
+        for path in &self.contracts.path_to_code {
+            let imports_changed = SourceUnit::parse(path)?.imports.iter().any(|module| {
+                module_candidates(path, &self.root, module)
+                    .iter()
+                    .any(|candidate| changed.contains(&normalize(candidate)))
+            });
+            affected.push(imports_changed || changed.contains(&normalize(path)));
+        }
+        // levels are in build order, so a single pass reaches every dependent
+        let levels = self.levels()?;
+        for &i in levels.iter().flatten() {
+            if imports[i].iter().any(|&dep| affected[dep]) {
+                affected[i] = true;
+            }
+        }
+        Ok(levels
+            .into_iter()
+            .map(|level| {
+                level
+                    .into_iter()
+                    .filter(|&i| affected[i])
+                    .collect::<Vec<_>>()
+            })
+            .filter(|level| !level.is_empty())
+            .collect())
+    }
+
+    /// Compiles the contracts with `indices` concurrently, returning their bytecode in the
+    /// same order
+    #[cfg(feature = "async")]
+    async fn compile_indices(
+        &self,
+        indices: &[usize],
+    ) -> Result<Vec<Bytecode>, VyperErrors> {
+        let mut contracts = self.contracts.clone();
+        contracts.path_to_code = indices
+            .iter()
+            .map(|&i| self.contracts.path_to_code[i].clone())
+            .collect();
+        contracts.abi = indices
+            .iter()
+            .map(|&i| self.contracts.abi[i].clone())
+            .collect();
+        contracts.bytecode = None;
+        match &self.evm_version {
+            Some(evm_version) => contracts.compile_many_ver(evm_version.clone()).await?,
+            None => contracts.compile_many().await?,
+        }
+        Ok(contracts.bytecode.unwrap_or_default())
+    }
+
+    /// Compiles every contract into `Vypers::bytecode`, modules before the contracts importing
+    /// them, so a broken module fails the build before any of its dependents is compiled
+    #[cfg(feature = "async")]
+    pub async fn compile_all(&mut self) -> Result<(), VyperErrors> {
+        let mut bytecode = vec![Bytecode::default(); self.contracts.path_to_code.len()];
+        for level in self.levels()? {
+            for (i, code) in level.iter().zip(self.compile_indices(&level).await?) {
+                bytecode[*i] = code;
+            }
+        }
+        self.contracts.bytecode = Some(bytecode);
+        Ok(())
+    }
+
+    /// Recompiles the contracts `affected` by a change to `changed` after a previous
+    /// `compile_all`, leaving the bytecode of every other contract as is. Compiles everything
+    /// when nothing was compiled yet. Returns the contracts that were compiled.
+    #[cfg(feature = "async")]
+    pub async fn compile_changed(
+        &mut self,
+        changed: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, VyperErrors> {
+        let compiled = self.contracts.bytecode.as_ref().map_or(false, |bytecode| {
+            bytecode.len() == self.contracts.path_to_code.len()
+        });
+        if !compiled {
+            self.compile_all().await?;
+            return Ok(self.contracts.path_to_code.clone());
+        }
+        let mut recompiled = Vec::new();
+        for level in self.affected_levels(changed)? {
+            let codes = self.compile_indices(&level).await?;
+            let bytecode = self.contracts.bytecode.get_or_insert_with(Vec::new);
+            for (i, code) in level.iter().zip(codes) {
+                bytecode[*i] = code;
+                recompiled.push(self.contracts.path_to_code[*i].clone());
+            }
+        }
+        Ok(recompiled)
+    }
+
+    /// Files below the project's root changed since the git revision `git_ref`, committed or
+    /// not, see `utils::git_changed_files`. Deleted files and modules that aren't contracts of
+    /// the project, such as `.vyi` interfaces, are included, to be passed to `affected`.
+    #[cfg(feature = "git")]
+    pub fn changed_since(&self, git_ref: &str) -> Result<Vec<PathBuf>, VyperErrors> {
+        utils::git_changed_files(&self.root, git_ref)
+    }
+
+    /// Compiles only the contracts changed since the git revision `git_ref` and the contracts
+    /// importing them, for CI runs that only need to check what a branch touched. The
+    /// bytecode of every other contract is left as is, empty when it was never compiled.
+    /// Returns the contracts that were compiled.
+    #[cfg(all(feature = "async", feature = "git"))]
+    pub async fn compile_changed_since(
+        &mut self,
+        git_ref: &str,
+    ) -> Result<Vec<PathBuf>, VyperErrors> {
+        let changed = self.changed_since(git_ref)?;
+        let len = self.contracts.path_to_code.len();
+        if self.contracts.bytecode.as_ref().map(Vec::len) != Some(len) {
+            self.contracts.bytecode = Some(vec![Bytecode::default(); len]);
+        }
+        let mut compiled = Vec::new();
+        for level in self.affected_levels(&changed)? {
+            let codes = self.compile_indices(&level).await?;
+            let bytecode = self.contracts.bytecode.get_or_insert_with(Vec::new);
+            for (i, code) in level.iter().zip(codes) {
+                bytecode[*i] = code;
+                compiled.push(self.contracts.path_to_code[*i].clone());
+            }
+        }
+        Ok(compiled)
+    }
+
+    /// `diagnostics` as newline-delimited JSON, for editors and CI annotators
+    #[cfg(feature = "async")]
+    pub async fn diagnostics_json(&self) -> Result<String, VyperErrors> {
+        diagnostics::to_json_lines(&self.diagnostics().await?)
+    }
+}
+
+/// Files the module `module`, as recorded in `SourceUnit::imports`, may refer to when imported
+/// by `contract`, most specific first. `from a import b` is recorded as `a.b`, which is either
+/// the module `a/b.vy` or a member of `a.vy`; leading dots make the import relative to the
+/// contract's directory, `.a` being in the directory itself and `..a` in its parent.
+fn module_candidates(contract: &Path, root: &Path, module: &str) -> Vec<PathBuf> {
+    let dir = contract.parent().unwrap_or(Path::new(""));
+    let relative = module.trim_start_matches('.');
+    let dots = module.len() - relative.len();
+    let bases: Vec<PathBuf> = match dots {
+        0 => vec![dir.to_path_buf(), root.to_path_buf()],
+        _ => dir
+            .ancestors()
+            .nth(dots - 1)
+            .map(Path::to_path_buf)
+            .into_iter()
+            .collect(),
+    };
+    let segments: Vec<&str> = relative.split('.').filter(|s| !s.is_empty()).collect();
+    let mut candidates = Vec::new();
+    for base in &bases {
+        for n in (1..=segments.len()).rev() {
+            let mut path = base.clone();
+            path.extend(&segments[..n]);
+            candidates.push(path.with_extension("vy"));
+            candidates.push(path.with_extension("vyi"));
+        }
+    }
+    candidates
+}
+
+/// The modules `contract` imports, directly or through other modules, that exist below `root`
+/// or relative to the importing file, sorted by path
+fn import_closure(contract: &Path, root: &Path) -> Result<Vec<PathBuf>, VyperErrors> {
+    let mut seen = BTreeSet::from([normalize(contract)]);
+    let mut imports = Vec::new();
+    let mut queue = vec![contract.to_path_buf()];
+    while let Some(file) = queue.pop() {
+        for module in SourceUnit::parse(&file)?.imports {
+            let found = module_candidates(&file, root, &module)
+                .into_iter()
+                .find(|candidate| candidate.is_file() && candidate != &file);
+            if let Some(found) = found {
+                if seen.insert(normalize(&found)) {
+                    imports.push(found.clone());
+                    queue.push(found);
+                }
+            }
+        }
+    }
+    imports.sort();
+    Ok(imports)
+}
+
+/// Hex digest with `algorithm` content addressing a build of `contract`: over its source, the
+/// path relative to the contract and the source of every module it imports (see
+/// `import_closure`), the compiler version and the settings, as in
+/// `artifacts::settings_fingerprint`
+pub(crate) fn content_hash(
+    algorithm: HashAlgorithm,
+    contract: &Path,
+    root: &Path,
+    compiler: &str,
+    evm_version: Option<&str>,
+    optimize: Option<&str>,
+) -> Result<String, VyperErrors> {
+    let mut manifest = format!("source {}\n", algorithm.file(contract)?);
+    let dir = normalize(contract.parent().unwrap_or(Path::new("")));
+    for module in import_closure(contract, root)? {
+        let module = normalize(&module);
+        let common = module
+            .components()
+            .zip(dir.components())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let relative: PathBuf = dir
+            .components()
+            .skip(common)
+            .map(|_| Component::ParentDir)
+            .chain(module.components().skip(common))
+            .collect();
+        manifest.push_str(&format!(
+            "import {} {}\n",
+            relative.to_string_lossy().replace('\\', "/"),
+            algorithm.file(&module)?
+        ));
+    }
+    manifest.push_str(&format!(
+        "settings {}\n",
+        artifacts::settings_fingerprint(Some(compiler), evm_version, optimize)
+    ));
+    Ok(algorithm.hex(manifest.as_bytes()))
+}
+
+/// `path` made absolute, with symlinks resolved as far as it exists, so paths from git and
+/// from contracts compare equal even for deleted files
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let absolute: PathBuf = std::env::current_dir()
+        .unwrap_or_default()
+        .join(path)
+        .components()
+        .collect();
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(dir), Some(name)) => normalize(dir).join(name),
+        _ => absolute,
+    }
+}
+
+/// Hash identifying the compiler at `bin`, see `AttestedContract::compiler_sha256`
+#[cfg(feature = "async")]
+fn compiler_sha256(bin: &Path) -> Result<Option<String>, VyperErrors> {
+    let bin = fs::canonicalize(bin)?;
+    // pip entry points live in `bin/` (`Scripts\` on windows) of the venv
+    if let Some(venv) = bin.parent().and_then(Path::parent) {
+        if venv.join("pyvenv.cfg").is_file() {
+            return crate::venv::distribution_sha256(venv);
+        }
+    }
+    let contents = fs::read(&bin)?;
+    Ok((!contents.starts_with(b"#!")).then(|| utils::sha256_hex(&contents)))
+}
@@ -0,0 +1,276 @@
+//! A job queue for services compiling on behalf of many callers. Unlike `Vypers`, which compiles
+//! a fixed set of contracts at once, a `CompileQueue` accepts jobs as they come in and:
+//!
+//! - runs at most `concurrency` compilers at any time, across every caller
+//! - starts higher priority jobs first, and jobs of the same priority in submission order
+//! - runs identical jobs only once while one is pending or running: the same source compiled by
+//!   the same compiler with the same settings. Every caller gets the result of that run.
+//!
+//! Jobs run on the tokio runtime, so `CompileQueue::submit` must be called from within one.
+
+use crate::{
+    backend::Backend,
+    bytecode::Bytecode,
+    process::RetryPolicy,
+    utils,
+    vyper::{self, Evm, Optimize, Vyper},
+    vyper_errors::VyperErrors,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// A contract to compile and how, see `CompileQueue::submit`
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Job {
+    pub path: PathBuf,
+    /// `None` for the compiler's default
+    pub evm_version: Option<Evm>,
+    /// `None` for the compiler's default
+    pub optimize: Option<Optimize>,
+    pub compiler: Option<PathBuf>,
+    pub venv: Option<PathBuf>,
+    pub backend: Backend,
+    pub timeout: Option<Duration>,
+    pub retry: RetryPolicy,
+}
+
+impl Job {
+    pub fn new(path: impl Into<PathBuf>) -> Job {
+        Job {
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    /// What makes two jobs produce the same bytecode
+    fn key(&self) -> JobKey {
+        JobKey {
+            path: self.path.clone(),
+            source_sha256: utils::sha256_file(&self.path).ok(),
+            evm_version: self.evm_version.clone(),
+            optimize: self.optimize,
+            compiler: self.compiler.clone(),
+            venv: self.venv.clone(),
+            backend: self.backend.clone(),
+        }
+    }
+
+    async fn run(&self, cancel: &CancellationToken) -> Result<Bytecode, VyperErrors> {
+        let bin = vyper::vyper_bin(self.compiler.as_deref(), self.venv.as_deref());
+        vyper::compile_async(
+            &*self.backend.resolve(bin),
+            &self.path,
+            self.evm_version.as_ref().map(Evm::as_str),
+            self.optimize.as_ref(),
+            self.timeout,
+            &self.retry,
+            cancel,
+        )
+        .await
+    }
+}
+
+impl From<&Vyper<'_>> for Job {
+    fn from(contract: &Vyper<'_>) -> Job {
+        Job {
+            path: contract.path_to_code.to_path_buf(),
+            evm_version: contract.evm_version.clone(),
+            optimize: contract.optimize,
+            compiler: contract.compiler.map(PathBuf::from),
+            venv: contract.venv.map(PathBuf::from),
+            backend: contract.backend.clone(),
+            timeout: contract.timeout,
+            retry: contract.retry,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct JobKey {
+    path: PathBuf,
+    source_sha256: Option<String>,
+    evm_version: Option<Evm>,
+    optimize: Option<Optimize>,
+    compiler: Option<PathBuf>,
+    venv: Option<PathBuf>,
+    backend: Backend,
+}
+
+type JobResult = Result<Bytecode, Arc<VyperErrors>>;
+
+/// A job waiting for a free slot
+#[derive(Debug)]
+struct Pending {
+    priority: Priority,
+    seq: u64,
+    key: JobKey,
+    job: Job,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    /// Higher priority first, then earlier submissions first
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    pending: BinaryHeap<Pending>,
+    running: usize,
+    /// Callers of every pending or running job
+    waiters: HashMap<JobKey, Vec<oneshot::Sender<JobResult>>>,
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Inner {
+    concurrency: usize,
+    state: Mutex<State>,
+    cancel: CancellationToken,
+}
+
+impl Inner {
+    /// Starts pending jobs until every slot is taken
+    fn dispatch(self: &Arc<Self>, state: &mut State) {
+        while state.running < self.concurrency {
+            let Some(Pending { key, job, .. }) = state.pending.pop() else {
+                break;
+            };
+            state.running += 1;
+            let inner = Arc::clone(self);
+            tokio::spawn(async move {
+                let res = job.run(&inner.cancel).await.map_err(Arc::new);
+                let mut state = inner.state.lock().unwrap();
+                state.running -= 1;
+                for waiter in state.waiters.remove(&key).into_iter().flatten() {
+                    let _ = waiter.send(res.clone());
+                }
+                inner.dispatch(&mut state);
+            });
+        }
+    }
+}
+
+/// See the module docs. Clones share the same queue.
+#[derive(Debug, Clone)]
+pub struct CompileQueue {
+    inner: Arc<Inner>,
+}
+
+impl CompileQueue {
+    /// A queue running at most `concurrency` compilers at once, at least one
+    pub fn new(concurrency: usize) -> CompileQueue {
+        CompileQueue {
+            inner: Arc::new(Inner {
+                concurrency: concurrency.max(1),
+                state: Mutex::new(State::default()),
+                cancel: CancellationToken::new(),
+            }),
+        }
+    }
+
+    /// Queues `job` and returns a future resolving to its bytecode. Dropping the future doesn't
+    /// cancel the job.
+    pub fn submit(&self, job: impl Into<Job>, priority: Priority) -> JobHandle {
+        let job = job.into();
+        let key = job.key();
+        let (tx, rx) = oneshot::channel();
+        let mut state = self.inner.state.lock().unwrap();
+        if let Some(waiters) = state.waiters.get_mut(&key) {
+            waiters.push(tx);
+            return JobHandle(rx);
+        }
+        state.waiters.insert(key.clone(), vec![tx]);
+        state.seq += 1;
+        let seq = state.seq;
+        state.pending.push(Pending {
+            priority,
+            seq,
+            key,
+            job,
+        });
+        self.inner.dispatch(&mut state);
+        JobHandle(rx)
+    }
+
+    /// Number of jobs waiting for a free slot
+    pub fn pending(&self) -> usize {
+        self.inner.state.lock().unwrap().pending.len()
+    }
+
+    /// Number of compilers running
+    pub fn running(&self) -> usize {
+        self.inner.state.lock().unwrap().running
+    }
+
+    /// Kills every running compiler and fails every job as cancelled, including the ones
+    /// submitted later
+    pub fn cancel(&self) {
+        self.inner.cancel.cancel();
+    }
+}
+
+/// The result of a job submitted to a `CompileQueue`. Failures are reported as
+/// `VyperErrors::QueuedJobFailed`, since a failed job may be shared by several callers.
+#[derive(Debug)]
+pub struct JobHandle(oneshot::Receiver<JobResult>);
+
+impl Future for JobHandle {
+    type Output = Result<Bytecode, VyperErrors>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|res| match res {
+            Ok(res) => res.map_err(VyperErrors::QueuedJobFailed),
+            // the job's task was dropped with the runtime
+            Err(_) => Err(VyperErrors::Cancelled),
+        })
+    }
+}
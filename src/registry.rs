@@ -0,0 +1,196 @@
+//! Publishing compiled contracts to an artifact store over HTTP, so deployment machines can
+//! pull what a build machine compiled without ever installing the compiler. Behind the
+//! `registry` feature.
+//!
+//! Artifacts are content addressed: a `RegistryEntry` is stored under the digest of its
+//! contract's canonical JSON, and pulling it checks the digest again. The store protocol is
+//! plain object storage, served by any HTTP server accepting uploads or an S3-compatible
+//! bucket:
+//!
+//! - `PUT <url>/<digest>.json` with a `RegistryEntry` body
+//! - `GET <url>/<digest>.json`, answered with that body
+//!
+//! Requests aren't signed with S3 credentials; headers added with `Registry::with_header`,
+//! like a bearer token, are sent with every request, so private buckets need a policy or a
+//! gateway accepting them.
+//!
+//! Entries carry signatures of their digest. With the `signing` feature,
+//! `RegistryEntry::sign` makes them and `Registry::pull_verified` only accepts entries signed
+//! by a given key.
+
+pub use crate::attest::ArtifactSignature;
+use crate::{
+    artifacts::CompiledContract, utils::HashAlgorithm, vyper_errors::VyperErrors,
+};
+use serde::{Deserialize, Serialize};
+
+/// Version of the entry format, bumped on incompatible changes
+pub const REGISTRY_VERSION: u32 = 1;
+
+/// A compiled contract as stored in a registry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub version: u32,
+    /// The vyper-rs version that published the contract
+    pub created_by: String,
+    pub algorithm: HashAlgorithm,
+    /// Digest of the contract's canonical JSON, its key in the registry
+    pub digest: String,
+    pub signatures: Vec<ArtifactSignature>,
+    pub contract: CompiledContract,
+}
+
+impl RegistryEntry {
+    pub fn new(
+        contract: CompiledContract,
+        algorithm: HashAlgorithm,
+    ) -> Result<RegistryEntry, VyperErrors> {
+        Ok(RegistryEntry {
+            version: REGISTRY_VERSION,
+            created_by: format!("vyper-rs {}", env!("CARGO_PKG_VERSION")),
+            algorithm,
+            digest: digest(&contract, algorithm)?,
+            signatures: vec![],
+            contract,
+        })
+    }
+
+    pub fn with_signature(mut self, signature: ArtifactSignature) -> RegistryEntry {
+        self.signatures.push(signature);
+        self
+    }
+
+    /// Adds a signature of the digest by `signer`
+    #[cfg(feature = "signing")]
+    pub fn sign(self, signer: &crate::signing::Signer) -> RegistryEntry {
+        let signature = signer.sign(&self.digest);
+        self.with_signature(signature)
+    }
+
+    /// Checks the entry and that its signatures include a valid one by `signer`, a public key
+    /// as returned by `signing::Signer::public_key`
+    #[cfg(feature = "signing")]
+    pub fn verify_signature(&self, signer: &str) -> Result<(), VyperErrors> {
+        self.verify()?;
+        crate::signing::verify_signature(&self.digest, &self.signatures, signer)
+    }
+
+    /// Fails when the contract doesn't hash to `digest`, i.e. the entry was altered
+    pub fn verify(&self) -> Result<(), VyperErrors> {
+        let found = digest(&self.contract, self.algorithm)?;
+        if found != self.digest {
+            Err(VyperErrors::DigestMismatch {
+                expected: self.digest.clone(),
+                found,
+            })?
+        }
+        Ok(())
+    }
+}
+
+/// Digest of `contract` serialized as compact JSON, which is stable as every map in it is
+/// sorted
+pub fn digest(
+    contract: &CompiledContract,
+    algorithm: HashAlgorithm,
+) -> Result<String, VyperErrors> {
+    Ok(algorithm.hex(serde_json::to_string(contract)?.as_bytes()))
+}
+
+/// An artifact store at `url`, see the module docs
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Registry {
+    pub url: String,
+    /// Hash function of pushed entries, keccak256 by default
+    pub algorithm: HashAlgorithm,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Registry {
+    pub fn new(url: impl Into<String>) -> Registry {
+        Registry {
+            url: url.into(),
+            algorithm: HashAlgorithm::Keccak256,
+            headers: vec![],
+        }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Registry {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sends the header with every request, e.g. `Authorization`
+    pub fn with_header(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Registry {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// URL of the entry stored under `digest`
+    pub fn entry_url(&self, digest: &str) -> String {
+        format!("{}/{}.json", self.url.trim_end_matches('/'), digest)
+    }
+
+    /// Uploads `contract` with `signatures`, returning the digest to pull it with
+    pub fn publish(
+        &self,
+        contract: CompiledContract,
+        signatures: Vec<ArtifactSignature>,
+    ) -> Result<String, VyperErrors> {
+        let mut entry = RegistryEntry::new(contract, self.algorithm)?;
+        entry.signatures = signatures;
+        self.push(&entry)?;
+        Ok(entry.digest)
+    }
+
+    /// Uploads `entry` under its digest, after checking it
+    pub fn push(&self, entry: &RegistryEntry) -> Result<(), VyperErrors> {
+        entry.verify()?;
+        self.request("PUT", &entry.digest)
+            .send_string(&serde_json::to_string(entry)?)
+            .map_err(|e| VyperErrors::RegistryError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Downloads the entry stored under `digest`, failing when its contract doesn't hash to it
+    pub fn pull(&self, digest: &str) -> Result<RegistryEntry, VyperErrors> {
+        let entry: RegistryEntry = self
+            .request("GET", digest)
+            .call()
+            .map_err(|e| VyperErrors::RegistryError(e.to_string()))?
+            .into_json()?;
+        if entry.digest != digest {
+            Err(VyperErrors::DigestMismatch {
+                expected: digest.to_owned(),
+                found: entry.digest.clone(),
+            })?
+        }
+        entry.verify()?;
+        Ok(entry)
+    }
+
+    /// Like `pull`, also failing unless the entry is signed by `signer`, see
+    /// `RegistryEntry::verify_signature`
+    #[cfg(feature = "signing")]
+    pub fn pull_verified(
+        &self,
+        digest: &str,
+        signer: &str,
+    ) -> Result<RegistryEntry, VyperErrors> {
+        let entry = self.pull(digest)?;
+        entry.verify_signature(signer)?;
+        Ok(entry)
+    }
+
+    fn request(&self, method: &str, digest: &str) -> ureq::Request {
+        self.headers.iter().fold(
+            ureq::request(method, &self.entry_url(digest))
+                .set("Content-Type", "application/json"),
+            |request, (name, value)| request.set(name, value),
+        )
+    }
+}
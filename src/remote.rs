@@ -0,0 +1,146 @@
+//! Compiling on a remote compile farm over HTTP, behind the `remote` feature.
+//!
+//! `RemoteBackend` plugs into the `CompilerBackend` trait like any other backend: the command
+//! it builds runs the `vyper-remote` client shipped with this crate
+//! (`cargo install vyper-rs --features remote --bin vyper-remote`), which accepts the same
+//! arguments as the vyper CLI, sends the contract to the server as standard-json and prints
+//! the outputs the way the CLI would. The server protocol is:
+//!
+//! - `POST <url>` with a standard-json input body, answered with the standard-json output
+//! - `GET <url>/version`, answered with the output of `vyper --version`
+//!
+//! Only the contract's own source is sent, so contracts importing other modules of the
+//! project can't be compiled remotely. See `standard_json`.
+
+use crate::{
+    backend::CompilerBackend,
+    standard_json::{render_output, standard_json_input},
+};
+use serde_json::Value;
+use std::{io::Write, path::PathBuf, process::Command};
+
+/// Name of the client executable, looked up in PATH unless set with `RemoteBackend::with_client`
+pub const CLIENT: &str = "vyper-remote";
+
+/// Compiles with the server at `url`, see the module docs
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RemoteBackend {
+    pub url: String,
+    pub client: PathBuf,
+}
+
+impl RemoteBackend {
+    pub fn new(url: impl Into<String>) -> RemoteBackend {
+        RemoteBackend {
+            url: url.into(),
+            client: PathBuf::from(CLIENT),
+        }
+    }
+
+    /// Runs the client at `client` instead of the one in PATH
+    pub fn with_client(mut self, client: impl Into<PathBuf>) -> RemoteBackend {
+        self.client = client.into();
+        self
+    }
+}
+
+impl CompilerBackend for RemoteBackend {
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.client);
+        cmd.arg("--remote-url").arg(&self.url);
+        cmd
+    }
+
+    fn writes_files(&self) -> bool {
+        false
+    }
+}
+
+/// The arguments of a compiler invocation, as passed by the rest of this crate
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct Invocation {
+    url: String,
+    version: bool,
+    formats: Vec<String>,
+    evm_version: Option<String>,
+    optimize: Option<String>,
+    contract: Option<PathBuf>,
+}
+
+impl Invocation {
+    fn parse(args: &[String]) -> Result<Invocation, String> {
+        let mut invocation = Invocation::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} requires a value", arg))
+            };
+            match arg.as_str() {
+                "--remote-url" => invocation.url = value()?,
+                "--version" => invocation.version = true,
+                "-f" => {
+                    invocation.formats = value()?.split(',').map(str::to_owned).collect();
+                }
+                "--evm-version" => invocation.evm_version = Some(value()?),
+                "--optimize" => invocation.optimize = Some(value()?),
+                flag if flag.starts_with('-') => {
+                    Err(format!("unsupported option {}", flag))?
+                }
+                contract => invocation.contract = Some(PathBuf::from(contract)),
+            }
+        }
+        if invocation.url.is_empty() {
+            Err("--remote-url is required")?
+        }
+        if invocation.formats.is_empty() {
+            invocation.formats.push("bytecode".to_owned());
+        }
+        Ok(invocation)
+    }
+}
+
+/// Entry point of the `vyper-remote` client: runs the compiler invocation `args` against the
+/// server, printing to `out` and `err` like the vyper CLI. Returns the exit code.
+pub fn run(args: &[String], out: &mut impl Write, err: &mut impl Write) -> i32 {
+    match request(args) {
+        Ok(stdout) => match out.write_all(stdout.as_bytes()) {
+            Ok(()) => 0,
+            Err(_) => 1,
+        },
+        Err(message) => {
+            let _ = writeln!(err, "{}", message);
+            1
+        }
+    }
+}
+
+fn request(args: &[String]) -> Result<String, String> {
+    let invocation = Invocation::parse(args)?;
+    let url = invocation.url.trim_end_matches('/');
+    if invocation.version {
+        let version = ureq::get(&format!("{}/version", url))
+            .call()
+            .map_err(|e| e.to_string())?
+            .into_string()
+            .map_err(|e| e.to_string())?;
+        return Ok(format!("{}\n", version.trim()));
+    }
+    let contract = invocation.contract.ok_or("no contract given")?;
+    let source = std::fs::read_to_string(&contract)
+        .map_err(|e| format!("FileNotFoundError: {}: {}", contract.display(), e))?;
+    let input = standard_json_input(
+        &contract,
+        &source,
+        &invocation.formats,
+        invocation.evm_version.as_deref(),
+        invocation.optimize.as_deref(),
+    )?;
+    let output: Value = ureq::post(url)
+        .send_json(input)
+        .map_err(|e| e.to_string())?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    render_output(&output, &contract, &invocation.formats)
+}
@@ -0,0 +1,142 @@
+//! A compilation service, behind the `server` feature: `CompileServer` exposes the compiler
+//! over HTTP with axum, running it through any `Backend` (natively, in docker, sandboxed...).
+//!
+//! - `POST /compile` with a standard-json input body, answered with the compiler's
+//!   standard-json output. Errors in the contracts are reported in its `errors`, like with the
+//!   CLI; a compiler that fails without an output is answered with `500` and `{"error": ...}`.
+//! - `GET /compile/version`, answered with the output of `vyper --version`
+//!
+//! This is the protocol of the `remote` module, so `RemoteBackend::new("http://host/compile")`
+//! compiles against the service.
+//!
+//! ```no_run
+//! # use vyper_rs::server::CompileServer;
+//! # async fn run() -> Result<(), vyper_rs::vyper_errors::VyperErrors> {
+//! CompileServer::new()
+//!     .set_venv("./venv")
+//!     .serve("0.0.0.0:8080")
+//!     .await
+//! # }
+//! ```
+
+use crate::{
+    backend::{Backend, CompilerBackend},
+    process, vyper,
+    vyper_errors::VyperErrors,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::{json, Value};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+/// The compiler configuration requests are served with
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct CompileServer {
+    pub backend: Backend,
+    /// Compiler binary, resolved like `Vyper::get_vyper` when unset: the venv, `VYPER_PATH` or
+    /// PATH
+    pub compiler: Option<PathBuf>,
+    pub venv: Option<PathBuf>,
+    /// How long a single compilation may take
+    pub timeout: Option<Duration>,
+}
+
+impl CompileServer {
+    pub fn new() -> CompileServer {
+        CompileServer::default()
+    }
+
+    pub fn set_backend(mut self, backend: Backend) -> CompileServer {
+        self.backend = backend;
+        self
+    }
+
+    pub fn set_compiler(mut self, compiler: impl Into<PathBuf>) -> CompileServer {
+        self.compiler = Some(compiler.into());
+        self
+    }
+
+    pub fn set_venv(mut self, venv: impl Into<PathBuf>) -> CompileServer {
+        self.venv = Some(venv.into());
+        self
+    }
+
+    pub fn set_timeout(mut self, timeout: Duration) -> CompileServer {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The routes of the service, to be served as is or nested into a larger application
+    pub fn router(self) -> Router {
+        let bin = vyper::vyper_bin(self.compiler.as_deref(), self.venv.as_deref());
+        let state = Arc::new(Service {
+            compiler: self.backend.resolve(bin),
+            timeout: self.timeout,
+        });
+        Router::new()
+            .route("/compile", post(compile))
+            .route("/compile/version", get(version))
+            .with_state(state)
+    }
+
+    /// Serves `router` on `addr` until the process exits
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> Result<(), VyperErrors> {
+        let listener = TcpListener::bind(addr).await?;
+        process::info_event!(addr = ?listener.local_addr().ok(), "serving compilations");
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+}
+
+struct Service {
+    compiler: Arc<dyn CompilerBackend>,
+    timeout: Option<Duration>,
+}
+
+async fn compile(
+    State(service): State<Arc<Service>>,
+    Json(input): Json<Value>,
+) -> Response {
+    let mut cmd = tokio::process::Command::from(service.compiler.command());
+    cmd.arg("--standard-json");
+    let input = input.to_string();
+    let output =
+        match process::output_async_with_input(cmd, input.as_bytes(), service.timeout)
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => return failure(e.to_string()),
+        };
+    // the compiler reports errors in the contracts in its output, even when exiting with 1
+    match serde_json::from_slice::<Value>(&output.stdout) {
+        Ok(output) => Json(output).into_response(),
+        Err(_) => failure(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+    }
+}
+
+async fn version(State(service): State<Arc<Service>>) -> Response {
+    let mut cmd = tokio::process::Command::from(service.compiler.command());
+    cmd.arg("--version");
+    match process::output_async_with_input(cmd, &[], service.timeout).await {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .to_owned()
+            .into_response(),
+        Ok(output) => failure(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+        Err(e) => failure(e.to_string()),
+    }
+}
+
+fn failure(error: String) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "error": error })),
+    )
+        .into_response()
+}
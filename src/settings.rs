@@ -0,0 +1,159 @@
+//! Compiler settings shared by the data modules and the ones running the compiler: the EVM
+//! versions `--evm-version` accepts and the modes of `--optimize`. `vyper` re-exports them.
+
+use crate::vyper_errors::VyperErrors;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// Optimization modes of `--optimize`, supported by vyper 0.3.10 and later
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Optimize {
+    None,
+    Gas,
+    Codesize,
+}
+
+impl Display for Optimize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Optimize::None => f.write_str("none"),
+            Optimize::Gas => f.write_str("gas"),
+            Optimize::Codesize => f.write_str("codesize"),
+        }
+    }
+}
+
+/// EVM versions the compiler can target. Serializes to, and parses from, the name passed to
+/// `--evm-version`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Evm {
+    Byzantium,
+    Constantinople,
+    #[serde(rename = "petersburg", alias = "petersberg")]
+    Petersberg,
+    Istanbul,
+    Berlin,
+    London,
+    Paris,
+    Shanghai,
+    Cancun,
+    Prague,
+    Osaka,
+    Atlantis,
+    Agharta,
+}
+
+impl Evm {
+    /// Name of the EVM version on the command line
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Evm::Byzantium => "byzantium",
+            Evm::Constantinople => "constantinople",
+            Evm::Petersberg => "petersburg",
+            Evm::Istanbul => "istanbul",
+            Evm::Berlin => "berlin",
+            Evm::London => "london",
+            Evm::Paris => "paris",
+            Evm::Shanghai => "shanghai",
+            Evm::Cancun => "cancun",
+            Evm::Prague => "prague",
+            Evm::Osaka => "osaka",
+            Evm::Atlantis => "atlantis",
+            Evm::Agharta => "agharta",
+        }
+    }
+
+    /// Range of compiler versions accepting this EVM version, as `(first, first unsupported)`
+    fn supported_range(&self) -> (Version, Option<Version>) {
+        let v = |minor, patch| Version::new(0, minor, patch);
+        match self {
+            Evm::Byzantium
+            | Evm::Constantinople
+            | Evm::Petersberg
+            | Evm::Atlantis
+            | Evm::Agharta => (v(0, 0), Some(v(3, 8))),
+            Evm::Istanbul => (v(0, 0), None),
+            Evm::Berlin => (v(2, 12), None),
+            Evm::London => (v(3, 2), None),
+            Evm::Paris => (v(3, 7), None),
+            Evm::Shanghai | Evm::Cancun => (v(3, 8), None),
+            Evm::Prague => (v(4, 1), None),
+            Evm::Osaka => (v(4, 3), None),
+        }
+    }
+
+    /// Whether vyper `compiler` accepts this EVM version. Pre-releases count as the release
+    /// they precede.
+    pub fn supported_by(&self, compiler: &Version) -> bool {
+        let compiler = Version::new(compiler.major, compiler.minor, compiler.patch);
+        let (first, last) = self.supported_range();
+        compiler >= first && last.map_or(true, |last| compiler < last)
+    }
+
+    /// The EVM version vyper `compiler` targets when `--evm-version` isn't passed
+    pub fn default_for(compiler: &Version) -> Evm {
+        let compiler = (compiler.major, compiler.minor, compiler.patch);
+        match compiler {
+            v if v < (0, 2, 12) => Evm::Istanbul,
+            v if v < (0, 3, 2) => Evm::Berlin,
+            v if v < (0, 3, 7) => Evm::London,
+            v if v < (0, 3, 8) => Evm::Paris,
+            v if v < (0, 4, 0) => Evm::Shanghai,
+            v if v < (0, 4, 3) => Evm::Cancun,
+            _ => Evm::Prague,
+        }
+    }
+
+    /// The versions of vyper supporting this EVM version, for error messages
+    pub(crate) fn requirement(&self) -> String {
+        match self.supported_range() {
+            (first, Some(last)) => {
+                format!("{} requires vyper >={}, <{}", self, first, last)
+            }
+            (first, None) => format!("{} requires vyper >={}", self, first),
+        }
+    }
+}
+
+impl Display for Evm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Evm {
+    type Err = VyperErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let evm = match s.trim().to_ascii_lowercase().as_str() {
+            "byzantium" => Evm::Byzantium,
+            "constantinople" => Evm::Constantinople,
+            "petersburg" | "petersberg" => Evm::Petersberg,
+            "istanbul" => Evm::Istanbul,
+            "berlin" => Evm::Berlin,
+            "london" => Evm::London,
+            "paris" => Evm::Paris,
+            "shanghai" => Evm::Shanghai,
+            "cancun" => Evm::Cancun,
+            "prague" => Evm::Prague,
+            "osaka" => Evm::Osaka,
+            "atlantis" => Evm::Atlantis,
+            "agharta" => Evm::Agharta,
+            _ => Err(VyperErrors::UnknownEvm(s.to_owned()))?,
+        };
+        Ok(evm)
+    }
+}
+
+impl TryFrom<&str> for Evm {
+    type Error = VyperErrors;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
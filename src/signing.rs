@@ -0,0 +1,84 @@
+//! Signing attestations and registry entries with secp256k1 keys, behind the `signing`
+//! feature.
+//!
+//! What gets signed is always a digest, as lowercase hex: `Attestation::digest` for an
+//! attestation and `RegistryEntry::digest` for a registry entry. Signers are identified by
+//! their public key, the 0x prefixed hex of its compressed SEC1 encoding, and signatures are
+//! ECDSA over the sha256 of the digest, as 0x prefixed hex of `r || s`.
+
+use crate::{attest::ArtifactSignature, vyper_errors::VyperErrors};
+use k256::ecdsa::{
+    signature::{Signer as _, Verifier as _},
+    Signature, SigningKey, VerifyingKey,
+};
+
+/// Name of the signature scheme in `ArtifactSignature::scheme`
+pub const SCHEME: &str = "secp256k1";
+
+/// A secp256k1 private key
+#[derive(Clone)]
+pub struct Signer {
+    key: SigningKey,
+}
+
+impl std::fmt::Debug for Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signer")
+            .field("public_key", &self.public_key())
+            .finish()
+    }
+}
+
+impl Signer {
+    /// Parses a 32 byte private key, with or without 0x prefix
+    pub fn from_hex(secret: &str) -> Result<Signer, VyperErrors> {
+        let bytes = decode_hex(secret)?;
+        let key = SigningKey::from_slice(&bytes)
+            .map_err(|e| VyperErrors::SigningKeyError(e.to_string()))?;
+        Ok(Signer { key })
+    }
+
+    /// The public key identifying this signer
+    pub fn public_key(&self) -> String {
+        format!(
+            "0x{}",
+            hex::encode(self.key.verifying_key().to_encoded_point(true).as_bytes())
+        )
+    }
+
+    pub fn sign(&self, digest: &str) -> ArtifactSignature {
+        let signature: Signature = self.key.sign(digest.as_bytes());
+        ArtifactSignature {
+            signer: self.public_key(),
+            scheme: SCHEME.to_owned(),
+            signature: format!("0x{}", hex::encode(signature.to_bytes())),
+        }
+    }
+}
+
+/// Succeeds when one of `signatures` is a valid signature of `digest` by `signer`, a public key
+/// as returned by `Signer::public_key`
+pub fn verify_signature(
+    digest: &str,
+    signatures: &[ArtifactSignature],
+    signer: &str,
+) -> Result<(), VyperErrors> {
+    let key = VerifyingKey::from_sec1_bytes(&decode_hex(signer)?)
+        .map_err(|e| VyperErrors::SigningKeyError(e.to_string()))?;
+    let valid = signatures
+        .iter()
+        .filter(|s| s.scheme == SCHEME && s.signer.eq_ignore_ascii_case(signer))
+        .filter_map(|s| Signature::from_slice(&decode_hex(&s.signature).ok()?).ok())
+        .any(|s| key.verify(digest.as_bytes(), &s).is_ok());
+    if !valid {
+        Err(VyperErrors::InvalidSignature {
+            signer: signer.to_owned(),
+        })?
+    }
+    Ok(())
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, VyperErrors> {
+    hex::decode(s.trim_start_matches("0x"))
+        .map_err(|e| VyperErrors::SigningKeyError(e.to_string()))
+}
@@ -0,0 +1,119 @@
+//! Translation between compiler CLI invocations and the compiler's standard-json interface,
+//! for backends that only speak standard-json. A standard-json input compiles a single
+//! contract, only its own source is included.
+
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+/// Where each CLI output format is found in the standard-json output of a contract
+fn selection(format: &str) -> Option<(&'static str, &'static [&'static str])> {
+    Some(match format {
+        "bytecode" => ("evm.bytecode.object", &["evm", "bytecode", "object"]),
+        "bytecode_runtime" => (
+            "evm.deployedBytecode.object",
+            &["evm", "deployedBytecode", "object"],
+        ),
+        "opcodes" => ("evm.bytecode.opcodes", &["evm", "bytecode", "opcodes"]),
+        "opcodes_runtime" => (
+            "evm.deployedBytecode.opcodes",
+            &["evm", "deployedBytecode", "opcodes"],
+        ),
+        "method_identifiers" => ("evm.methodIdentifiers", &["evm", "methodIdentifiers"]),
+        "abi" => ("abi", &["abi"]),
+        "layout" => ("layout", &["layout"]),
+        "userdoc" => ("userdoc", &["userdoc"]),
+        "devdoc" => ("devdoc", &["devdoc"]),
+        "ast" => ("ast", &["ast"]),
+        _ => return None,
+    })
+}
+
+/// The standard-json input compiling the contract at `path`, whose source is `source`, for
+/// `formats`
+pub fn standard_json_input(
+    path: &Path,
+    source: &str,
+    formats: &[String],
+    evm_version: Option<&str>,
+    optimize: Option<&str>,
+) -> Result<Value, String> {
+    let selected = formats
+        .iter()
+        .map(|format| {
+            selection(format).map(|(name, _)| name).ok_or_else(|| {
+                format!("output format {} isn't supported remotely", format)
+            })
+        })
+        .collect::<Result<Vec<&str>, String>>()?;
+    let mut settings = Map::new();
+    settings.insert("outputSelection".to_owned(), json!({ "*": selected }));
+    if let Some(evm_version) = evm_version {
+        settings.insert("evmVersion".to_owned(), json!(evm_version));
+    }
+    if let Some(optimize) = optimize {
+        settings.insert("optimize".to_owned(), json!(optimize));
+    }
+    Ok(json!({
+        "language": "Vyper",
+        "sources": { path.to_string_lossy(): { "content": source } },
+        "settings": settings,
+    }))
+}
+
+/// Renders the standard-json `output` for the contract at `path` the way the CLI prints
+/// `formats`, one line per format. Compiler errors are returned as the CLI would print them.
+/// Since the input only holds `path`, the output is read from the first source, whatever the
+/// compiler normalized its name to.
+pub fn render_output(
+    output: &Value,
+    path: &Path,
+    formats: &[String],
+) -> Result<String, String> {
+    let errors: Vec<&str> = output["errors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|error| error["severity"] == "error")
+        .map(|error| {
+            error["formattedMessage"]
+                .as_str()
+                .or_else(|| error["message"].as_str())
+                .unwrap_or_default()
+        })
+        .collect();
+    if !errors.is_empty() {
+        Err(errors.join("\n"))?
+    }
+    let contract = first(&output["contracts"])
+        .and_then(first)
+        .ok_or_else(|| format!("no output for {}", path.display()))?;
+    let mut out = String::new();
+    for format in formats {
+        let value = match selection(format) {
+            Some(("ast", _)) => {
+                first(&output["sources"]).map_or(&Value::Null, |s| &s["ast"])
+            }
+            Some((_, keys)) => keys.iter().fold(contract, |value, key| &value[key]),
+            None => Err(unsupported(format))?,
+        };
+        match value {
+            Value::Null => Err(format!("no {} output for {}", format, path.display()))?,
+            Value::String(s) => out.push_str(s),
+            other => out.push_str(&other.to_string()),
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// The first value of a JSON object
+fn first(value: &Value) -> Option<&Value> {
+    value.as_object().and_then(|map| map.values().next())
+}
+
+fn unsupported(format: &str) -> String {
+    format!(
+        "output format {} isn't supported with standard-json",
+        format
+    )
+}
@@ -0,0 +1,125 @@
+//! Snapshot assertions for contract authors' Rust test suites, catching unintended changes to
+//! a contract's bytecode or ABI.
+//!
+//! The assertions compare against a committed snapshot and panic on a mismatch, or when the
+//! snapshot is missing so a CI run can't silently pass by writing it. Snapshots are normalized
+//! so they only change when the contract does: the compiler's metadata is stripped from
+//! bytecode and ABIs are written with sorted entries. Set `VYPER_RS_UPDATE_SNAPSHOTS=1` to
+//! create snapshots, or overwrite them after an intended change.
+//!
+//! With the `rpc` and `revm` features, `ForkFixture` runs compiled contracts against the state
+//! of a live chain at a fixed block.
+
+use crate::{utils, vyper::Vyper, vyper_errors::VyperErrors};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+#[cfg(all(feature = "rpc", feature = "revm"))]
+pub use crate::fork::{ForkDB, ForkFixture};
+
+/// Environment variable that makes the assertions write snapshots instead of comparing
+pub const UPDATE_SNAPSHOTS: &str = "VYPER_RS_UPDATE_SNAPSHOTS";
+
+/// Asserts that the deployment bytecode of `contract`, without metadata, matches the snapshot
+/// at `snapshot`
+pub fn assert_bytecode_snapshot(contract: &Vyper, snapshot: impl AsRef<Path>) {
+    let bytecode = contract
+        .build()
+        .and_then(|compiled| normalize_bytecode(&compiled.bytecode))
+        .unwrap_or_else(|e| {
+            panic!(
+                "failed to compile {}: {}",
+                contract.path_to_code.display(),
+                e
+            )
+        });
+    assert_snapshot(snapshot.as_ref(), &bytecode);
+}
+
+/// Asserts that the runtime bytecode of `contract`, without metadata, matches the snapshot at
+/// `snapshot`
+pub fn assert_runtime_bytecode_snapshot(contract: &Vyper, snapshot: impl AsRef<Path>) {
+    let bytecode = contract
+        .build()
+        .and_then(|compiled| normalize_bytecode(&compiled.runtime_bytecode))
+        .unwrap_or_else(|e| {
+            panic!(
+                "failed to compile {}: {}",
+                contract.path_to_code.display(),
+                e
+            )
+        });
+    assert_snapshot(snapshot.as_ref(), &bytecode);
+}
+
+/// Asserts that the ABI of `contract` matches the snapshot at `snapshot`, regardless of the
+/// order of its entries
+pub fn assert_abi_snapshot(contract: &Vyper, snapshot: impl AsRef<Path>) {
+    let abi = contract
+        .get_abi()
+        .and_then(normalize_abi)
+        .unwrap_or_else(|e| {
+            panic!(
+                "failed to compile {}: {}",
+                contract.path_to_code.display(),
+                e
+            )
+        });
+    assert_snapshot(snapshot.as_ref(), &abi);
+}
+
+/// Hex encoded `bytecode` with its metadata stripped, as stored in snapshots
+pub fn normalize_bytecode(bytecode: &str) -> Result<String, VyperErrors> {
+    let bytes = hex::decode(bytecode.trim().trim_start_matches("0x"))
+        .map_err(|_| VyperErrors::StringParsingError)?;
+    Ok(format!(
+        "0x{}\n",
+        hex::encode(utils::strip_metadata(&bytes))
+    ))
+}
+
+/// Pretty printed `abi` with its entries sorted, as stored in snapshots
+pub fn normalize_abi(abi: Value) -> Result<String, VyperErrors> {
+    let abi = match abi {
+        Value::Array(entries) => {
+            let mut entries = entries
+                .into_iter()
+                .map(|entry| Ok((serde_json::to_string(&entry)?, entry)))
+                .collect::<Result<Vec<(String, Value)>, VyperErrors>>()?;
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Array(entries.into_iter().map(|(_, entry)| entry).collect())
+        }
+        other => other,
+    };
+    Ok(format!("{}\n", serde_json::to_string_pretty(&abi)?))
+}
+
+fn assert_snapshot(snapshot: &Path, actual: &str) {
+    let update = std::env::var_os(UPDATE_SNAPSHOTS).map_or(false, |v| v != "0");
+    if update {
+        if let Some(dir) = snapshot.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .unwrap_or_else(|e| panic!("failed to create {}: {}", dir.display(), e));
+        }
+        fs::write(snapshot, actual)
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", snapshot.display(), e));
+        return;
+    }
+    match fs::read_to_string(snapshot) {
+        Ok(expected) => assert!(
+            expected == actual,
+            "snapshot {} doesn't match\n--- expected\n{}\n+++ actual\n{}\nrerun with {}=1 to \
+             update it",
+            snapshot.display(),
+            expected.trim_end(),
+            actual.trim_end(),
+            UPDATE_SNAPSHOTS
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => panic!(
+            "snapshot {} doesn't exist, rerun with {}=1 to create it",
+            snapshot.display(),
+            UPDATE_SNAPSHOTS
+        ),
+        Err(e) => panic!("failed to read {}: {}", snapshot.display(), e),
+    }
+}
@@ -1,92 +1,582 @@
 //! Utilities offered by the crate.
 
 use std::{
+    collections::BTreeMap,
     fs::read_dir,
     io::Error,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use crate::vyper_errors::VyperErrors;
+use globset::GlobSetBuilder;
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
 
-/// Parses the ERC-5202 bytecode container format for indexing blueprint contracts.
-///
-/// "A blueprint contract MUST use the preamble 0xFE71<version bits><length encoding bits>. 6 bits are allocated to the version, and 2 bits to the length encoding. The first version begins at 0 (0b000000), and versions increment by 1. The value 0b11 for <length encoding bits> is reserved. In the case that the length bits are 0b11, the third byte is considered a continuation byte (that is, the version requires multiple bytes to encode). The exact encoding of a multi-byte version is left to a future ERC.
-/// A blueprint contract MUST contain at least one byte of initcode.
-/// A blueprint contract MAY insert any bytes (data or code) between the version byte(s) and the initcode. If such variable length data is used, the preamble must be 0xFE71<version bits><length encoding bits><length bytes><data>. The <length encoding bits> represent a number between 0 and 2 (inclusive) describing how many bytes <length bytes> takes, and <length bytes> is the big-endian encoding of the number of bytes that <data> takes."
-///
-/// "ERC-5202: Blueprint contract format," Ethereum Improvement Proposals, no. 5202, June 2022. [Online serial].
-/// Available: https://eips.ethereum.org/EIPS/eip-5202.
+#[cfg(feature = "native")]
+use crate::vyper::Vyper;
+use crate::{
+    abi::{self, Abi},
+    bytecode::Bytecode,
+    vyper_errors::VyperErrors,
+};
+use semver::{Version, VersionReq};
+
+pub use crate::blueprint::{strip_metadata, Blueprint};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Blueprint {
-    pub erc_version: u8,
-    pub preamble_data: Option<Vec<u8>>,
-    pub initcode: Vec<u8>,
+impl Blueprint {
+    /// Whether the initcode is the one of `contract`, ignoring the CBOR metadata. `contract`
+    /// must have been compiled, either with `compile` or as a blueprint with
+    /// `compile_blueprint`, so an on-chain blueprint can be checked against its source.
+    #[cfg(feature = "native")]
+    pub fn matches(&self, contract: &Vyper) -> Result<bool, VyperErrors> {
+        let bytecode = contract.bytecode.as_ref().ok_or_else(|| {
+            VyperErrors::CompilerError(format!(
+                "{} has not been compiled",
+                contract.path_to_code.display()
+            ))
+        })?;
+        let initcode = match parse_blueprint(bytecode) {
+            Ok(blueprint) => blueprint.initcode,
+            Err(_) => bytecode.to_vec(),
+        };
+        Ok(strip_metadata(&self.initcode) == strip_metadata(&initcode))
+    }
 }
 
+/// `blueprint::parse_blueprint` with its error as a `VyperErrors::BlueprintError`
 pub fn parse_blueprint(bytecode: &[u8]) -> Result<Blueprint, VyperErrors> {
-    if bytecode.is_empty() {
-        Err(VyperErrors::BlueprintError("Empty Bytecode".to_owned()))?
+    Ok(crate::blueprint::parse_blueprint(bytecode)?)
+}
+
+/// Fetches the code at `address` with `eth_getCode` from the node at `rpc_url` and parses it
+/// as a blueprint. Behind the `rpc` feature.
+#[cfg(feature = "rpc")]
+pub fn fetch_blueprint(rpc_url: &str, address: &str) -> Result<Blueprint, VyperErrors> {
+    fetch_blueprints(rpc_url, &[address])?
+        .pop()
+        .ok_or_else(|| VyperErrors::RpcError("Empty response".to_owned()))?
+}
+
+/// `fetch_blueprint` for every address of `addresses` in a single JSON-RPC batch. Results are
+/// in the order of `addresses`, each failing on its own when the code isn't a blueprint.
+#[cfg(feature = "rpc")]
+pub fn fetch_blueprints(
+    rpc_url: &str,
+    addresses: &[&str],
+) -> Result<Vec<Result<Blueprint, VyperErrors>>, VyperErrors> {
+    let calls: Vec<(&str, serde_json::Value)> = addresses
+        .iter()
+        .map(|address| ("eth_getCode", serde_json::json!([address, "latest"])))
+        .collect();
+    Ok(rpc_batch(rpc_url, &calls)?
+        .into_iter()
+        .map(|result| {
+            result.and_then(|code| match code.as_str() {
+                Some(code) => parse_blueprint(&code.parse::<Bytecode>()?),
+                None => Err(VyperErrors::RpcError(format!("Unexpected result {}", code))),
+            })
+        })
+        .collect())
+}
+
+/// Sends `calls`, pairs of a method and its params, to the node at `rpc_url` in a single
+/// JSON-RPC batch. Results are in the order of `calls`, each failing on its own when the node
+/// answered it with an error. A `null` result, e.g. for a block that doesn't exist, isn't an
+/// error.
+#[cfg(feature = "rpc")]
+pub(crate) fn rpc_batch(
+    rpc_url: &str,
+    calls: &[(&str, serde_json::Value)],
+) -> Result<Vec<Result<serde_json::Value, VyperErrors>>, VyperErrors> {
+    let batch: Vec<serde_json::Value> = calls
+        .iter()
+        .enumerate()
+        .map(|(id, (method, params))| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            })
+        })
+        .collect();
+    let responses: Vec<serde_json::Value> = ureq::post(rpc_url)
+        .send_json(batch)
+        .map_err(|e| VyperErrors::RpcError(e.to_string()))?
+        .into_json()?;
+    let mut results: Vec<Option<Result<serde_json::Value, VyperErrors>>> =
+        calls.iter().map(|_| None).collect();
+    for mut response in responses {
+        let Some(slot) = response["id"]
+            .as_u64()
+            .and_then(|id| results.get_mut(id as usize))
+        else {
+            continue;
+        };
+        let error = &response["error"];
+        *slot = Some(if error.is_null() {
+            Ok(response["result"].take())
+        } else {
+            Err(VyperErrors::RpcError(
+                error["message"]
+                    .as_str()
+                    .map_or_else(|| error.to_string(), str::to_owned),
+            ))
+        });
     }
-    if &bytecode[0..2] != b"\xFE\x71" {
-        Err(VyperErrors::BlueprintError("Not a blueprint!".to_owned()))?
+    Ok(results
+        .into_iter()
+        .zip(calls)
+        .map(|(result, (method, params))| {
+            result.unwrap_or_else(|| {
+                Err(VyperErrors::RpcError(format!(
+                    "No response to {} {}",
+                    method, params
+                )))
+            })
+        })
+        .collect())
+}
+
+/// Runtime code of an ERC-1167 minimal proxy before and after the 20 byte target address
+const MINIMAL_PROXY_PREFIX: [u8; 10] =
+    [0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+const MINIMAL_PROXY_SUFFIX: [u8; 15] = [
+    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b,
+    0xf3,
+];
+/// Initcode of an ERC-1167 minimal proxy before its 45 byte runtime code, which it returns
+const MINIMAL_PROXY_CONSTRUCTOR: [u8; 10] =
+    [0x3d, 0x60, 0x2d, 0x80, 0x60, 0x0a, 0x3d, 0x39, 0x81, 0xf3];
+
+/// Runtime code of an ERC-1167 minimal proxy, a clone delegating every call to `target`, the
+/// address of e.g. a compiled and deployed contract
+pub fn minimal_proxy_runtime(target: &str) -> Result<Bytecode, VyperErrors> {
+    let target: Bytecode = target.parse()?;
+    if target.len() != 20 {
+        Err(VyperErrors::StringParsingError)?
     }
+    Ok(Bytecode(
+        [&MINIMAL_PROXY_PREFIX[..], &target, &MINIMAL_PROXY_SUFFIX].concat(),
+    ))
+}
+
+/// Initcode deploying an ERC-1167 minimal proxy of `target`, see `minimal_proxy_runtime`
+pub fn minimal_proxy_initcode(target: &str) -> Result<Bytecode, VyperErrors> {
+    let runtime = minimal_proxy_runtime(target)?;
+    Ok(Bytecode(
+        [&MINIMAL_PROXY_CONSTRUCTOR[..], &runtime].concat(),
+    ))
+}
+
+/// The address an ERC-1167 minimal proxy delegates to, as lowercase hex with a `0x` prefix.
+/// `code` is the runtime code or the initcode of the proxy. `None` when it's neither.
+pub fn minimal_proxy_target(code: &[u8]) -> Option<String> {
+    let runtime = code
+        .strip_prefix(&MINIMAL_PROXY_CONSTRUCTOR[..])
+        .unwrap_or(code);
+    let target = runtime
+        .strip_prefix(&MINIMAL_PROXY_PREFIX[..])?
+        .strip_suffix(&MINIMAL_PROXY_SUFFIX[..])?;
+    (target.len() == 20).then(|| format!("0x{}", hex::encode(target)))
+}
+
+/// Whether `code` is an ERC-1167 minimal proxy delegating to `target`, regardless of the case
+/// of `target`'s hex
+pub fn is_minimal_proxy_of(code: &[u8], target: &str) -> bool {
+    let target = target.trim();
+    let target = target.strip_prefix("0x").unwrap_or(target);
+    minimal_proxy_target(code)
+        .map_or(false, |address| address[2..].eq_ignore_ascii_case(target))
+}
+
+/// Storage and code layout of a contract, as output by `vyper -f layout`
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct StorageLayout {
+    #[serde(default)]
+    pub storage_layout: BTreeMap<String, LayoutEntry>,
+    /// Transient storage variables, vyper 0.4 and later
+    #[serde(default)]
+    pub transient_storage_layout: BTreeMap<String, LayoutEntry>,
+    /// Immutables, which live in the runtime code
+    #[serde(default)]
+    pub code_layout: BTreeMap<String, LayoutEntry>,
+}
+
+/// A variable in a `StorageLayout`, or the variables of an imported module
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LayoutEntry {
+    Variable(LayoutVariable),
+    Module(BTreeMap<String, LayoutEntry>),
+}
 
-    let erc_version = (&bytecode[2] & 0b11111100) >> 2;
-    let n_length_bytes = &bytecode[2] & 0b11;
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct LayoutVariable {
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// First storage slot, for storage variables
+    pub slot: Option<u64>,
+    /// Number of storage slots taken up
+    pub n_slots: Option<u64>,
+    /// Byte offset in the runtime code, for immutables
+    pub offset: Option<u64>,
+    /// Size in bytes, for immutables
+    pub length: Option<u64>,
+}
 
-    if n_length_bytes == 0b11 {
-        Err(VyperErrors::BlueprintError("Reserved bits are set".to_owned()))?
+impl StorageLayout {
+    /// The immutables of `code_layout`, see `ImmutablesLayout`
+    pub fn immutables(&self) -> ImmutablesLayout {
+        let mut immutables = BTreeMap::new();
+        flatten_immutables("", &self.code_layout, &mut immutables);
+        let size = immutables
+            .values()
+            .map(|immutable: &Immutable| immutable.offset + immutable.length)
+            .max()
+            .unwrap_or(0);
+        ImmutablesLayout { immutables, size }
     }
+}
 
-    let size_temp = bytecode[3..(3 + n_length_bytes as usize)].to_vec();
-    let data_length = match size_temp.len() {
-        0 => 0,
-        _ => {
-            let size: String = hex::encode(&size_temp);
-            match u32::from_str_radix(&size, size_temp.len() as u32 * 8u32) {
-                Ok(num) => num,
-                Err(e) => Err(VyperErrors::IntParseError(e))?,
+fn flatten_immutables(
+    prefix: &str,
+    layout: &BTreeMap<String, LayoutEntry>,
+    out: &mut BTreeMap<String, Immutable>,
+) {
+    for (name, entry) in layout {
+        let name = format!("{}{}", prefix, name);
+        match entry {
+            LayoutEntry::Variable(variable) => {
+                let (Some(offset), Some(length)) = (variable.offset, variable.length)
+                else {
+                    continue;
+                };
+                out.insert(
+                    name,
+                    Immutable {
+                        ty: variable.ty.clone(),
+                        offset: offset as usize,
+                        length: length as usize,
+                    },
+                );
+            }
+            LayoutEntry::Module(module) => {
+                flatten_immutables(&format!("{}.", name), module, out)
             }
         }
-    };
+    }
+}
+
+/// The data section the constructor appends to the runtime code, holding the values of the
+/// immutables. The runtime bytecode output by the compiler ends before it, so the code of a
+/// deployed contract is `size` bytes longer than the compiled one.
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct ImmutablesLayout {
+    /// Keyed by name, `module.name` for immutables of imported modules
+    pub immutables: BTreeMap<String, Immutable>,
+    /// Size of the data section in bytes
+    pub size: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Immutable {
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Byte offset in the data section
+    pub offset: usize,
+    /// Size in bytes
+    pub length: usize,
+}
+
+impl ImmutablesLayout {
+    /// Splits the code of a deployed contract into its runtime code, comparable with the
+    /// compiled runtime bytecode, and the data section. `None` when the code is shorter than
+    /// the data section.
+    pub fn split<'a>(&self, deployed: &'a [u8]) -> Option<(&'a [u8], &'a [u8])> {
+        let at = deployed.len().checked_sub(self.size)?;
+        Some(deployed.split_at(at))
+    }
+
+    /// The raw value of immutable `name` in the code of a deployed contract
+    pub fn value<'a>(&self, name: &str, deployed: &'a [u8]) -> Option<&'a [u8]> {
+        let immutable = self.immutables.get(name)?;
+        let (_, data) = self.split(deployed)?;
+        data.get(immutable.offset..immutable.offset + immutable.length)
+    }
+}
 
-    let preamble_data: Option<Vec<u8>> = match data_length {
-        0 => None,
-        _ => {
-            let data_start = 3 + n_length_bytes as usize;
-            Some(bytecode[data_start..data_start + data_length as usize].to_vec())
+/// Smart contract development frameworks that `scan_workspace` knows how to detect.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub enum Framework {
+    Ape,
+    Foundry,
+    Hardhat,
+    Brownie,
+}
+
+impl Framework {
+    /// Config files that mark the root of a project built with this framework
+    pub fn config_files(&self) -> &'static [&'static str] {
+        match self {
+            Framework::Ape => &["ape-config.yaml", "ape-config.yml"],
+            Framework::Foundry => &["foundry.toml"],
+            Framework::Hardhat => &[
+                "hardhat.config.js",
+                "hardhat.config.ts",
+                "hardhat.config.cjs",
+                "hardhat.config.mjs",
+            ],
+            Framework::Brownie => &["brownie-config.yaml", "brownie-config.yml"],
         }
-    };
+    }
+
+    /// Source directory used when the config file doesn't override it
+    pub fn default_sources(&self) -> &'static str {
+        match self {
+            Framework::Foundry => "src",
+            Framework::Ape | Framework::Hardhat | Framework::Brownie => "contracts",
+        }
+    }
+
+    /// Key in the config file that overrides the source directory
+    fn sources_key(&self) -> &'static str {
+        match self {
+            Framework::Ape => "contracts_folder",
+            Framework::Foundry => "src",
+            Framework::Hardhat => "sources",
+            Framework::Brownie => "contracts",
+        }
+    }
+}
+
+/// A framework detected in a workspace, along with the config file it was detected from and the
+/// source directory it compiles contracts from.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct FrameworkConfig {
+    pub framework: Framework,
+    pub config_file: PathBuf,
+    pub sources: PathBuf,
+}
+
+/// The result of scanning a workspace: every framework detected at its root and every contract
+/// found in their source directories.
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub frameworks: Vec<FrameworkConfig>,
+    pub contracts: Vec<PathBuf>,
+}
+
+/// Dependency and build output directories that never contain a project's own sources.
+#[cfg(feature = "async")]
+const WORKSPACE_EXCLUDES: [&str; 7] = [
+    "**/node_modules/**",
+    "lib/**",
+    "build/**",
+    "out/**",
+    "artifacts/**",
+    "cache/**",
+    "target/**",
+];
+
+/// Detects the frameworks configured at the root of a workspace and reads the source directory
+/// from each config file.
+pub fn detect_frameworks(root: &Path) -> Vec<FrameworkConfig> {
+    let mut frameworks = Vec::new();
+    for framework in [
+        Framework::Ape,
+        Framework::Foundry,
+        Framework::Hardhat,
+        Framework::Brownie,
+    ] {
+        let config_file = framework
+            .config_files()
+            .iter()
+            .map(|name| root.join(name))
+            .find(|path| path.is_file());
+        if let Some(config_file) = config_file {
+            let sources = std::fs::read_to_string(&config_file)
+                .ok()
+                .and_then(|contents| config_value(&contents, framework.sources_key()))
+                .unwrap_or_else(|| framework.default_sources().to_owned());
+            frameworks.push(FrameworkConfig {
+                framework,
+                config_file,
+                sources: root.join(sources),
+            });
+        }
+    }
+    frameworks
+}
 
-    let initcode =
-        bytecode[3 + n_length_bytes as usize + data_length as usize..].to_vec();
-    match initcode.is_empty() {
-        true => {
-            Err(VyperErrors::BlueprintError("Empty Initcode!".to_owned()))?
+/// Reads the value of `key` from a line of the form `key = "value"` (TOML), `key: value` (YAML)
+/// or `key: "value",` (JS/TS). This is deliberately simple and only meant to find the source
+/// directory in framework config files.
+fn config_value(contents: &str, key: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix(key)?.trim_start();
+        let rest = rest
+            .strip_prefix(':')
+            .or_else(|| rest.strip_prefix('='))?
+            .trim();
+        let value = rest
+            .split('#')
+            .next()?
+            .trim()
+            .trim_end_matches(',')
+            .trim_matches(|c| c == '"' || c == '\'' || c == '`');
+        if value.is_empty() {
+            None
+        } else {
+            Some(value.to_owned())
         }
-        false => Ok(Blueprint{erc_version, preamble_data, initcode}),
-    }
-}
-
-/// Scans current directory, looks for /contracts or /src folder and searches them too if they
-/// exist. Returns a Vec of PathBufs to any Vyper contract found. 
-pub async fn scan_workspace(root: PathBuf) -> Result<Vec<PathBuf>, Error> {
-    let cwd = root.clone();
-    let h1 = tokio::spawn(async move { get_contracts_in_dir(cwd) });
-    let hh_ape = root.join("contracts");
-    let h2 = tokio::spawn(async move { get_contracts_in_dir(hh_ape) });
-    let foundry = root.join("src");
-    let h3 = tokio::spawn(async move { get_contracts_in_dir(foundry) });
-    let mut res = Vec::new();
-    for i in [h1, h2, h3].into_iter() {
-        let result = match i.await {
-            Ok(Ok(x)) => x,
-            _ => Vec::new(),
+    })
+}
+
+/// Scans a workspace for Vyper contracts. Ape, Foundry, Hardhat and Brownie projects are detected
+/// by their config files and their configured source directories are searched recursively. If no
+/// framework is detected, the whole workspace is searched. Dependencies (`node_modules`, `lib`) and
+/// build outputs are always skipped.
+#[cfg(feature = "async")]
+pub async fn scan_workspace(root: PathBuf) -> Result<Workspace, VyperErrors> {
+    let frameworks = detect_frameworks(&root);
+    let mut dirs: Vec<PathBuf> = frameworks.iter().map(|f| f.sources.clone()).collect();
+    if dirs.is_empty() {
+        dirs.push(root.clone());
+    }
+    dirs.sort();
+    dirs.dedup();
+
+    let mut handles = Vec::with_capacity(dirs.len());
+    for dir in dirs.into_iter().filter(|dir| dir.is_dir()) {
+        // root-relative excludes would hide e.g. `src/lib` inside a framework's sources
+        let glob = if dir == root {
+            WORKSPACE_EXCLUDES
+                .iter()
+                .fold(Glob::default(), |glob, pattern| glob.exclude(pattern))
+        } else {
+            Glob::default().exclude(WORKSPACE_EXCLUDES[0])
         };
-        res.push(result)
+        handles.push(tokio::spawn(async move { find_contracts(&dir, &glob) }));
+    }
+    let mut contracts = Vec::new();
+    for handle in handles {
+        contracts.extend(handle.await??);
+    }
+    contracts.sort();
+    contracts.dedup();
+
+    Ok(Workspace {
+        root,
+        frameworks,
+        contracts,
+    })
+}
+
+/// Name of the contract defined in a source file, which for Vyper is the file stem, i.e.
+/// `contracts/token/ERC20.vy` defines `ERC20`.
+pub fn contract_name(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+}
+
+/// Metadata about a contract that can be read from its source without invoking the compiler.
+/// The parsing is line based and only looks at top level declarations and comments.
+#[derive(
+    Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct SourceUnit {
+    pub path: PathBuf,
+    pub name: String,
+    /// Version requirement from `# @version` or `# pragma version`, i.e. `^0.3.10`
+    pub pragma: Option<String>,
+    /// SPDX identifier from `# SPDX-License-Identifier:` or a `@license` natspec tag
+    pub license: Option<String>,
+    /// Interfaces the contract declares it conforms to with `implements:`
+    pub implements: Vec<String>,
+    /// Interfaces defined inline with `interface Name:`
+    pub interfaces: Vec<String>,
+    /// Modules brought in with `import` or `from ... import`, `from a import b` as `a.b`. Each
+    /// leading dot of a relative import is a directory level, `.a` being `a` in the importing
+    /// file's directory.
+    pub imports: Vec<String>,
+}
+
+impl SourceUnit {
+    /// Reads and parses the contract at `path`
+    pub fn parse(path: &Path) -> Result<SourceUnit, VyperErrors> {
+        let source = std::fs::read_to_string(path)?;
+        Ok(SourceUnit::from_source(path, &source))
+    }
+
+    /// Parses contract source that was already read from `path`
+    pub fn from_source(path: &Path, source: &str) -> SourceUnit {
+        let mut unit = SourceUnit {
+            path: path.to_path_buf(),
+            name: contract_name(path).unwrap_or_default(),
+            ..Default::default()
+        };
+        for line in source.lines() {
+            let trimmed = line.trim();
+            if let Some(comment) = trimmed.strip_prefix('#') {
+                let comment = comment.trim();
+                if let Some(version) = comment
+                    .strip_prefix("@version")
+                    .or_else(|| comment.strip_prefix("pragma version"))
+                {
+                    unit.pragma.get_or_insert_with(|| version.trim().to_owned());
+                } else if let Some(license) =
+                    comment.strip_prefix("SPDX-License-Identifier:")
+                {
+                    unit.license
+                        .get_or_insert_with(|| license.trim().to_owned());
+                } else if let Some(license) = comment.strip_prefix("@license") {
+                    unit.license
+                        .get_or_insert_with(|| license.trim().to_owned());
+                }
+                continue;
+            }
+            if let Some(license) = trimmed.strip_prefix("@license") {
+                unit.license
+                    .get_or_insert_with(|| license.trim().to_owned());
+            }
+            // everything below is a top level declaration
+            if line.starts_with(char::is_whitespace) {
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix("implements:") {
+                unit.implements.push(name.trim().to_owned());
+            } else if let Some(name) = trimmed.strip_prefix("interface ") {
+                unit.interfaces
+                    .push(name.trim_end_matches(':').trim().to_owned());
+            } else if let Some(rest) = trimmed.strip_prefix("from ") {
+                if let Some((module, names)) = rest.split_once(" import ") {
+                    let module = module.trim();
+                    // `from . import a` is `.a`, like `import .a`
+                    let separator = if module.ends_with('.') { "" } else { "." };
+                    for name in names.split(',') {
+                        let name = name.split(" as ").next().unwrap_or(name).trim();
+                        unit.imports
+                            .push(format!("{}{}{}", module, separator, name));
+                    }
+                }
+            } else if let Some(rest) = trimmed.strip_prefix("import ") {
+                let module = rest.split(" as ").next().unwrap_or(rest).trim();
+                unit.imports.push(module.to_owned());
+            }
+        }
+        unit
     }
-    Ok(res.into_iter().flatten().collect::<Vec<PathBuf>>())
 }
 
 /// Scans current directory, looks for any vyper contracts and returns a Vec of PathBufs to any
@@ -97,7 +587,7 @@ pub fn get_contracts_in_dir(dir: PathBuf) -> Result<Vec<PathBuf>, Error> {
         Vec::new(),
         |mut acc, x| -> Result<Vec<PathBuf>, Error> {
             let file = x?;
-            if file.path().ends_with(".vy") {
+            if is_vyper_file(&file.path()) {
                 acc.push(file.path())
             }
             Ok(acc)
@@ -105,3 +595,343 @@ pub fn get_contracts_in_dir(dir: PathBuf) -> Result<Vec<PathBuf>, Error> {
     )?;
     Ok(contracts)
 }
+
+fn is_vyper_file(path: &Path) -> bool {
+    path.is_file() && path.extension().map_or(false, |ext| ext == "vy")
+}
+
+/// Include and exclude patterns used by `find_contracts`. Patterns are matched against the path
+/// of each `.vy` file relative to the search root, i.e. `token/**` or `**/test_*.vy`. A contract
+/// is returned when it matches at least one include pattern, or there is none, and no exclude
+/// pattern. The default matches every Vyper contract below the root.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Glob {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Glob {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        Self { include, exclude }
+    }
+
+    /// Adds an include pattern, restricting the search to the included contracts
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include.push(pattern.to_owned());
+        self
+    }
+
+    /// Adds an exclude pattern
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(pattern.to_owned());
+        self
+    }
+}
+
+/// How `Vypers::in_dir_filtered` collects contracts, for keeping test fixtures, mocks and
+/// vendored contracts out of a build
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DiscoveryOptions {
+    pub glob: Glob,
+    /// How many directory levels below the root are searched, `Some(0)` for the root's own
+    /// files only. `None` searches every level.
+    pub max_depth: Option<usize>,
+    /// Accept finding no contract at all instead of failing with `NoContractsFound`
+    pub allow_empty: bool,
+}
+
+impl DiscoveryOptions {
+    /// Adds an include pattern, see `Glob`
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.glob = self.glob.include(pattern);
+        self
+    }
+
+    /// Adds an exclude pattern, see `Glob`
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.glob = self.glob.exclude(pattern);
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+}
+
+/// Recursively walks `root` and returns every file matching `glob`, sorted by path. Hidden
+/// files and anything ignored by a `.gitignore`/`.ignore` file are skipped.
+pub fn find_contracts(root: &Path, glob: &Glob) -> Result<Vec<PathBuf>, VyperErrors> {
+    find_contracts_within(root, glob, None)
+}
+
+/// `find_contracts`, searching at most `max_depth` directory levels below `root`, see
+/// `DiscoveryOptions::max_depth`
+pub fn find_contracts_within(
+    root: &Path,
+    glob: &Glob,
+    max_depth: Option<usize>,
+) -> Result<Vec<PathBuf>, VyperErrors> {
+    let mut include = GlobSetBuilder::new();
+    for pattern in glob.include.iter() {
+        include.add(globset::Glob::new(pattern)?);
+    }
+    let include = include.build()?;
+    let mut exclude = GlobSetBuilder::new();
+    for pattern in glob.exclude.iter() {
+        exclude.add(globset::Glob::new(pattern)?);
+    }
+    let exclude = exclude.build()?;
+
+    let mut contracts = Vec::new();
+    let walk = WalkBuilder::new(root)
+        .require_git(false)
+        // the walker counts the files directly in `root` as depth 1
+        .max_depth(max_depth.map(|depth| depth + 1))
+        .build();
+    for entry in walk {
+        let entry = entry?;
+        if !entry.file_type().map_or(false, |ft| ft.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        if path.extension().map_or(false, |ext| ext == "vy")
+            && (glob.include.is_empty() || include.is_match(relative))
+            && !exclude.is_match(relative)
+        {
+            contracts.push(path.to_path_buf());
+        }
+    }
+    contracts.sort();
+    Ok(contracts)
+}
+
+/// The stdout of a bytecode compilation (`vyper`, `-f bytecode` or `-f blueprint_bytecode`)
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct BytecodeOutput {
+    pub bytecode: Bytecode,
+    /// Every other line the compiler printed, such as warnings
+    pub diagnostics: Vec<String>,
+}
+
+/// Isolates the bytecode in the compiler's stdout: the last `0x` prefixed hex token, which
+/// may follow a `contract.vy:` label on its line. Fails when there is no such token.
+pub fn parse_bytecode_output(stdout: &str) -> Result<BytecodeOutput, VyperErrors> {
+    let lines: Vec<&str> = stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let (index, bytecode) = lines
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, line)| {
+            let token = line
+                .rsplit(|c: char| c.is_whitespace() || c == ':')
+                .next()?;
+            is_hex(token).then_some((i, token))
+        })
+        .ok_or(VyperErrors::StringParsingError)?;
+    let diagnostics = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, line)| line.to_string())
+        .collect();
+    Ok(BytecodeOutput {
+        bytecode: bytecode.parse()?,
+        diagnostics,
+    })
+}
+
+/// `0x` followed by an even number of hex digits
+fn is_hex(token: &str) -> bool {
+    token.strip_prefix("0x").map_or(false, |digits| {
+        digits.len() % 2 == 0 && digits.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+/// Lowercase hex sha256 digest of `bytes`
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Lowercase hex sha256 digest of the file at `path`
+pub fn sha256_file(path: &Path) -> Result<String, VyperErrors> {
+    Ok(sha256_hex(&std::fs::read(path)?))
+}
+
+/// Hash functions sources can be content addressed with, see `Vyper::hash_source`
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Keccak256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Lowercase hex digest of `bytes`
+    pub fn hex(&self, bytes: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Keccak256 => hex::encode(Keccak256::digest(bytes)),
+            HashAlgorithm::Blake3 => blake3::hash(bytes).to_hex().to_string(),
+        }
+    }
+
+    /// Lowercase hex digest of the file at `path`
+    pub fn file(&self, path: &Path) -> Result<String, VyperErrors> {
+        Ok(self.hex(&std::fs::read(path)?))
+    }
+}
+
+/// Files below `dir` that changed since the git revision `git_ref`, committed or not, with
+/// untracked files included, as `dir` joined with their relative path. Deleted files are
+/// included too. Runs the `git` CLI in `dir` rather than linking gix, behind the `git` feature,
+/// see its comment in `Cargo.toml`.
+#[cfg(feature = "git")]
+pub fn git_changed_files(dir: &Path, git_ref: &str) -> Result<Vec<PathBuf>, VyperErrors> {
+    use crate::process::CommandExt;
+    let git = |args: &[&str]| -> Result<Vec<PathBuf>, VyperErrors> {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output_timeout(None)?;
+        if !output.status.success() {
+            Err(VyperErrors::GitError(
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            ))?
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| dir.join(line))
+            .collect())
+    };
+    let mut changed = git(&["diff", "--name-only", "--relative", git_ref, "--"])?;
+    changed.extend(git(&["ls-files", "--others", "--exclude-standard"])?);
+    changed.sort();
+    changed.dedup();
+    Ok(changed)
+}
+
+/// `program` when it's a path, else the first match in PATH
+pub fn which(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    let names = [
+        program.to_owned(),
+        format!("{}{}", program, std::env::consts::EXE_SUFFIX),
+    ];
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The constructor arguments of a deployment, ABI decoded from `creation_tx_input`, the input
+/// of the transaction that created the contract, after the contract's initcode `bytecode`.
+/// Values are in the order of the constructor's inputs in `abi`, represented as described in
+/// `abi::decode`. Fails when the input doesn't start with `bytecode`, e.g. when the deployment
+/// was built with other settings or another compiler.
+pub fn decode_constructor_args(
+    abi: &Abi,
+    creation_tx_input: &[u8],
+    bytecode: &[u8],
+) -> Result<Vec<serde_json::Value>, VyperErrors> {
+    let args = creation_tx_input.strip_prefix(bytecode).ok_or_else(|| {
+        VyperErrors::AbiDecodeError(
+            "the creation input doesn't start with the contract's initcode".to_owned(),
+        )
+    })?;
+    let inputs = abi
+        .0
+        .iter()
+        .find(|entry| entry.ty == "constructor")
+        .map_or(&[][..], |constructor| &constructor.inputs[..]);
+    abi::decode(inputs, args)
+}
+
+/// Parses the output of `vyper --version`, e.g. `0.3.10+commit.9136169` or `0.4.0rc6`.
+/// Python style pre-release suffixes are turned into semver pre-releases.
+pub fn parse_version(version: &str) -> Result<Version, VyperErrors> {
+    let version = version.split_whitespace().next().unwrap_or_default();
+    let release = version.split('+').next().unwrap_or_default();
+    let numeric = release
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(release.len());
+    let (numbers, pre) = release.split_at(numeric);
+    let pre = pre.trim_start_matches(['-', '.']);
+    if pre.is_empty() {
+        Ok(Version::parse(numbers)?)
+    } else {
+        Ok(Version::parse(&format!("{}-{}", numbers, pre))?)
+    }
+}
+
+/// Parses a version pragma such as `^0.3.10`, `>=0.3.9,<0.4` or `~=0.4.0`. A bare version is
+/// an exact requirement, as in vyper.
+pub fn version_req(pragma: &str) -> Result<VersionReq, VyperErrors> {
+    let pragma = pragma.trim();
+    let req = if let Some(compatible) = pragma.strip_prefix("~=") {
+        format!("~{}", compatible.trim())
+    } else if pragma.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("={}", pragma)
+    } else {
+        pragma.to_owned()
+    };
+    Ok(VersionReq::parse(&req)?)
+}
+
+/// Directory in the system's temp directory holding the files of `FileLock`
+pub const LOCK_DIR: &str = "vyper-rs-locks";
+
+/// An advisory lock shared by every process on the machine, held until dropped. Guards
+/// compiler installations and shared files against parallel CI jobs and tests. The lock files
+/// live in `LOCK_DIR`, so locking never writes next to the locked path.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct FileLock {
+    file: std::fs::File,
+}
+
+#[cfg(feature = "native")]
+impl FileLock {
+    /// Waits for the lock on `path`, which doesn't have to exist. Relative paths are resolved
+    /// against the current directory, so `./venv` and `venv` share a lock.
+    pub fn acquire(path: &Path) -> Result<FileLock, VyperErrors> {
+        let path: PathBuf = std::env::current_dir()?.join(path).components().collect();
+        FileLock::named(&sha256_hex(path.to_string_lossy().as_bytes()))
+    }
+
+    /// Waits for the lock called `name`, for resources without a path such as the global
+    /// python installation
+    pub fn named(name: &str) -> Result<FileLock, VyperErrors> {
+        use fs2::FileExt;
+        let dir = std::env::temp_dir().join(LOCK_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(format!("{}.lock", name)))?;
+        file.lock_exclusive()?;
+        Ok(FileLock { file })
+    }
+}
+
+#[cfg(feature = "native")]
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs2::FileExt::unlock(&self.file);
+    }
+}
@@ -14,10 +14,16 @@
 //! namespace are mostly equivalent to the ones in the Vyper module, thus you can rely on the
 //! documentation for these methods inside the Venv module.
 use crate::{
-    vyper::{Vyper, Vypers},
+    process::{debug_event, info_event, CommandExt, RetryPolicy},
+    utils::{self, FileLock},
+    vyper::{VersionReq, Vyper, Vypers, VYPER_PATH},
     vyper_errors::VyperErrors,
 };
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
+    ffi::OsString,
+    fs,
     path::{Path, PathBuf},
     process::Command,
 };
@@ -52,6 +58,16 @@ pub struct Complete;
 //
 //              init
 //
+//              with_source
+//
+//              list
+//
+//              with_python
+//
+//              recreate
+//
+//              ensure
+//
 //              skip
 //
 //      Initialized:
@@ -60,29 +76,73 @@ pub struct Complete;
 //
 //              ivyper_venv
 //
+//              ivyper_venv_report
+//
+//              install_tools
+//
 //              try_ready
 //
+//              try_ready_ver
+//
+//              repair
+//
+//              fingerprint
+//
 //      Skip:
 //
 //          Methods:
 //
 //              ivyper_pip
 //
+//              ivyper_pip_report
+//
+//              ivyper_pipx
+//
+//              ivyper_pipx_report
+//
+//              install
+//
 //              try_ready
 //
 //      Ready:
 //
 //         Methods:
 //
-//             compile
+//             install_tools
+//
+//             upgrade
+//
+//             verify_integrity
+//
+//             uninstall
+//
+//             vyper
+//
+//             vypers
+//
+//             vyper_with_abi
+//
+//             vypers_from_dir
+//
+//             vypers_from_workspace
+//
+//             into_vyper_for_each
+//
+//     Complete:
+//
+//         Methods:
+//
+//             vyper
+//
+//             vypers
 //
-//             compile_ver
+//             vyper_with_abi
 //
-//             abi
+//             vypers_from_dir
 //
-//             abi_json
+//             vypers_from_workspace
 //
-//     Complete
+//             into_vyper_for_each
 
 /// Venv is the primary namespace of the module. Its methods are split between various states
 /// represented by individual structs. The main documentation for the module is here. Functions
@@ -92,6 +152,10 @@ pub struct Complete;
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub struct Venv<'a, State = NotInitialized> {
     venv_path: &'a Path,
+    retry: RetryPolicy,
+    python: Option<&'a Path>,
+    source: InstallSource<'a>,
+    installer: InstallBackend,
     state: std::marker::PhantomData<State>,
 }
 
@@ -99,58 +163,401 @@ impl<'a> Default for Venv<'a, NotInitialized> {
     fn default() -> Self {
         Self {
             venv_path: Path::new("./venv"),
+            retry: RetryPolicy::default(),
+            python: None,
+            source: InstallSource::default(),
+            installer: InstallBackend::default(),
             state: std::marker::PhantomData::<NotInitialized>,
         }
     }
 }
 
+impl<'a, State> Venv<'a, State> {
+    /// Retries pip and venv creation, as well as compiler invocations of any contract
+    /// constructed from this venv, according to `retry`
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Where pip (or pipx) installs vyper from, for offline and air-gapped installations
+    pub fn with_source(mut self, source: InstallSource<'a>) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Points the venv at `venv_path` without changing state, so several venvs can be managed
+    /// side by side
+    pub fn at(mut self, venv_path: &'a Path) -> Self {
+        self.venv_path = venv_path;
+        self
+    }
+
+    /// Installs packages with `installer` instead of pip, see `InstallBackend`
+    pub fn with_installer(mut self, installer: InstallBackend) -> Self {
+        self.installer = installer;
+        self
+    }
+
+    pub fn venv_path(&self) -> &'a Path {
+        self.venv_path
+    }
+
+    /// The venv's python interpreter
+    pub fn python_bin(&self) -> PathBuf {
+        self.bin("python")
+    }
+
+    /// Python and vyper versions installed in the venv
+    pub fn fingerprint(&self) -> VenvFingerprint {
+        let python = fs::read_to_string(self.venv_path.join("pyvenv.cfg"))
+            .ok()
+            .and_then(|cfg| {
+                cfg.lines().find_map(|line| {
+                    let (key, value) = line.split_once('=')?;
+                    matches!(key.trim(), "version" | "version_info")
+                        .then(|| value.trim().to_owned())
+                })
+            });
+        let vyper = Command::new(self.bin("vyper"))
+            .arg("--version")
+            .output_timeout(None)
+            .ok()
+            .filter(|out| out.status.success())
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_owned());
+        VenvFingerprint { python, vyper }
+    }
+
+    /// Runs `pip install` with `pip`, upgrading an existing installation when `upgrade` is set
+    fn pip_install(
+        &self,
+        mut pip: Command,
+        ver: Option<&str>,
+        upgrade: bool,
+    ) -> Result<InstallReport, VyperErrors> {
+        self.source.verify(ver)?;
+        pip.arg("install");
+        if upgrade {
+            pip.arg("--upgrade");
+        }
+        let c = pip
+            .args(self.source.pip_args(ver))
+            .output_retry_status(None, &self.retry)?;
+        if !c.status.success() {
+            Err(pip_error(ver, &String::from_utf8_lossy(&c.stderr)))?
+        }
+        Ok(InstallReport::parse(&self.source, &c.stdout, &c.stderr))
+    }
+
+    /// See `Venv::<Initialized>::install_tools`
+    fn pip_install_tools(&self, tools: &[(&str, &str)]) -> Result<PathBuf, VyperErrors> {
+        let packages: Vec<(&str, Option<&str>)> = tools
+            .iter()
+            .map(|(name, ver)| (*name, Some(*ver).filter(|v| !v.is_empty())))
+            .collect();
+        let requirements: Vec<String> = packages
+            .iter()
+            .map(|(name, ver)| requirement(name, *ver))
+            .collect();
+        let installs_vyper = tools.iter().any(|(name, _)| *name == "vyper");
+        if let Some((_, ver)) = packages.iter().find(|(name, _)| *name == "vyper") {
+            self.source.verify(*ver)?;
+        }
+        let _lock = self.lock()?;
+        let c = self
+            .pip()
+            .arg("install")
+            .args(self.source.pip_args_for(&packages))
+            .output_retry_status(None, &self.retry)?;
+        if !c.status.success() {
+            Err(VyperErrors::ToolInstallFailed {
+                tools: requirements.clone(),
+                stderr: String::from_utf8_lossy(&c.stderr).to_string(),
+            })?
+        }
+        if installs_vyper {
+            self.record_provenance()?;
+        }
+        let freeze = self
+            .pip()
+            .arg("freeze")
+            .output_retry_status(None, &self.retry)?;
+        if !freeze.status.success() {
+            Err(VyperErrors::ToolInstallFailed {
+                tools: requirements.clone(),
+                stderr: String::from_utf8_lossy(&freeze.stderr).to_string(),
+            })?
+        }
+        let path = self.venv_path.join(FREEZE_FILE);
+        fs::write(&path, &freeze.stdout).map_err(|error| VyperErrors::VenvIoError {
+            path: path.clone(),
+            error,
+        })?;
+        info_event!(
+            venv = %self.venv_path.display(),
+            tools = ?requirements,
+            "installed tools"
+        );
+        Ok(path)
+    }
+
+    /// Provenance recorded when the compiler was installed, if it was installed by this
+    /// library
+    pub fn provenance(&self) -> Option<Provenance> {
+        let file = fs::File::open(self.venv_path.join(PROVENANCE_FILE)).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    /// Held while the venv is created or packages are installed into it, so parallel processes
+    /// set up the same venv one after the other
+    fn lock(&self) -> Result<FileLock, VyperErrors> {
+        FileLock::acquire(self.venv_path)
+    }
+
+    /// The marker written when this library created the venv, `None` for venvs created
+    /// some other way
+    pub fn marker(&self) -> Option<VenvMarker> {
+        let file = fs::File::open(self.venv_path.join(MARKER_FILE)).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    fn write_marker(&self, python: &Python) -> Result<(), VyperErrors> {
+        let path = self.venv_path.join(MARKER_FILE);
+        let marker = VenvMarker {
+            created_by: format!("vyper-rs {}", env!("CARGO_PKG_VERSION")),
+            python: python.program.clone(),
+            python_version: format!(
+                "{}.{}.{}",
+                python.version.0, python.version.1, python.version.2
+            ),
+        };
+        let json = serde_json::to_vec_pretty(&marker)?;
+        fs::write(&path, json).map_err(|error| VyperErrors::VenvIoError { path, error })
+    }
+
+    fn record_provenance(&self) -> Result<(), VyperErrors> {
+        let provenance = Provenance {
+            version: self.fingerprint().vyper,
+            source: self.source.describe(),
+            wheel_sha256: self.source.wheel_sha256(),
+            record_sha256: match self.dist_info_record() {
+                Some(record) => Some(utils::sha256_file(&record)?),
+                None => None,
+            },
+        };
+        let file = fs::File::create(self.venv_path.join(PROVENANCE_FILE))?;
+        serde_json::to_writer_pretty(file, &provenance)?;
+        Ok(())
+    }
+
+    /// pip's `RECORD` of the installed vyper package, listing every installed file and its hash
+    fn dist_info_record(&self) -> Option<PathBuf> {
+        dist_info_record(self.venv_path)
+    }
+
+    /// The venv's package installer, ready for its subcommand
+    fn pip(&self) -> Command {
+        match self.installer {
+            InstallBackend::Pip | InstallBackend::Pipx => Command::new(self.bin("pip3")),
+            InstallBackend::Uv => {
+                let mut uv = Command::new("uv");
+                uv.arg("pip").env("VIRTUAL_ENV", self.venv_path);
+                uv
+            }
+        }
+    }
+
+    /// Path of the executable `name` inside the venv
+    fn bin(&self, name: &str) -> PathBuf {
+        executable(self.venv_path, name)
+    }
+}
+
 impl<'a> Venv<'a, NotInitialized> {
     /// Constructs the Venv type with PhantomData
     pub fn new(venv_path: &'a Path) -> Venv<'a, NotInitialized> {
         Self {
             venv_path,
+            retry: RetryPolicy::default(),
+            python: None,
+            source: InstallSource::default(),
+            installer: InstallBackend::default(),
             state: std::marker::PhantomData::<NotInitialized>,
         }
     }
 
     /// Init will check whether or not a venv was created by this program
-    /// If it was not, we will create one
+    /// If it was not, we will create one and mark it with `MARKER_FILE`. An existing directory
+    /// that isn't a python venv is rejected with `InvalidVenv`.
     pub fn init(self) -> Result<Venv<'a, Initialized>, VyperErrors> {
+        let _lock = self.lock()?;
+        self.init_locked()
+    }
+
+    /// `init` while holding the venv's lock
+    fn init_locked(self) -> Result<Venv<'a, Initialized>, VyperErrors> {
         match self.venv_path.exists() {
-            true => Ok(Venv {
-                venv_path: self.venv_path,
-                state: std::marker::PhantomData::<Initialized>,
-            }),
-            false => {
-                let a = Command::new("mkdir").arg(self.venv_path).output()?;
-                if !a.status.success() {
-                    Err(VyperErrors::DirError(
-                        String::from_utf8_lossy(&a.stderr).to_string(),
-                    ))?
+            true => {
+                if !is_venv(self.venv_path) {
+                    Err(VyperErrors::InvalidVenv {
+                        path: self.venv_path.to_path_buf(),
+                    })?
                 }
+                debug_event!(venv = %self.venv_path.display(), "venv cache hit");
+                Ok(Venv {
+                    venv_path: self.venv_path,
+                    retry: self.retry,
+                    python: self.python,
+                    source: self.source,
+                    installer: self.installer,
+                    state: std::marker::PhantomData::<Initialized>,
+                })
+            }
+            false => {
+                debug_event!(venv = %self.venv_path.display(), "venv cache miss");
+                let python = match self.python {
+                    Some(path) => Python::at(path)?,
+                    None => Python::discover()?,
+                };
+                debug_event!(
+                    python = %python.program.display(),
+                    version = ?python.version,
+                    "creating venv"
+                );
+                fs::create_dir_all(self.venv_path).map_err(|error| {
+                    VyperErrors::VenvIoError {
+                        path: self.venv_path.to_path_buf(),
+                        error,
+                    }
+                })?;
 
-                let b = Command::new("python3")
+                let b = python
+                    .command()
                     .arg("-m")
                     .arg("venv")
                     .arg(self.venv_path)
-                    .output()?;
+                    .output_retry_status(None, &self.retry)?;
                 if !b.status.success() {
-                    Err(VyperErrors::VenvError(
-                        String::from_utf8_lossy(&b.stderr).to_string(),
-                    ))?
+                    Err(VyperErrors::VenvCreationFailed {
+                        path: self.venv_path.to_path_buf(),
+                        stderr: String::from_utf8_lossy(&b.stderr).to_string(),
+                    })?
                 }
+                self.write_marker(&python)?;
 
                 Ok(Venv {
                     venv_path: self.venv_path,
+                    retry: self.retry,
+                    python: self.python,
+                    source: self.source,
+                    installer: self.installer,
                     state: std::marker::PhantomData::<Initialized>,
                 })
             }
         }
     }
+    /// Creates the venv with the interpreter at `python` instead of discovering one, see
+    /// `Python::discover`
+    pub fn with_python(mut self, python: &'a Path) -> Self {
+        self.python = Some(python);
+        self
+    }
+
+    /// Every venv directly inside `dir` with a working compiler, for keeping track of the
+    /// compilers managed side by side with `Venv::at`
+    pub fn list(dir: &Path) -> Result<Vec<InstalledCompiler>, VyperErrors> {
+        let mut out = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !is_venv(&path) {
+                continue;
+            }
+            if let Some(version) = Venv::new(&path).fingerprint().vyper {
+                out.push(InstalledCompiler {
+                    venv: path,
+                    version,
+                });
+            }
+        }
+        out.sort();
+        Ok(out)
+    }
+
+    /// Deletes the venv and creates a fresh one in its place, holding the venv's lock
+    /// throughout. Only venvs created by this library, marked with `MARKER_FILE`, are deleted:
+    /// anything else at the path is refused with `UnmanagedVenv`, so a mistyped path can't
+    /// wipe unrelated data.
+    pub fn recreate(self) -> Result<Venv<'a, Initialized>, VyperErrors> {
+        let _lock = self.lock()?;
+        if self.venv_path.exists() {
+            if !self.venv_path.join(MARKER_FILE).is_file() {
+                Err(VyperErrors::UnmanagedVenv {
+                    path: self.venv_path.to_path_buf(),
+                })?
+            }
+            debug_event!(venv = %self.venv_path.display(), "removing venv");
+            fs::remove_dir_all(self.venv_path)?;
+        }
+        self.init_locked()
+    }
+
+    /// Provisions a compiler matching `req` in one call. A matching global compiler is used as
+    /// is, then one already installed in the venv. Otherwise the venv is created if needed and
+    /// a matching compiler is installed into it.
+    pub fn ensure(self, req: &VersionReq) -> Result<Provisioned<'a>, VyperErrors> {
+        let vyper = std::env::var_os(VYPER_PATH).unwrap_or_else(|| "vyper".into());
+        self.ensure_with(req, Path::new(&vyper))
+    }
+
+    /// `ensure`, with `global` as the global compiler
+    pub(crate) fn ensure_with(
+        self,
+        req: &VersionReq,
+        global: &Path,
+    ) -> Result<Provisioned<'a>, VyperErrors> {
+        if version_satisfies(global_version(Command::new(global)).as_deref(), req) {
+            debug_event!(req = %req, "using the global compiler");
+            return Ok(Provisioned::Global(Venv {
+                venv_path: self.venv_path,
+                retry: self.retry,
+                python: self.python,
+                source: self.source,
+                installer: self.installer,
+                state: std::marker::PhantomData::<Complete>,
+            }));
+        }
+        self.ensure_venv(req).map(Provisioned::Venv)
+    }
+
+    /// `ensure` without looking at the global compiler: provisions a compiler matching `req`
+    /// in this venv, creating it and installing the compiler when needed
+    pub fn ensure_venv(self, req: &VersionReq) -> Result<Venv<'a, Ready>, VyperErrors> {
+        let venv = self.init()?;
+        if !version_satisfies(venv.fingerprint().vyper.as_deref(), req) {
+            debug_event!(venv = %venv.venv_path.display(), req = %req, "installing vyper");
+            let _lock = venv.lock()?;
+            venv.pip_install(venv.pip(), Some(&pip_specifier(req)), false)?;
+            venv.record_provenance()?;
+            let found = venv.fingerprint().vyper;
+            if !version_satisfies(found.as_deref(), req) {
+                Err(VyperErrors::VyperVersionMismatch {
+                    venv: venv.venv_path.to_path_buf(),
+                    expected: req.to_string(),
+                    found,
+                })?
+            }
+        }
+        venv.try_ready()
+    }
+
     /// For the psychopaths that decide to globally rawdog pip on their PC  
     pub fn skip() -> Venv<'a, Skip> {
         Venv {
             venv_path: Path::new("./venv"),
+            retry: RetryPolicy::default(),
+            python: None,
+            source: InstallSource::default(),
+            installer: InstallBackend::default(),
             state: std::marker::PhantomData::<Skip>,
         }
     }
@@ -159,131 +566,878 @@ impl<'a> Venv<'a, Initialized> {
     /// Installs vyper into virtual environment
     /// Optional argument for the version of vyper to be installed
     pub fn ivyper_venv(self, ver: Option<&'a str>) -> Result<Venv<'a, Ready>, VyperErrors> {
-        match ver {
-            Some(version) => {
-                if cfg!(target_os = "windows") {
-                    let c = Command::new("./venv/scripts/pip3")
-                        .arg("install")
-                        .arg(format!("vyper=={}", version))
-                        .output()?;
-                    if !c.status.success() {
-                        Err(VyperErrors::CompilerError(
-                            String::from_utf8_lossy(&c.stderr).to_string(),
-                        ))?
-                    }
-                    println!("Version {} of Vyper has been installed", version);
-                } else {
-                    let c = Command::new("./venv/bin/pip3")
-                        .arg("install")
-                        .arg(format!("vyper=={}", version))
-                        .output()?;
-                    if !c.status.success() {
-                        Err(VyperErrors::PipError(
-                            String::from_utf8_lossy(&c.stderr).to_string(),
-                        ))?
-                    }
-                    println!("Version {} of Vyper has been installed", version);
-                }
+        self.ivyper_venv_report(ver).map(|(venv, _)| venv)
+    }
 
-                Ok(Venv {
-                    venv_path: self.venv_path,
-                    state: std::marker::PhantomData::<Ready>,
-                })
-            }
-            None => {
-                if cfg!(target_os = "windows") {
-                    let c = Command::new("./venv/scripts/pip3")
-                        .arg("install")
-                        .arg("vyper")
-                        .output()?;
-                    if !c.status.success() {
-                        Err(VyperErrors::PipError(
-                            String::from_utf8_lossy(&c.stderr).to_string(),
-                        ))?
-                    }
-                    println!("The latest version of vyper has been installed");
-                } else {
-                    let c = Command::new("./venv/bin/pip3")
-                        .arg("install")
-                        .arg("vyper")
-                        .output()?;
-                    if !c.status.success() {
-                        Err(VyperErrors::PipError(
-                            String::from_utf8_lossy(&c.stderr).to_string(),
-                        ))?
-                    }
-                    println!("The latest version of vyper has been installed");
-                }
-                Ok(Venv {
-                    venv_path: self.venv_path,
-                    state: std::marker::PhantomData::<Ready>,
-                })
-            }
+    /// `ivyper_venv`, also returning what pip installed
+    pub fn ivyper_venv_report(
+        self,
+        ver: Option<&'a str>,
+    ) -> Result<(Venv<'a, Ready>, InstallReport), VyperErrors> {
+        let _lock = self.lock()?;
+        let mut report = self.pip_install(self.pip(), ver, false)?;
+        self.record_provenance()?;
+        if report.version.is_none() {
+            report.version = self.fingerprint().vyper;
         }
+        info_event!(
+            venv = %self.venv_path.display(),
+            version = report.version.as_deref().unwrap_or("latest"),
+            "installed vyper"
+        );
+        let venv = Venv {
+            venv_path: self.venv_path,
+            retry: self.retry,
+            python: self.python,
+            source: self.source,
+            installer: self.installer,
+            state: std::marker::PhantomData::<Ready>,
+        };
+        Ok((venv, report))
     }
+
+    /// Installs a declared set of python tools into the venv with a single pip invocation,
+    /// each pinned to its version, e.g. `&[("vyper", "0.4.0"), ("vyper-lsp", "0.1.0")]`. An
+    /// empty version installs the latest release. Packages come from the venv's
+    /// `InstallSource`, vyper from its wheel when it's one. Everything installed in the venv is then
+    /// frozen to `FREEZE_FILE`, whose path is returned, so the environment can be recreated
+    /// with `pip install -r`.
+    pub fn install_tools(&self, tools: &[(&str, &str)]) -> Result<PathBuf, VyperErrors> {
+        self.pip_install_tools(tools)
+    }
+
+    /// Like `try_ready()`, but also checks that the installed compiler is version `ver`, so
+    /// that a venv left behind by an older project isn't silently reused
+    pub fn try_ready_ver(self, ver: &str) -> Result<Venv<'a, Ready>, VyperErrors> {
+        let found = self.fingerprint().vyper;
+        match &found {
+            Some(found) if version_matches(found, ver) => self.try_ready(),
+            _ => Err(VyperErrors::VyperVersionMismatch {
+                venv: self.venv_path.to_path_buf(),
+                expected: ver.to_owned(),
+                found,
+            })?,
+        }
+    }
+
+    /// Makes sure the venv holds the requested compiler, reinstalling it when it's missing or
+    /// the version differs. With `None` any installed version is accepted.
+    pub fn repair(self, ver: Option<&'a str>) -> Result<Venv<'a, Ready>, VyperErrors> {
+        let healthy = match (ver, self.fingerprint().vyper) {
+            (Some(ver), Some(found)) => version_matches(&found, ver),
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if healthy {
+            self.try_ready()
+        } else {
+            debug_event!(venv = %self.venv_path.display(), "repairing venv");
+            self.ivyper_venv(ver)
+        }
+    }
+
     /// Check to see if Vyper is installed in a Venv. If so, transition state to Ready and
     /// access to the methods of this namespace.
     pub fn try_ready(self) -> Result<Venv<'a, Ready>, VyperErrors> {
+        match self.bin("vyper").exists() {
+            true => Ok(Venv {
+                venv_path: self.venv_path,
+                retry: self.retry,
+                python: self.python,
+                source: self.source,
+                installer: self.installer,
+                state: std::marker::PhantomData::<Ready>,
+            }),
+            false => Err(VyperErrors::VyperNotInstalled {
+                venv: Some(self.venv_path.to_path_buf()),
+            })?,
+        }
+    }
+}
+
+/// What's installed in a venv, see `Venv::fingerprint`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct VenvFingerprint {
+    /// Python version recorded in `pyvenv.cfg`
+    pub python: Option<String>,
+    /// Output of `vyper --version`, e.g. `0.3.10+commit.91361694`
+    pub vyper: Option<String>,
+}
+
+/// Oldest python that any supported vyper release can be installed on
+pub const MIN_PYTHON_VERSION: (u32, u32) = (3, 8);
+
+/// A python interpreter able to create venvs
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Python {
+    pub program: PathBuf,
+    /// Arguments passed before anything else, e.g. `-3` for the Windows `py` launcher
+    pub args: Vec<String>,
+    /// Major, minor and patch version
+    pub version: (u32, u32, u32),
+}
+
+impl Python {
+    /// Finds the first interpreter that meets `MIN_PYTHON_VERSION`, trying the python selected
+    /// by pyenv (when installed), then `python3`, `python` and, on Windows, `py -3` on PATH
+    pub fn discover() -> Result<Python, VyperErrors> {
+        let mut candidates: Vec<(PathBuf, Vec<String>)> = pyenv_which("python3")
+            .into_iter()
+            .map(|python| (python, vec![]))
+            .collect();
+        candidates.push(("python3".into(), vec![]));
+        candidates.push(("python".into(), vec![]));
         if cfg!(target_os = "windows") {
-            match self.venv_path.join("scripts/vyper").exists() {
-                true => Ok(Venv {
-                    venv_path: self.venv_path,
-                    state: std::marker::PhantomData::<Ready>,
-                }),
-                false => Err(VyperErrors::CompilerError(
-                    "Vyper was not installed in venv".to_owned(),
-                ))?,
+            candidates.push(("py".into(), vec!["-3".to_owned()]));
+        }
+        let mut too_old = None;
+        for (program, args) in candidates {
+            match Python::probe(&program, args) {
+                Ok(python) => return Ok(python),
+                Err(e @ VyperErrors::UnsupportedPython { .. }) => {
+                    too_old.get_or_insert(e);
+                }
+                Err(_) => continue,
             }
-        } else {
-            match self.venv_path.join("bin/vyper").exists() {
-                true => Ok(Venv {
-                    venv_path: self.venv_path,
-                    state: std::marker::PhantomData::<Ready>,
-                }),
-                false => Err(VyperErrors::CompilerError(
-                    "Vyper was not installed in venv".to_owned(),
-                ))?,
+        }
+        Err(too_old.unwrap_or(VyperErrors::PythonNotFound))
+    }
+
+    /// Uses the interpreter at `path`, checking that it meets `MIN_PYTHON_VERSION`
+    pub fn at(path: &Path) -> Result<Python, VyperErrors> {
+        Python::probe(path, vec![])
+    }
+
+    fn probe(program: &Path, args: Vec<String>) -> Result<Python, VyperErrors> {
+        let out = Command::new(program)
+            .args(&args)
+            .arg("--version")
+            .output_timeout(None)?;
+        if !out.status.success() {
+            Err(VyperErrors::PythonNotFound)?
+        }
+        // python 2 prints its version to stderr
+        let text = [out.stdout, out.stderr].concat();
+        let text = String::from_utf8_lossy(&text);
+        let version = text
+            .trim()
+            .strip_prefix("Python ")
+            .map(parse_version)
+            .ok_or(VyperErrors::PythonNotFound)?;
+        if (version.0, version.1) < MIN_PYTHON_VERSION {
+            Err(VyperErrors::UnsupportedPython {
+                python: program.to_path_buf(),
+                version: format!("{}.{}.{}", version.0, version.1, version.2),
+                minimum: MIN_PYTHON_VERSION,
+            })?
+        }
+        Ok(Python {
+            program: program.to_path_buf(),
+            args,
+            version,
+        })
+    }
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        cmd
+    }
+}
+
+/// Where vyper is installed from
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum InstallSource<'a> {
+    /// The package index pip is configured with. Nothing is passed to pip, so `PIP_INDEX_URL`,
+    /// `pip.conf` and proxy settings such as `HTTPS_PROXY` all apply.
+    #[default]
+    Index,
+    /// A specific package index, e.g. a corporate mirror
+    IndexUrl(&'a str),
+    /// A pre-downloaded `.whl` file, or a directory of wheels containing vyper and its
+    /// dependencies. The network is never used: dependencies are looked up in the directory,
+    /// or next to the file. A file must be the requested version.
+    LocalWheel(&'a Path),
+    /// A pre-downloaded `.whl` file that is only installed if its sha256 matches `sha256`, e.g.
+    /// the hash published on PyPI or GitHub for that release
+    VerifiedWheel { wheel: &'a Path, sha256: &'a str },
+}
+
+impl<'a> InstallSource<'a> {
+    /// Arguments following `pip install`
+    fn pip_args(&self, ver: Option<&str>) -> Vec<OsString> {
+        self.pip_args_for(&[("vyper", ver)])
+    }
+
+    /// Arguments following `pip install` for the packages `(name, version)`. A wheel file
+    /// stands in for vyper's requirement.
+    fn pip_args_for(&self, packages: &[(&str, Option<&str>)]) -> Vec<OsString> {
+        let mut args: Vec<OsString> = match self {
+            InstallSource::IndexUrl(url) => vec!["--index-url".into(), url.into()],
+            InstallSource::LocalWheel(path) => {
+                vec![
+                    "--no-index".into(),
+                    "--find-links".into(),
+                    wheel_dir(path).into(),
+                ]
+            }
+            _ => vec![],
+        };
+        for (name, ver) in packages {
+            args.push(match self {
+                InstallSource::LocalWheel(wheel)
+                | InstallSource::VerifiedWheel { wheel, .. }
+                    if *name == "vyper" && wheel.is_file() =>
+                {
+                    wheel.into()
+                }
+                _ => requirement(name, *ver).into(),
+            });
+        }
+        args
+    }
+
+    /// Arguments following `pipx install`
+    fn pipx_args(&self, ver: Option<&str>) -> Vec<OsString> {
+        match self {
+            InstallSource::LocalWheel(path) => vec![
+                "--pip-args".into(),
+                format!("--no-index --find-links \"{}\"", wheel_dir(path).display())
+                    .into(),
+                match path.is_file() {
+                    true => path.into(),
+                    false => package_spec(ver),
+                },
+            ],
+            _ => self.pip_args(ver),
+        }
+    }
+
+    /// Checks that a wheel file is version `ver` and matches its expected hash before it's
+    /// handed to pip
+    fn verify(&self, ver: Option<&str>) -> Result<(), VyperErrors> {
+        if let InstallSource::LocalWheel(wheel)
+        | InstallSource::VerifiedWheel { wheel, .. } = self
+        {
+            // `{name}-{version}-...whl`
+            let found = wheel
+                .file_name()
+                .and_then(|name| name.to_str()?.split('-').nth(1));
+            let req = ver.and_then(pip_req);
+            let version = found.and_then(|found| utils::parse_version(found).ok());
+            if let (Some(req), Some(version)) = (req, version) {
+                if wheel.is_file() && !req.matches(&version) {
+                    Err(VyperErrors::PipVersionNotFound {
+                        version: ver.map(str::to_owned),
+                        available: found.map(str::to_owned).into_iter().collect(),
+                    })?
+                }
+            }
+        }
+        if let InstallSource::VerifiedWheel { wheel, sha256 } = self {
+            let found = utils::sha256_file(wheel)?;
+            if !found.eq_ignore_ascii_case(sha256) {
+                Err(VyperErrors::ChecksumMismatch {
+                    path: wheel.to_path_buf(),
+                    expected: sha256.to_string(),
+                    found: Some(found),
+                })?
+            }
+        }
+        Ok(())
+    }
+
+    /// Human readable description recorded in `Provenance::source`
+    fn describe(&self) -> String {
+        match self {
+            InstallSource::Index => match std::env::var("PIP_INDEX_URL") {
+                Ok(url) => format!("index {}", url),
+                Err(_) => "index".to_owned(),
+            },
+            InstallSource::IndexUrl(url) => format!("index {}", url),
+            InstallSource::LocalWheel(path) => format!("wheel {}", path.display()),
+            InstallSource::VerifiedWheel { wheel, .. } => {
+                format!("wheel {}", wheel.display())
+            }
+        }
+    }
+
+    fn wheel_sha256(&self) -> Option<String> {
+        match self {
+            InstallSource::LocalWheel(wheel) if wheel.is_file() => {
+                utils::sha256_file(wheel).ok()
+            }
+            InstallSource::VerifiedWheel { sha256, .. } => Some(sha256.to_lowercase()),
+            _ => None,
+        }
+    }
+}
+
+/// Directory pip looks up the wheels of a `LocalWheel` in
+fn wheel_dir(path: &Path) -> &Path {
+    match path.is_file() {
+        true => path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new(".")),
+        false => path,
+    }
+}
+
+fn package_spec(ver: Option<&str>) -> OsString {
+    requirement("vyper", ver).into()
+}
+
+/// Requirement for package `name` at version `ver`, which can also be a specifier, i.e.
+/// `vyper==0.3.10` or `vyper>=0.3.10,<0.4.0`
+fn requirement(name: &str, ver: Option<&str>) -> String {
+    match ver {
+        // already a specifier, see `pip_specifier`
+        Some(spec) if spec.starts_with(['=', '<', '>', '!', '~']) => {
+            format!("{}{}", name, spec)
+        }
+        Some(version) => format!("{}=={}", name, version),
+        None => name.to_owned(),
+    }
+}
+
+/// What an installation put in place, as reported by pip (or pipx, or uv)
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct InstallReport {
+    /// The installed vyper version, `None` when the installer didn't report it
+    pub version: Option<String>,
+    /// File name of the wheel vyper was installed from, `None` when it was already installed
+    /// or the installer didn't report it
+    pub wheel: Option<String>,
+    /// The requested version was installed already, so nothing changed
+    pub already_installed: bool,
+    /// Where vyper was installed from, as in `Provenance::source`
+    pub source: String,
+}
+
+impl InstallReport {
+    fn parse(source: &InstallSource, stdout: &[u8], stderr: &[u8]) -> InstallReport {
+        let (stdout, stderr) = (
+            String::from_utf8_lossy(stdout),
+            String::from_utf8_lossy(stderr),
+        );
+        let mut report = InstallReport {
+            source: source.describe(),
+            ..Default::default()
+        };
+        // uv reports to stderr
+        for line in stdout.lines().chain(stderr.lines()).map(str::trim) {
+            if let Some(installed) = line.strip_prefix("Successfully installed ") {
+                report.version = installed
+                    .split_whitespace()
+                    .find_map(|package| package.strip_prefix("vyper-"))
+                    .map(str::to_owned)
+                    .or(report.version);
+            } else if let Some(rest) = line.strip_prefix("installed package vyper ") {
+                // pipx: `installed package vyper 0.3.10, installed using Python 3.11.4`
+                report.version = rest.split(',').next().map(|v| v.trim().to_owned());
+            } else if let Some(version) = line.strip_prefix("+ vyper==") {
+                report.version = Some(version.trim().to_owned());
+            } else if let Some(rest) =
+                line.strip_prefix("Requirement already satisfied: vyper")
+            {
+                report.already_installed = true;
+                report.version = rest
+                    .strip_prefix("==")
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .map(str::to_owned)
+                    .or(report.version);
+            }
+            let wheel = line
+                .split_whitespace()
+                .filter_map(|word| word.rsplit(['/', '\\']).next())
+                .find(|file| file.starts_with("vyper-") && file.ends_with(".whl"));
+            if let Some(wheel) = wheel {
+                report.wheel = Some(wheel.to_owned());
+            }
+        }
+        report
+    }
+}
+
+/// The error for a failed `pip install` of `ver`, telling apart the failures that can be
+/// acted on from pip's output
+fn pip_error(ver: Option<&str>, stderr: &str) -> VyperErrors {
+    const NETWORK: [&str; 7] = [
+        "Failed to establish a new connection",
+        "Temporary failure in name resolution",
+        "Could not fetch URL",
+        "ConnectTimeoutError",
+        "ReadTimeoutError",
+        "ProxyError",
+        "SSLError",
+    ];
+    let version = ver.map(str::to_owned);
+    let stderr = stderr.to_owned();
+    if NETWORK.iter().any(|marker| stderr.contains(marker)) {
+        VyperErrors::PipNetworkError { version, stderr }
+    } else if stderr.contains("Permission denied") || stderr.contains("[Errno 13]") {
+        VyperErrors::PipPermissionDenied { version, stderr }
+    } else if let Some(at) = stderr.find("Could not find a version that satisfies") {
+        // `... (from versions: 0.1.0b1, 0.1.0b2)`, or `(from versions: none)`
+        let available = stderr[at..]
+            .split_once("(from versions:")
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .map(|(versions, _)| {
+                versions
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty() && *v != "none")
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        VyperErrors::PipVersionNotFound { version, available }
+    } else {
+        VyperErrors::PipInstallFailed { version, stderr }
+    }
+}
+
+/// Tools that install vyper, either into a venv managed by this library or outside of one
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum InstallBackend {
+    /// `pip3 install`: the venv's own pip, or whichever python is active outside of a venv.
+    /// That's the pyenv-selected python when pyenv is installed.
+    #[default]
+    Pip,
+    /// `pipx install`, into an isolated environment with the executable linked into
+    /// `PIPX_BIN_DIR`. pipx can't install into a venv, which falls back to its own pip.
+    Pipx,
+    /// `uv pip install`, targeting the venv through `VIRTUAL_ENV`, or the active python
+    /// through `UV_SYSTEM_PYTHON` outside of a venv. `uv` has to be in PATH.
+    Uv,
+}
+
+impl InstallBackend {
+    /// Path of the vyper executable installed with this backend, if there is one. `None` for
+    /// `Pip` and `Uv` means the compiler, if any, is resolved through PATH.
+    pub fn vyper_bin(&self) -> Option<PathBuf> {
+        self.vyper_bin_in(pipx_bin_dir())
+    }
+
+    /// `vyper_bin`, with pipx linking executables into `pipx_bin_dir`
+    pub(crate) fn vyper_bin_in(&self, pipx_bin_dir: Option<PathBuf>) -> Option<PathBuf> {
+        match self {
+            InstallBackend::Pip | InstallBackend::Uv => pyenv_which("vyper"),
+            InstallBackend::Pipx => pipx_bin_dir
+                .map(|dir| dir.join(exe("vyper")))
+                .filter(|bin| bin.is_file()),
+        }
+    }
+
+    /// The first backend with vyper installed and the path of its executable, so that an
+    /// existing pipx or pyenv installation can be passed to `Vyper::with_compiler` instead of
+    /// creating yet another venv
+    pub fn detect() -> Option<(InstallBackend, PathBuf)> {
+        InstallBackend::detect_in(pipx_bin_dir())
+    }
+
+    /// `detect`, with pipx linking executables into `pipx_bin_dir`
+    pub(crate) fn detect_in(
+        pipx_bin_dir: Option<PathBuf>,
+    ) -> Option<(InstallBackend, PathBuf)> {
+        [InstallBackend::Pipx, InstallBackend::Pip]
+            .into_iter()
+            .find_map(|backend| {
+                backend
+                    .vyper_bin_in(pipx_bin_dir.clone())
+                    .map(|bin| (backend, bin))
+            })
+    }
+}
+
+/// Directory pipx links executables into: `PIPX_BIN_DIR` or its default, `~/.local/bin`
+fn pipx_bin_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("PIPX_BIN_DIR") {
+        return Some(dir.into());
+    }
+    let home = std::env::var_os(if cfg!(target_os = "windows") {
+        "USERPROFILE"
+    } else {
+        "HOME"
+    })?;
+    Some(Path::new(&home).join(".local").join("bin"))
+}
+
+/// Asks pyenv for the real path of `name` in the active python, skipping the shim
+fn pyenv_which(name: &str) -> Option<PathBuf> {
+    let out = Command::new("pyenv")
+        .arg("which")
+        .arg(name)
+        .output_timeout(None)
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let path = PathBuf::from(String::from_utf8_lossy(&out.stdout).trim());
+    path.is_file().then_some(path)
+}
+
+/// Path of the executable `name` in the venv at `venv`. A venv created by a Windows python keeps
+/// its executables in `Scripts` with an `.exe` suffix, except for MSYS2 and Cygwin pythons which
+/// use `bin` like everywhere else, so on Windows every layout is probed. When the executable
+/// doesn't exist, the path the platform's python would create is returned.
+pub fn executable(venv: &Path, name: &str) -> PathBuf {
+    let candidates = executable_candidates(venv, name, cfg!(target_os = "windows"));
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_file())
+        .unwrap_or(&candidates[0])
+        .clone()
+}
+
+/// Where `name` may be in the venv at `venv`, the default layout of the platform first
+pub(crate) fn executable_candidates(
+    venv: &Path,
+    name: &str,
+    windows: bool,
+) -> Vec<PathBuf> {
+    if !windows {
+        return vec![venv.join("bin").join(name)];
+    }
+    let names = [format!("{}.exe", name), name.to_owned()];
+    ["Scripts", "bin"]
+        .into_iter()
+        .flat_map(|dir| names.iter().map(move |name| venv.join(dir).join(name)))
+        .collect()
+}
+
+fn exe(name: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("{}.exe", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Parses `3.11.4`, ignoring anything after the patch number such as `rc1`
+fn parse_version(version: &str) -> (u32, u32, u32) {
+    let mut parts = version.trim().split('.').map(|part| {
+        part.chars()
+            .take_while(char::is_ascii_digit)
+            .collect::<String>()
+            .parse()
+            .unwrap_or(0)
+    });
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    )
+}
+
+/// File in the venv recording where its compiler came from
+pub const PROVENANCE_FILE: &str = "vyper-rs-provenance.json";
+
+/// File in the venv listing every package installed in it, written by `Venv::install_tools`
+/// in the format of `pip freeze`
+pub const FREEZE_FILE: &str = "vyper-rs-requirements.txt";
+
+/// Name of the `FileLock` held while installing the compiler globally with pip or pipx
+pub const GLOBAL_INSTALL_LOCK: &str = "global-install";
+
+/// File marking a venv as created and managed by this library, see `Venv::marker`
+pub const MARKER_FILE: &str = "vyper-rs-venv.json";
+
+/// Written to `MARKER_FILE` when `Venv::init` creates a venv
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct VenvMarker {
+    /// `vyper-rs` followed by the version of this library
+    pub created_by: String,
+    /// The interpreter the venv was created with
+    pub python: PathBuf,
+    pub python_version: String,
+}
+
+/// Where the compiler in a venv came from, recorded on installation
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Output of `vyper --version` right after installation
+    pub version: Option<String>,
+    /// `index`, followed by its URL when known, or `wheel` followed by the wheel's path
+    pub source: String,
+    /// sha256 of the installed wheel, when installed from a local wheel file
+    pub wheel_sha256: Option<String>,
+    /// sha256 of pip's `RECORD` for the installed package, which in turn lists the hash of
+    /// every installed file
+    pub record_sha256: Option<String>,
+}
+
+/// A digest in the form used by pip's `RECORD`: urlsafe base64 without padding
+pub(crate) fn record_hash(bytes: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(bytes))
+}
+
+/// A compiler installed in a venv, see `Venv::list`
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct InstalledCompiler {
+    pub venv: PathBuf,
+    /// Output of `vyper --version`
+    pub version: String,
+}
+
+/// Whether `path` was created by `python -m venv`
+fn is_venv(path: &Path) -> bool {
+    path.join("pyvenv.cfg").is_file()
+}
+
+/// pip's `RECORD` of the vyper package installed in the venv at `venv`
+fn dist_info_record(venv: &Path) -> Option<PathBuf> {
+    let lib = if cfg!(target_os = "windows") {
+        venv.join("Lib")
+    } else {
+        venv.join("lib")
+    };
+    let mut site_packages = vec![lib.join("site-packages")];
+    if let Ok(entries) = fs::read_dir(&lib) {
+        site_packages.extend(entries.flatten().map(|e| e.path().join("site-packages")));
+    }
+    site_packages
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    name.starts_with("vyper-") && name.ends_with(".dist-info")
+                })
+        })
+        .map(|dist_info| dist_info.join("RECORD"))
+        .filter(|record| record.is_file())
+}
+
+/// sha256 of the vyper distribution installed in the venv at `venv`, over the hashes its
+/// `RECORD` lists for the files of the package. Entry point scripts, whose shebang names the
+/// venv, and the files pip writes to the dist-info directory are left out, so installing the
+/// same wheel anywhere gives the same hash. `None` when vyper isn't installed with pip.
+pub fn distribution_sha256(venv: &Path) -> Result<Option<String>, VyperErrors> {
+    let Some(record) = dist_info_record(venv) else {
+        return Ok(None);
+    };
+    let mut entries: Vec<String> = fs::read_to_string(&record)?
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, ',');
+            let (_size, hash, path) = (fields.next()?, fields.next()?, fields.next()?);
+            let path = path.trim_matches('"');
+            let package = !path.starts_with("..") && !path.contains(".dist-info/");
+            (package && !hash.is_empty()).then(|| format!("{},{}", path, hash))
+        })
+        .collect();
+    entries.sort();
+    Ok(Some(utils::sha256_hex(entries.join("\n").as_bytes())))
+}
+
+/// Output of `vyper --version`, `None` when `vyper` can't be invoked
+fn global_version(mut vyper: Command) -> Option<String> {
+    vyper
+        .arg("--version")
+        .output_timeout(None)
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_owned())
+}
+
+/// Whether the output of `vyper --version` satisfies `req`
+fn version_satisfies(found: Option<&str>, req: &VersionReq) -> bool {
+    found
+        .and_then(|found| utils::parse_version(found).ok())
+        .map_or(false, |version| req.matches(&version))
+}
+
+/// `req` as a pip version specifier, e.g. `>=0.3.10,<0.4.0` for `^0.3.10`
+pub(crate) fn pip_specifier(req: &VersionReq) -> String {
+    use semver::Op;
+    let mut specs = vec![];
+    for c in &req.comparators {
+        let pre = c.pre.as_str().replace(['.', '-'], "");
+        let exact = match (c.minor, c.patch) {
+            (Some(minor), Some(patch)) => {
+                format!("{}.{}.{}{}", c.major, minor, patch, pre)
             }
+            (Some(minor), None) => format!("{}.{}", c.major, minor),
+            _ => c.major.to_string(),
+        };
+        // the first version past the precision given, e.g. 0.4 for 0.3 and 1 for 0
+        let next = match (c.minor, c.patch) {
+            (Some(minor), Some(patch)) => format!("{}.{}.{}", c.major, minor, patch + 1),
+            (Some(minor), None) => format!("{}.{}", c.major, minor + 1),
+            _ => (c.major + 1).to_string(),
+        };
+        let wildcard = match c.patch {
+            Some(_) => format!("=={}", exact),
+            None => format!("=={}.*", exact),
+        };
+        match c.op {
+            Op::Exact | Op::Wildcard => specs.push(wildcard),
+            Op::Greater if c.patch.is_some() => specs.push(format!(">{}", exact)),
+            Op::Greater => specs.push(format!(">={}", next)),
+            Op::GreaterEq => specs.push(format!(">={}", exact)),
+            Op::Less => specs.push(format!("<{}", exact)),
+            Op::LessEq if c.patch.is_some() => specs.push(format!("<={}", exact)),
+            Op::LessEq => specs.push(format!("<{}", next)),
+            Op::Tilde => {
+                let upper = match c.minor {
+                    Some(minor) => format!("{}.{}", c.major, minor + 1),
+                    None => (c.major + 1).to_string(),
+                };
+                specs.push(format!(">={}", exact));
+                specs.push(format!("<{}", upper));
+            }
+            Op::Caret => {
+                let upper = match (c.major, c.minor, c.patch) {
+                    (0, Some(0), Some(patch)) => format!("0.0.{}", patch + 1),
+                    (0, Some(minor), _) => format!("0.{}", minor + 1),
+                    (major, _, _) => (major + 1).to_string(),
+                };
+                specs.push(format!(">={}", exact));
+                specs.push(format!("<{}", upper));
+            }
+            _ => specs.push(wildcard),
         }
     }
+    specs.join(",")
+}
+
+/// `ver`, a version or specifier as passed to pip such as `0.3.10`, `==0.3.*` or
+/// `>=0.3.10,<0.4`, as a `VersionReq`. `!=` clauses are left out, `None` when nothing is left
+/// or the specifier can't be parsed, leaving the check to pip.
+fn pip_req(ver: &str) -> Option<VersionReq> {
+    let clauses: Vec<String> = ver
+        .split(',')
+        .map(str::trim)
+        .filter(|clause| !clause.is_empty() && !clause.starts_with("!="))
+        .map(|clause| {
+            if let Some(compatible) = clause.strip_prefix("~=") {
+                format!("~{}", compatible)
+            } else if clause.starts_with(['<', '>']) {
+                clause.to_owned()
+            } else {
+                let version = clause.trim_start_matches('=');
+                // semver writes `==0.3.*` as `0.3.*`
+                if version.ends_with(".*") {
+                    version.to_owned()
+                } else {
+                    format!("={}", version)
+                }
+            }
+        })
+        .collect();
+    if clauses.is_empty() {
+        return None;
+    }
+    VersionReq::parse(&clauses.join(",")).ok()
+}
+
+/// Compares the output of `vyper --version` with a version as passed to pip, ignoring the
+/// commit hash
+fn version_matches(found: &str, expected: &str) -> bool {
+    let found = found.trim();
+    let found = found.split_once('+').map_or(found, |(v, _)| v);
+    found == expected.trim().trim_start_matches("==")
 }
 
 impl<'a> Venv<'a, Skip> {
-    /// Installs vyper compiler globally, without the protection of a venv
+    /// Installs vyper compiler globally, without the protection of a venv, with pip or the
+    /// installer set with `with_installer`
     /// Optional argument for the version of vyper to be installed
     pub fn ivyper_pip(self, ver: Option<&'a str>) -> Result<Venv<Complete>, VyperErrors> {
-        match ver {
-            Some(version) => {
-                let c = Command::new("pip3")
-                    .arg("install")
-                    .arg(format!("vyper=={}", version))
-                    .output()?;
-                if !c.status.success() {
-                    Err(VyperErrors::PipError(
-                        String::from_utf8_lossy(&c.stderr).to_string(),
-                    ))?
-                }
-                println!("Version {} of Vyper has been installed", version);
-            }
-            None => {
-                let c = Command::new("pip3").arg("install").arg("vyper").output()?;
-                if !c.status.success() {
-                    Err(VyperErrors::PipError(
-                        String::from_utf8_lossy(&c.stderr).to_string(),
-                    ))?
-                }
-                println!("The Latest Version of Vyper has been installed");
+        self.ivyper_pip_report(ver).map(|(venv, _)| venv)
+    }
+
+    /// `ivyper_pip`, also returning what pip installed
+    pub fn ivyper_pip_report(
+        self,
+        ver: Option<&'a str>,
+    ) -> Result<(Venv<'a, Complete>, InstallReport), VyperErrors> {
+        let _lock = FileLock::named(GLOBAL_INSTALL_LOCK)?;
+        let pip = match self.installer {
+            InstallBackend::Pip | InstallBackend::Pipx => Command::new("pip3"),
+            InstallBackend::Uv => {
+                let mut uv = Command::new("uv");
+                uv.arg("pip").env("UV_SYSTEM_PYTHON", "1");
+                uv
             }
+        };
+        let report = self.pip_install(pip, ver, false)?;
+        info_event!(
+            version = report.version.as_deref().unwrap_or("latest"),
+            "installed vyper globally"
+        );
+        Ok((self.complete(), report))
+    }
+
+    /// Installs vyper with pipx, replacing any version pipx installed before
+    /// Optional argument for the version of vyper to be installed
+    pub fn ivyper_pipx(
+        self,
+        ver: Option<&'a str>,
+    ) -> Result<Venv<'a, Complete>, VyperErrors> {
+        self.ivyper_pipx_report(ver).map(|(venv, _)| venv)
+    }
+
+    /// `ivyper_pipx`, also returning what pipx installed
+    pub fn ivyper_pipx_report(
+        self,
+        ver: Option<&'a str>,
+    ) -> Result<(Venv<'a, Complete>, InstallReport), VyperErrors> {
+        self.source.verify(ver)?;
+        let _lock = FileLock::named(GLOBAL_INSTALL_LOCK)?;
+        let c = Command::new("pipx")
+            .arg("install")
+            .arg("--force")
+            .args(self.source.pipx_args(ver))
+            .output_retry_status(None, &self.retry)?;
+        if !c.status.success() {
+            Err(pip_error(ver, &String::from_utf8_lossy(&c.stderr)))?
         }
-        Ok(Venv {
+        let report = InstallReport::parse(&self.source, &c.stdout, &c.stderr);
+        info_event!(
+            version = report.version.as_deref().unwrap_or("latest"),
+            "installed vyper with pipx"
+        );
+        Ok((self.complete(), report))
+    }
+
+    fn complete(self) -> Venv<'a, Complete> {
+        Venv {
             venv_path: self.venv_path,
+            retry: self.retry,
+            python: self.python,
+            source: self.source,
+            installer: self.installer,
             state: std::marker::PhantomData::<Complete>,
-        })
+        }
     }
 
-    /// checks whether vyper is in PATH and can be invoked by this library
+    /// Installs vyper with `backend`, see `ivyper_pip` and `ivyper_pipx`. `Uv` installs into
+    /// the active python like `Pip`.
+    pub fn install(
+        self,
+        backend: InstallBackend,
+        ver: Option<&'a str>,
+    ) -> Result<Venv<'a, Complete>, VyperErrors> {
+        match backend {
+            InstallBackend::Pipx => self.ivyper_pipx(ver),
+            InstallBackend::Pip | InstallBackend::Uv => {
+                self.with_installer(backend).ivyper_pip(ver)
+            }
+        }
+    }
+
+    /// checks whether vyper is in PATH (or set with `VYPER_PATH`) and can be invoked by this
+    /// library
     pub fn global_exists() -> bool {
-        Command::new("vyper").arg("-h").output().is_ok()
+        let vyper = std::env::var_os(VYPER_PATH).unwrap_or_else(|| "vyper".into());
+        Command::new(vyper).arg("-h").output_timeout(None).is_ok()
     }
 
     /// Transition to Complete if the Vyper compiler is installed globally
@@ -291,55 +1445,259 @@ impl<'a> Venv<'a, Skip> {
         match Self::global_exists() {
             true => Ok(Venv {
                 venv_path: self.venv_path,
+                retry: self.retry,
+                python: self.python,
+                source: self.source,
+                installer: self.installer,
                 state: std::marker::PhantomData::<Complete>,
             }),
-            false => Err(VyperErrors::CompilerError("Vyper not installed".to_owned()))?,
+            false => Err(VyperErrors::VyperNotInstalled { venv: None })?,
+        }
+    }
+}
+
+/// A provisioned compiler, either installed in a venv (`Venv<Ready>`) or globally
+/// (`Venv<Complete>`), for code that compiles contracts without caring which one it got
+pub trait VyperProvider<'a> {
+    /// The venv holding the compiler, `None` when it's installed globally
+    fn venv(&self) -> Option<&'a Path>;
+
+    /// The retry policy given to every contract
+    fn retry(&self) -> RetryPolicy;
+
+    /// A contract compiled with this compiler
+    fn contract(&self, path: &'a Path) -> Vyper<'a> {
+        let vyper = match self.venv() {
+            Some(venv) => Vyper::with_venv(path, venv),
+            None => Vyper::new(path),
+        };
+        vyper.set_retry(self.retry())
+    }
+
+    /// Contracts compiled concurrently with this compiler
+    fn contracts(&self, paths: Vec<PathBuf>) -> Vypers {
+        let vypers = match self.venv() {
+            Some(venv) => Vypers::with_venv(paths, venv),
+            None => Vypers::new(paths),
+        };
+        vypers.set_retry(self.retry())
+    }
+}
+
+impl<'a> VyperProvider<'a> for Venv<'a, Ready> {
+    fn venv(&self) -> Option<&'a Path> {
+        Some(self.venv_path)
+    }
+
+    fn retry(&self) -> RetryPolicy {
+        self.retry
+    }
+}
+
+impl<'a> VyperProvider<'a> for Venv<'a, Complete> {
+    fn venv(&self) -> Option<&'a Path> {
+        None
+    }
+
+    fn retry(&self) -> RetryPolicy {
+        self.retry
+    }
+}
+
+/// The compiler provisioned by `Venv::ensure`
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Provisioned<'a> {
+    Venv(Venv<'a, Ready>),
+    Global(Venv<'a, Complete>),
+}
+
+impl<'a> VyperProvider<'a> for Provisioned<'a> {
+    fn venv(&self) -> Option<&'a Path> {
+        match self {
+            Provisioned::Venv(venv) => VyperProvider::venv(venv),
+            Provisioned::Global(global) => VyperProvider::venv(global),
+        }
+    }
+
+    fn retry(&self) -> RetryPolicy {
+        match self {
+            Provisioned::Venv(venv) => VyperProvider::retry(venv),
+            Provisioned::Global(global) => VyperProvider::retry(global),
         }
     }
 }
 
 impl<'a> Venv<'a, Complete> {
     pub fn vyper(self, path_to_contract: &'a Path) -> Vyper<'a> {
-        Vyper::new(path_to_contract)
+        Vyper::new(path_to_contract).set_retry(self.retry)
+    }
+
+    /// A contract for each of `paths`, configured like `vyper()`
+    pub fn into_vyper_for_each(
+        self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> Vec<Vyper<'a>> {
+        paths.into_iter().map(|path| self.vyper(path)).collect()
     }
 
     pub fn vypers(self, paths: Vec<PathBuf>) -> Vypers {
-        Vypers::new(paths)
+        Vypers::new(paths).set_retry(self.retry)
     }
 
     pub fn vyper_with_abi(self, path: &'a Path, abi: PathBuf) -> Vyper<'a> {
-        Vyper::with_abi(path, abi)
+        Vyper::with_abi(path, abi).set_retry(self.retry)
     }
 
-    pub fn vypers_from_dir(self, path: PathBuf) -> Option<Vypers> {
-        Vypers::in_dir(path)
+    pub fn vypers_from_dir(
+        self,
+        path: PathBuf,
+        allow_empty: bool,
+    ) -> Result<Vypers, VyperErrors> {
+        Vypers::in_dir(path, allow_empty).map(|e| e.set_retry(self.retry))
     }
 
-    pub async fn vypers_from_workspace(self, path: PathBuf) -> Option<Vypers> {
-        Vypers::in_workspace(path).await
+    #[cfg(feature = "async")]
+    pub async fn vypers_from_workspace(
+        self,
+        path: PathBuf,
+        allow_empty: bool,
+    ) -> Result<Vypers, VyperErrors> {
+        Vypers::in_workspace(path, allow_empty)
+            .await
+            .map(|e| e.set_retry(self.retry))
     }
 }
 
 impl<'a> Venv<'a, Ready> {
+    /// Installs tools next to the compiler, see `Venv::<Initialized>::install_tools`
+    pub fn install_tools(&self, tools: &[(&str, &str)]) -> Result<PathBuf, VyperErrors> {
+        self.pip_install_tools(tools)
+    }
+
+    /// Verifies every file of the installed compiler against the sha256 recorded by pip at
+    /// install time, and the record itself against the provenance written by this library.
+    /// Returns the number of files verified.
+    pub fn verify_integrity(&self) -> Result<usize, VyperErrors> {
+        let record =
+            self.dist_info_record()
+                .ok_or_else(|| VyperErrors::VyperNotInstalled {
+                    venv: Some(self.venv_path.to_path_buf()),
+                })?;
+        if let Some(expected) = self.provenance().and_then(|p| p.record_sha256) {
+            let found = utils::sha256_file(&record)?;
+            if found != expected {
+                Err(VyperErrors::ChecksumMismatch {
+                    path: record.clone(),
+                    expected,
+                    found: Some(found),
+                })?
+            }
+        }
+        // entries are relative to site-packages, the parent of the dist-info directory
+        let root = record
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(self.venv_path);
+        let mut verified = 0;
+        for line in fs::read_to_string(&record)?.lines() {
+            let mut fields = line.rsplitn(3, ',');
+            let (_size, hash, path) = (fields.next(), fields.next(), fields.next());
+            let (Some(hash), Some(path)) = (hash, path) else {
+                continue;
+            };
+            let Some(expected) = hash.strip_prefix("sha256=") else {
+                continue;
+            };
+            let path = root.join(path.trim_matches('"'));
+            let found = fs::read(&path).ok().map(|bytes| record_hash(&bytes));
+            if found.as_deref() != Some(expected) {
+                Err(VyperErrors::ChecksumMismatch {
+                    path,
+                    expected: expected.to_owned(),
+                    found,
+                })?
+            }
+            verified += 1;
+        }
+        Ok(verified)
+    }
+
+    /// Upgrades the compiler in the venv to `ver`, or to the latest release with `None`
+    pub fn upgrade(self, ver: Option<&str>) -> Result<Venv<'a, Ready>, VyperErrors> {
+        let _lock = self.lock()?;
+        self.pip_install(self.pip(), ver, true)?;
+        self.record_provenance()?;
+        Ok(self)
+    }
+
+    /// Removes the compiler from the venv, keeping the venv itself
+    pub fn uninstall(self) -> Result<Venv<'a, Initialized>, VyperErrors> {
+        let _lock = self.lock()?;
+        let mut pip = self.pip();
+        pip.arg("uninstall");
+        // uv never prompts and rejects the flag
+        if self.installer != InstallBackend::Uv {
+            pip.arg("--yes");
+        }
+        let c = pip.arg("vyper").output_retry_status(None, &self.retry)?;
+        if !c.status.success() {
+            Err(VyperErrors::PipUninstallFailed {
+                venv: self.venv_path.to_path_buf(),
+                stderr: String::from_utf8_lossy(&c.stderr).to_string(),
+            })?
+        }
+        Ok(Venv {
+            venv_path: self.venv_path,
+            retry: self.retry,
+            python: self.python,
+            source: self.source,
+            installer: self.installer,
+            state: std::marker::PhantomData::<Initialized>,
+        })
+    }
+
     pub fn vyper(self, path_to_contract: &'a Path) -> Vyper<'a> {
-        Vyper::with_venv(path_to_contract, self.venv_path)
+        Vyper::with_venv(path_to_contract, self.venv_path).set_retry(self.retry)
+    }
+
+    /// A contract for each of `paths`, configured like `vyper()`
+    pub fn into_vyper_for_each(
+        self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> Vec<Vyper<'a>> {
+        paths.into_iter().map(|path| self.vyper(path)).collect()
     }
 
     pub fn vypers(self, paths: Vec<PathBuf>) -> Vypers {
-        Vypers::with_venv(paths, self.venv_path)
+        Vypers::with_venv(paths, self.venv_path).set_retry(self.retry)
     }
 
     pub fn vyper_with_abi(self, path: &'a Path, abi: PathBuf) -> Vyper<'a> {
-        Vyper::with_venv_and_abi(path, self.venv_path, abi)
+        Vyper::with_venv_and_abi(path, self.venv_path, abi).set_retry(self.retry)
     }
 
-    pub fn vypers_from_dir(self, path: PathBuf) -> Option<Vypers> {
-        let vyps = Vypers::in_dir(path);
-        vyps.map(|e| e.set_venv(self.venv_path.to_path_buf()))
+    pub fn vypers_from_dir(
+        self,
+        path: PathBuf,
+        allow_empty: bool,
+    ) -> Result<Vypers, VyperErrors> {
+        let vyps = Vypers::in_dir(path, allow_empty);
+        vyps.map(|e| {
+            e.set_venv(self.venv_path.to_path_buf())
+                .set_retry(self.retry)
+        })
     }
 
-    pub async fn vypers_from_workspace(self, path: PathBuf) -> Option<Vypers> {
-        let vyps = Vypers::in_workspace(path).await;
-        vyps.map(|e| e.set_venv(self.venv_path.to_path_buf()))
+    #[cfg(feature = "async")]
+    pub async fn vypers_from_workspace(
+        self,
+        path: PathBuf,
+        allow_empty: bool,
+    ) -> Result<Vypers, VyperErrors> {
+        let vyps = Vypers::in_workspace(path, allow_empty).await;
+        vyps.map(|e| {
+            e.set_venv(self.venv_path.to_path_buf())
+                .set_retry(self.retry)
+        })
     }
 }
@@ -0,0 +1,167 @@
+//! Verification of contracts deployed behind proxies. `resolve_implementation` reads the
+//! address a proxy delegates to from the storage slots standardized by EIP-1967 and EIP-1822,
+//! and `verify_implementation` compares the code at that address with a local build. Behind the
+//! `rpc` feature.
+
+use crate::{
+    bytecode::Bytecode,
+    utils::{self, StorageLayout},
+    vyper::{BytecodeDiff, Vyper},
+    vyper_errors::VyperErrors,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// `keccak256("eip1967.proxy.implementation") - 1`
+pub const EIP1967_IMPLEMENTATION_SLOT: &str =
+    "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+/// `keccak256("eip1967.proxy.beacon") - 1`
+pub const EIP1967_BEACON_SLOT: &str =
+    "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+/// `keccak256("PROXIABLE")`
+pub const EIP1822_PROXIABLE_SLOT: &str =
+    "0xc5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7";
+
+/// Selector of `implementation()`, which EIP-1967 beacons expose
+const IMPLEMENTATION_SELECTOR: &str = "0x5c60da1b";
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub enum ProxyKind {
+    /// The implementation is in the EIP-1967 implementation slot
+    Eip1967,
+    /// The EIP-1967 beacon slot holds a beacon, whose `implementation()` is the implementation
+    Eip1967Beacon,
+    /// A UUPS proxy, the implementation is in the EIP-1822 `PROXIABLE` slot
+    Eip1822,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Implementation {
+    pub kind: ProxyKind,
+    /// Lowercase hex with a `0x` prefix
+    pub address: String,
+    /// The beacon the address was read from, for `ProxyKind::Eip1967Beacon`
+    pub beacon: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ProxyVerification {
+    pub implementation: Implementation,
+    /// Where the deployed code diverges from the build, metadata and immutables excluded.
+    /// `None` when they match.
+    pub diff: Option<BytecodeDiff>,
+    /// Values of the implementation's immutables as deployed, hex encoded, see
+    /// `ImmutablesLayout`
+    pub immutables: BTreeMap<String, String>,
+}
+
+impl ProxyVerification {
+    pub fn matches(&self) -> bool {
+        self.diff.is_none()
+    }
+}
+
+/// The implementation the proxy at `proxy` delegates to, read from the node at `rpc_url`.
+/// The EIP-1967 implementation slot is checked first, then the EIP-1822 slot, then the
+/// EIP-1967 beacon slot. Fails with `NotAProxy` when all of them are empty.
+pub fn resolve_implementation(
+    rpc_url: &str,
+    proxy: &str,
+) -> Result<Implementation, VyperErrors> {
+    let calls: Vec<(&str, Value)> = [
+        EIP1967_IMPLEMENTATION_SLOT,
+        EIP1822_PROXIABLE_SLOT,
+        EIP1967_BEACON_SLOT,
+    ]
+    .iter()
+    .map(|slot| ("eth_getStorageAt", json!([proxy, slot, "latest"])))
+    .collect();
+    let slots = utils::rpc_batch(rpc_url, &calls)?
+        .into_iter()
+        .map(|result| address_in(&result?))
+        .collect::<Result<Vec<Option<String>>, VyperErrors>>()?;
+    let implementation = |kind, address: &String| Implementation {
+        kind,
+        address: address.clone(),
+        beacon: None,
+    };
+    if let Some(address) = &slots[0] {
+        return Ok(implementation(ProxyKind::Eip1967, address));
+    }
+    if let Some(address) = &slots[1] {
+        return Ok(implementation(ProxyKind::Eip1822, address));
+    }
+    let Some(beacon) = slots[2].clone() else {
+        Err(VyperErrors::NotAProxy(proxy.to_owned()))?
+    };
+    let call = json!([{ "to": beacon, "data": IMPLEMENTATION_SELECTOR }, "latest"]);
+    let result = call_one(rpc_url, "eth_call", call)?;
+    let address = address_in(&result)?.ok_or_else(|| {
+        VyperErrors::RpcError(format!("Beacon {} has no implementation", beacon))
+    })?;
+    Ok(Implementation {
+        beacon: Some(beacon),
+        ..implementation(ProxyKind::Eip1967Beacon, &address)
+    })
+}
+
+/// Resolves the implementation behind `proxy` and compares its code with a build of
+/// `contract`. The metadata of both and the immutables data section of the deployed code are
+/// left out of the comparison.
+pub fn verify_implementation(
+    rpc_url: &str,
+    proxy: &str,
+    contract: &Vyper,
+) -> Result<ProxyVerification, VyperErrors> {
+    let implementation = resolve_implementation(rpc_url, proxy)?;
+    let code = call_one(
+        rpc_url,
+        "eth_getCode",
+        json!([implementation.address, "latest"]),
+    )?;
+    let deployed: Bytecode = code.as_str().ok_or_else(|| unexpected(&code))?.parse()?;
+    let compiled = contract.build()?;
+    let runtime: Bytecode = compiled.runtime_bytecode.parse()?;
+    let layout =
+        serde_json::from_value::<StorageLayout>(compiled.storage_layout)?.immutables();
+    let (deployed_runtime, _) = layout.split(&deployed).unwrap_or((&deployed, &[]));
+    let immutables = layout
+        .immutables
+        .keys()
+        .filter_map(|name| {
+            let value = layout.value(name, &deployed)?;
+            Some((name.clone(), format!("0x{}", hex::encode(value))))
+        })
+        .collect();
+    Ok(ProxyVerification {
+        implementation,
+        diff: BytecodeDiff::new(
+            runtime.without_metadata(),
+            utils::strip_metadata(deployed_runtime),
+        ),
+        immutables,
+    })
+}
+
+fn call_one(rpc_url: &str, method: &str, params: Value) -> Result<Value, VyperErrors> {
+    utils::rpc_batch(rpc_url, &[(method, params)])?
+        .pop()
+        .ok_or_else(|| VyperErrors::RpcError("Empty response".to_owned()))?
+}
+
+/// The address in the low 20 bytes of a word returned by the node, `None` when it's zero
+fn address_in(word: &Value) -> Result<Option<String>, VyperErrors> {
+    let bytes: Bytecode = word.as_str().ok_or_else(|| unexpected(word))?.parse()?;
+    let address = &bytes[bytes.len().saturating_sub(20)..];
+    Ok(address
+        .iter()
+        .any(|b| *b != 0)
+        .then(|| format!("0x{:0>40}", hex::encode(address))))
+}
+
+fn unexpected(result: &Value) -> VyperErrors {
+    VyperErrors::RpcError(format!("Unexpected result {}", result))
+}
@@ -1,31 +1,74 @@
 //! This is the main module of the crate. Uses the global installation of Vyper.
 
 use crate::{
-    utils::{self, get_contracts_in_dir},
+    abi::{Abi, SelectorReport},
+    artifacts::{
+        self, Artifact, BoaExport, BoaSettings, CompiledContract, Manifest,
+        OverwritePolicy, PythonBuild, BUILD_FORMATS,
+    },
+    assembly::Assembly,
+    backend::{Backend, CompilerBackend},
+    bytecode::Bytecode,
+    code_size::CodeSizeReport,
+    diagnostics::Diagnostic,
+    dispatch::DispatchReport,
+    lints::{self, LintFinding, Lints},
+    process::{self, CommandExt, RetryPolicy},
+    utils::{
+        self, find_contracts_within, Blueprint, DiscoveryOptions, HashAlgorithm,
+        ImmutablesLayout, SourceUnit, StorageLayout,
+    },
+    venv::{self, InstalledCompiler, VyperProvider},
     vyper_errors::VyperErrors,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::{to_writer_pretty, Value};
+use serde_json::Value;
+#[cfg(feature = "async")]
+use std::time::Instant;
 use std::{
     borrow::BorrowMut,
+    collections::{BTreeMap, HashMap},
     fmt::Display,
-    fs::File,
-    io::{BufWriter, Write},
+    fs::{self, File},
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, Output},
     sync::Arc,
     thread,
+    time::Duration,
 };
+#[cfg(feature = "async")]
 use tokio::task::JoinHandle;
+#[cfg(feature = "async")]
+use tokio_util::sync::CancellationToken;
 
 /// Represents important information about a Vyper contract. ABI doesn't need to point to an
 /// existing file since it can just be generated using `gen_abi()`. If the ABI already exists at the given path, you can use serde_json to retrieve it from a file.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Vyper<'a> {
     pub path_to_code: &'a Path,
-    pub bytecode: Option<String>,
+    pub bytecode: Option<Bytecode>,
     pub abi: PathBuf,
     pub venv: Option<&'a Path>,
+    pub compiler: Option<&'a Path>,
+    /// Compiler invocations running longer than this are killed
+    pub timeout: Option<Duration>,
+    /// Retries compiler invocations that couldn't be run or timed out
+    pub retry: RetryPolicy,
+    /// Where the compiler runs
+    pub backend: Backend,
+    /// EVM version every compilation targets with `--evm-version`, set with `VyperBuilder::evm`
+    /// or by `compile_ver`, `None` for the compiler's default. See `effective_evm_version`.
+    pub evm_version: Option<Evm>,
+    /// Optimization mode passed with `--optimize`, `None` for the compiler's default
+    pub optimize: Option<Optimize>,
+    /// Lines the compiler printed besides the bytecode on the last compilation, such as
+    /// warnings
+    pub diagnostics: Vec<String>,
+    /// What the methods writing outputs to files do when the file exists
+    pub overwrite: OverwritePolicy,
+    /// Record the files written for the contract in the manifest of the current directory,
+    /// for `clean_outputs`
+    pub track_outputs: bool,
 }
 
 impl<'a> Display for Vyper<'a> {
@@ -47,35 +90,86 @@ impl<'a> Vyper<'a> {
             bytecode: None,
             abi: np,
             venv: None,
+            compiler: None,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            backend: Backend::default(),
+            evm_version: None,
+            optimize: None,
+            diagnostics: Vec::new(),
+            overwrite: OverwritePolicy::default(),
+            track_outputs: false,
         }
     }
 
-    pub fn with_abi(root: &'a Path, abi_path: PathBuf) -> Self {
-        Self {
-            path_to_code: root,
-            bytecode: None,
-            abi: abi_path,
-            venv: None,
+    /// Starts a `VyperBuilder` for the contract at `path`, the one entry point to every setting
+    /// of a contract
+    pub fn builder<P: AsRef<Path> + ?Sized>(path: &'a P) -> VyperBuilder<'a> {
+        VyperBuilder {
+            vyper: Vyper::new(path.as_ref()),
         }
     }
 
-    pub fn with_venv(path: &'a Path, venv: &'a Path) -> Vyper<'a> {
-        let abi = path.with_extension("json");
+    pub fn with_abi(root: &'a Path, abi_path: PathBuf) -> Self {
+        Vyper::builder(root).abi(abi_path).build()
+    }
 
-        Vyper {
-            path_to_code: path,
-            bytecode: None,
-            abi,
-            venv: Some(venv),
-        }
+    pub fn with_venv(path: &'a Path, venv: &'a Path) -> Vyper<'a> {
+        Vyper::builder(path).venv(venv).build()
     }
 
     pub fn with_venv_and_abi(path: &'a Path, venv: &'a Path, abi: PathBuf) -> Vyper<'a> {
-        Vyper {
-            path_to_code: path,
-            bytecode: None,
-            abi,
-            venv: Some(venv),
+        Vyper::builder(path).venv(venv).abi(abi).build()
+    }
+
+    /// Uses the compiler binary at `compiler` instead of looking one up in a venv or PATH
+    pub fn with_compiler(path: &'a Path, compiler: &'a Path) -> Vyper<'a> {
+        Vyper::builder(path).compiler(compiler).build()
+    }
+
+    /// Kills any compiler invocation that runs for longer than `timeout`
+    pub fn set_timeout(mut self, timeout: Duration) -> Vyper<'a> {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries compiler invocations that couldn't be run or timed out
+    pub fn set_retry(mut self, retry: RetryPolicy) -> Vyper<'a> {
+        self.retry = retry;
+        self
+    }
+
+    pub fn set_backend(mut self, backend: Backend) -> Vyper<'a> {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets what the methods writing outputs to files do when the file exists
+    pub fn set_overwrite(mut self, overwrite: OverwritePolicy) -> Vyper<'a> {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Records the files written for the contract in the manifest of the current directory,
+    /// so `clean_outputs` can delete them
+    pub fn set_track_outputs(mut self, track_outputs: bool) -> Vyper<'a> {
+        self.track_outputs = track_outputs;
+        self
+    }
+
+    /// Compiles with `--optimize`, i.e. for the smallest bytecode with `Optimize::Codesize`
+    pub fn set_optimize(mut self, optimize: Optimize) -> Vyper<'a> {
+        self.optimize = Some(optimize);
+        self
+    }
+
+    /// Appends the settings shared by every compilation to `cmd`
+    fn settings_args(&self, cmd: &mut Command) {
+        if let Some(evm_version) = &self.evm_version {
+            cmd.arg("--evm-version").arg(evm_version.to_string());
+        }
+        if let Some(optimize) = &self.optimize {
+            cmd.arg("--optimize").arg(optimize.to_string());
         }
     }
 
@@ -91,37 +185,59 @@ impl<'a> Vyper<'a> {
         self.path_to_code.exists()
     }
 
+    /// Hex digest with `algorithm` of the contract's source, the modules it imports (resolved
+    /// from the current directory), the compiler version and the settings, to content address
+    /// the contract's outputs. Asks the compiler for its version.
+    pub fn hash_source(&self, algorithm: HashAlgorithm) -> Result<String, VyperErrors> {
+        let compiler = self.get_version()?.trim().to_owned();
+        let evm_version = self
+            .evm_version
+            .clone()
+            .or_else(|| Some(Evm::default_for(&utils::parse_version(&compiler).ok()?)));
+        crate::project::content_hash(
+            algorithm,
+            self.path_to_code,
+            Path::new("."),
+            &compiler,
+            evm_version.as_ref().map(Evm::as_str),
+            self.optimize.map(|o| o.to_string()).as_deref(),
+        )
+    }
+
+    /// Path to the compiler binary. In order of precedence: the compiler set with
+    /// `with_compiler`, the venv, the `VYPER_PATH` environment variable, then `vyper` in PATH.
     pub fn get_vyper(&self) -> String {
-        if let Some(venv) = self.venv {
-            if cfg!(target_os = "windows") {
-                format!("{}/scripts/vyper", venv.to_string_lossy())
-            } else {
-                format!("{}/bin/vyper", venv.to_string_lossy())
-            }
-        } else {
-            "vyper".to_owned()
-        }
+        vyper_bin(self.compiler, self.venv)
+    }
+
+    /// The backend every compiler invocation for this contract goes through
+    pub(crate) fn resolve_compiler(&self) -> Arc<dyn CompilerBackend> {
+        self.backend.resolve(self.get_vyper())
     }
 
     pub fn get_pip(&self) -> String {
         if let Some(venv) = self.venv {
-            if cfg!(target_os = "windows") {
-                format!("{}/scripts/pip3", venv.to_string_lossy())
-            } else {
-                format!("{}/bin/pip3", venv.to_string_lossy())
-            }
+            venv::executable(venv, "pip3").to_string_lossy().to_string()
         } else {
             "pip3".to_owned()
         }
     }
 
     pub fn exists(&self) -> bool {
-        Command::new(self.get_vyper()).arg("-h").output().is_ok()
+        self.resolve_compiler()
+            .command()
+            .arg("-h")
+            .output_timeout(self.timeout)
+            .is_ok()
     }
 
     /// check the version of the vyper compiler
     pub fn get_version(&self) -> Result<String, VyperErrors> {
-        let out = Command::new(self.get_vyper()).arg("--version").output()?;
+        let out = self
+            .resolve_compiler()
+            .command()
+            .arg("--version")
+            .output_retry(self.timeout, &self.retry)?;
         if !out.status.success() {
             Err(VyperErrors::CompilerError(
                 "Couldn't locate version info, installation does not exist".to_string(),
@@ -130,110 +246,259 @@ impl<'a> Vyper<'a> {
         Ok(String::from_utf8_lossy(&out.stdout).to_string())
     }
 
-    /// Compiles a vyper contract by invoking the vyper compiler, updates the ABI field in the Vyper struct
-    pub fn compile(&mut self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg(self.path_to_code)
-            .output()?;
-        if compiler_output.status.success() {
-            let mut out = String::from_utf8_lossy(&compiler_output.stdout).to_string();
-            for _ in 0..1 {
-                out.pop();
-            }
-            if !out.starts_with("0x") {
-                self.bytecode = out.split(":").last().map(|s| s.to_owned());
-            } else {
-                self.bytecode = Some(out);
+    /// The EVM version the compiler targets when none is passed, based on its version
+    pub fn default_evm_version(&self) -> Result<Evm, VyperErrors> {
+        let version = utils::parse_version(&self.get_version()?)?;
+        Ok(Evm::default_for(&version))
+    }
+
+    /// The EVM version `bytecode` targets: `evm_version`, as set by the builder or the last
+    /// `compile_ver`, or else the compiler's default
+    pub fn effective_evm_version(&self) -> Result<Evm, VyperErrors> {
+        match &self.evm_version {
+            Some(evm) => Ok(evm.clone()),
+            None => self.default_evm_version(),
+        }
+    }
+
+    /// Fails with `UnsupportedEvm` when the compiler is known not to support `evm`. A compiler
+    /// whose version can't be read is given the benefit of the doubt.
+    fn check_evm(&self, evm: &Evm) -> Result<(), VyperErrors> {
+        let compiler = self
+            .get_version()
+            .ok()
+            .and_then(|version| utils::parse_version(&version).ok());
+        match compiler {
+            Some(compiler) if !evm.supported_by(&compiler) => {
+                Err(VyperErrors::UnsupportedEvm {
+                    evm: evm.clone(),
+                    compiler,
+                })
             }
+            _ => Ok(()),
+        }
+    }
 
+    /// Error for an unsuccessful invocation of `cmd` on this contract, with the command line and
+    /// compiler version attached
+    fn compilation_failed(&self, cmd: &Command, output: &Output) -> VyperErrors {
+        VyperErrors::CompilationFailed {
+            contract: self.path_to_code.to_path_buf(),
+            command: process::command_line(cmd),
+            version: self.get_version().ok().map(|v| v.trim().to_owned()),
+            exit: (&output.status).into(),
+            stdout: String::from_utf8_lossy(&output.stdout).into(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        }
+    }
+
+    /// Stores the bytecode and diagnostics of a bytecode compilation's `stdout`
+    fn set_bytecode_output(&mut self, stdout: &[u8]) -> Result<(), VyperErrors> {
+        let output = utils::parse_bytecode_output(&String::from_utf8_lossy(stdout))?;
+        self.bytecode = Some(output.bytecode);
+        self.diagnostics = output.diagnostics;
+        Ok(())
+    }
+
+    /// Compiles a vyper contract by invoking the vyper compiler, updates the ABI field in the Vyper struct.
+    /// Targets `evm_version` and `optimize` when set.
+    pub fn compile(&mut self) -> Result<(), VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg(self.path_to_code);
+        self.settings_args(&mut cmd);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
+        if compiler_output.status.success() {
+            self.set_bytecode_output(&compiler_output.stdout)?;
             Ok(())
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
+            Err(unsupported_evm(
+                self.evm_version.as_ref(),
+                self.compilation_failed(&cmd, &compiler_output),
             ))?
         }
     }
 
     pub fn compile_blueprint(&mut self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f")
             .arg("blueprint_bytecode")
-            .arg(self.path_to_code)
-            .output()?;
+            .arg(self.path_to_code);
+        self.settings_args(&mut cmd);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let mut out = String::from_utf8_lossy(&compiler_output.stdout).to_string();
-            for _ in 0..1 {
-                out.pop();
-            }
-            if !out.starts_with("0x") {
-                self.bytecode = out.split(":").last().map(|s| s.to_owned());
-            } else {
-                self.bytecode = Some(out);
-            }
+            self.set_bytecode_output(&compiler_output.stdout)?;
             Ok(())
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
+            Err(unsupported_evm(
+                self.evm_version.as_ref(),
+                self.compilation_failed(&cmd, &compiler_output),
             ))?
         }
     }
 
-    /// Compiles a vyper contract by invoking the vyper compiler, arg for specifying the EVM version to compile to
-    pub fn compile_ver(&mut self, ver: &Evm) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg(self.path_to_code)
-            .arg("--evm-version")
-            .arg(ver.to_string())
-            .output()?;
+    /// Compiles the contract as an ERC-5202 blueprint and parses the result, see
+    /// `utils::parse_blueprint`
+    pub fn get_blueprint(&mut self) -> Result<Blueprint, VyperErrors> {
+        self.compile_blueprint()?;
+        utils::parse_blueprint(self.bytecode.as_deref().unwrap_or_default())
+    }
 
+    /// Compiles a vyper contract by invoking the vyper compiler, arg for specifying the EVM version to compile to.
+    /// Fails with `UnsupportedEvm` without compiling when the compiler is known not to support
+    /// `ver`, see `Evm::supported_by`.
+    pub fn compile_ver(&mut self, ver: &Evm) -> Result<(), VyperErrors> {
+        self.check_evm(ver)?;
+        let previous = self.evm_version.replace(ver.clone());
+        let compiled = self.compile();
+        if compiled.is_err() {
+            self.evm_version = previous;
+        }
+        compiled
+    }
+    /// Compiles the contract and generates its runtime bytecode, ABI, method identifiers,
+    /// storage layout and natspec in a single compiler invocation. Nothing is written to disk.
+    pub fn build(&self) -> Result<CompiledContract, VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg(BUILD_FORMATS).arg(self.path_to_code);
+        self.settings_args(&mut cmd);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let mut out = String::from_utf8_lossy(&compiler_output.stdout).to_string();
-            for _ in 0..1 {
-                out.pop();
-            }
-            if !out.starts_with("0x") {
-                self.bytecode = out.split(":").last().map(|s| s.to_owned());
-            } else {
-                self.bytecode = Some(out);
-            }
-            Ok(())
+            CompiledContract::from_output(
+                self.path_to_code,
+                &String::from_utf8_lossy(&compiler_output.stdout),
+            )
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
+            Err(unsupported_evm(
+                self.evm_version.as_ref(),
+                self.compilation_failed(&cmd, &compiler_output),
             ))?
         }
     }
-    /// Generates the ABI and creates a file @ the abi path specified in the Vyper struct
-    pub fn gen_abi(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("abi")
-            .arg(self.path_to_code)
-            .output()?;
 
-        if compiler_output.status.success() {
-            let json = serde_json::from_str::<Value>(&String::from_utf8_lossy(
-                &compiler_output.stdout,
-            ))?;
+    /// Everything known about the compiled contract. The ABI is read from the ABI path when it
+    /// was generated with `gen_abi`, and requested from the compiler otherwise.
+    pub fn artifact(&self) -> Result<Artifact, VyperErrors> {
+        let abi = if self.abi_exists() {
+            self.read_abi()?
+        } else {
+            self.get_abi()?
+        };
+        let compiler = self.get_version().ok().map(|v| v.trim().to_owned());
+        let evm_version = self.evm_version.clone().or_else(|| {
+            let version = utils::parse_version(compiler.as_deref()?).ok()?;
+            Some(Evm::default_for(&version))
+        });
+        let artifact = Artifact::new(
+            self.path_to_code.to_path_buf(),
+            self.abi.clone(),
+            self.bytecode.clone(),
+            abi,
+            compiler,
+            evm_version.map(|evm| evm.to_string()),
+        );
+        let artifact = match self.optimize {
+            Some(optimize) => artifact.with_optimize(optimize),
+            None => artifact,
+        };
+        // compilers before 0.3.10 have no metadata output
+        Ok(match self.metadata() {
+            Ok(metadata) => artifact.with_metadata(metadata),
+            Err(_) => artifact,
+        })
+    }
 
-            let file = File::create(&self.abi)?;
+    /// Writes `artifact()` to `path`. An existing file is handled according to `overwrite`,
+    /// returning the artifact it holds when it's kept.
+    pub fn write_artifact(&self, path: &Path) -> Result<Artifact, VyperErrors> {
+        if !self.overwrite.should_write(self.path_to_code, path)? {
+            return Artifact::read(path);
+        }
+        let artifact = self.artifact()?;
+        artifact.write(path)?;
+        self.record_outputs([path])?;
+        Ok(artifact)
+    }
 
-            to_writer_pretty(file, &json)?;
-            Ok(())
-        } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+    /// Records files written for this contract in the manifest of the current directory, with
+    /// `track_outputs`
+    fn record_outputs<'p>(
+        &self,
+        files: impl IntoIterator<Item = &'p Path>,
+    ) -> Result<(), VyperErrors> {
+        if !self.track_outputs {
+            return Ok(());
+        }
+        Manifest::record(Path::new("."), self.path_to_code, files)
+    }
+
+    /// Deletes every file generated for this contract (its ABI, artifacts and the
+    /// `storage_layout.json`, `ast.json`, ... outputs), as recorded in the manifest of the
+    /// current directory with `track_outputs`. Returns the deleted files.
+    pub fn clean_outputs(&self) -> Result<Vec<PathBuf>, VyperErrors> {
+        Manifest::clean(Path::new("."), |contract| contract == self.path_to_code)
+    }
+
+    /// The contract described by `artifact`, with its bytecode and EVM version restored so it
+    /// can be deployed without compiling it again. The artifact's ABI is written to its
+    /// `abi_path` unless the file already holds it, so `read_abi` works without the compiler.
+    pub fn from_artifact(artifact: &'a Artifact) -> Result<Vyper<'a>, VyperErrors> {
+        let mut vyper = Vyper::with_abi(&artifact.contract, artifact.abi_path.clone());
+        vyper.bytecode = artifact.bytecode.clone();
+        vyper.evm_version = artifact.evm_version.as_deref().and_then(|e| e.parse().ok());
+        if !artifact.abi.is_null()
+            && vyper.read_abi().ok().as_ref() != Some(&artifact.abi)
+        {
+            artifacts::write_output(
+                &vyper.abi,
+                &serde_json::to_vec_pretty(&artifact.abi)?,
+            )?;
         }
+        Ok(vyper)
+    }
+
+    /// Generates the ABI and creates a file @ the abi path specified in the Vyper struct
+    pub fn gen_abi(&self) -> Result<(), VyperErrors> {
+        self.write_format("abi", &self.abi, true)
+    }
+
+    /// Writes output `format` of the contract to `output`, pretty printed when it's `json`.
+    /// Parent directories are created, and an existing file is handled according to
+    /// `overwrite`.
+    fn write_format(
+        &self,
+        format: &str,
+        output: &Path,
+        json: bool,
+    ) -> Result<(), VyperErrors> {
+        if !self.overwrite.should_write(self.path_to_code, output)? {
+            return Ok(());
+        }
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg(format).arg(self.path_to_code);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
+        if !compiler_output.status.success() {
+            Err(self.compilation_failed(&cmd, &compiler_output))?
+        }
+        let contents = if json {
+            let json: Value = serde_json::from_slice(&compiler_output.stdout)?;
+            serde_json::to_vec_pretty(&json)?
+        } else {
+            compiler_output.stdout
+        };
+        artifacts::write_output(output, &contents)?;
+        self.record_outputs([output])
+    }
+
+    /// Reads the ABI previously written to the ABI path by `gen_abi`
+    pub fn read_abi(&self) -> Result<Value, VyperErrors> {
+        Ok(serde_json::from_reader(File::open(&self.abi)?)?)
     }
 
     /// Generates the ABI and creates a file @ the abi path specified in the Vyper struct
     pub fn get_abi(&self) -> Result<Value, VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("abi")
-            .arg(self.path_to_code)
-            .output()?;
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg("abi").arg(self.path_to_code);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
 
         if compiler_output.status.success() {
             let json = serde_json::from_str::<Value>(&String::from_utf8_lossy(
@@ -241,143 +506,363 @@ impl<'a> Vyper<'a> {
             ))?;
             Ok(json)
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+            Err(self.compilation_failed(&cmd, &compiler_output))?
         }
     }
 
+    /// The ABI in the compiler's `abi_python` format, a python literal for python tooling
+    pub fn abi_python(&self) -> Result<String, VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg("abi_python").arg(self.path_to_code);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
+        if compiler_output.status.success() {
+            Ok(String::from_utf8_lossy(&compiler_output.stdout).to_string())
+        } else {
+            Err(self.compilation_failed(&cmd, &compiler_output))?
+        }
+    }
+
+    /// Builds the contract and writes it to the build folder `layout` of the python project
+    /// at `root`, so Ape or Brownie can load it without compiling it again. Returns the
+    /// written file. An existing file is handled according to `overwrite`.
+    pub fn write_python_build(
+        &self,
+        root: &Path,
+        layout: PythonBuild,
+    ) -> Result<PathBuf, VyperErrors> {
+        let name = utils::contract_name(self.path_to_code).unwrap_or_default();
+        let path = layout.path(root, &name);
+        if !self.overwrite.should_write(self.path_to_code, &path)? {
+            return Ok(path);
+        }
+        let compiled = self.build()?;
+        let path = layout.write(root, &compiled, &self.get_version()?)?;
+        // the Ape manifest is shared with the project's other contracts
+        if layout == PythonBuild::Brownie {
+            self.record_outputs([path.as_path()])?;
+        }
+        Ok(path)
+    }
+
+    /// Builds the contract into the layout titanoboa loads contracts from, see `BoaExport`
+    pub fn boa_export(&self) -> Result<BoaExport, VyperErrors> {
+        let settings = BoaSettings {
+            evm_version: self.evm_version.as_ref().map(Evm::to_string),
+            optimize: self.optimize,
+        };
+        BoaExport::new(self.build()?, &self.get_version()?, settings)
+    }
+
+    /// Writes `boa_export()` to `path`. An existing file is handled according to `overwrite`,
+    /// returning the export it holds when it's kept.
+    pub fn write_boa_export(&self, path: &Path) -> Result<BoaExport, VyperErrors> {
+        if !self.overwrite.should_write(self.path_to_code, path)? {
+            return BoaExport::read(path);
+        }
+        let export = self.boa_export()?;
+        export.write(path)?;
+        self.record_outputs([path])?;
+        Ok(export)
+    }
+
     /// Storage layout as JSON, saves it to a file
     pub fn storage_layout(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("layout")
-            .arg(self.path_to_code.to_string_lossy().to_string())
-            .output()?;
-
+        self.write_format("layout", Path::new("./storage_layout.json"), true)
+    }
+    /// Storage layout of the contract, without saving it to a file
+    pub fn get_layout(&self) -> Result<StorageLayout, VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg("layout").arg(self.path_to_code);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let json = serde_json::from_str::<Value>(&String::from_utf8_lossy(
-                &compiler_output.stdout,
-            ))?;
-            let file = File::create("./storage_layout.json")?;
-            to_writer_pretty(file, &json)?;
-            Ok(())
+            Ok(serde_json::from_slice(&compiler_output.stdout)?)
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+            Err(self.compilation_failed(&cmd, &compiler_output))?
         }
     }
+    /// Offset and size of every immutable in the data section appended to the runtime code
+    /// on deployment, to account for it when comparing deployed code with a build
+    pub fn immutables_layout(&self) -> Result<ImmutablesLayout, VyperErrors> {
+        Ok(self.get_layout()?.immutables())
+    }
     /// AST of your contract as JSON, saves it to a file
     pub fn ast(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("ast")
-            .arg(self.path_to_code.to_string_lossy().to_string())
-            .output()?;
-
+        self.write_format("ast", Path::new("./ast.json"), true)
+    }
+    /// Generates an external interface for your vyper contract to be called with
+    pub fn interface(&self) -> Result<(), VyperErrors> {
+        self.write_format("external_interface", Path::new("./interface.vy"), false)
+    }
+    /// Generates the opcodes produced by your vyper contract, saves it as a text file
+    pub fn opcodes(&self) -> Result<(), VyperErrors> {
+        self.write_format("opcodes", Path::new("./opcodes.txt"), false)
+    }
+    /// Generates the opcodes produced by your vyper contract at runtime, saves it as a text file
+    pub fn opcodes_runtime(&self) -> Result<(), VyperErrors> {
+        self.write_format("opcodes_runtime", Path::new("./opcodes_runtime.txt"), false)
+    }
+    /// AST of the contract as JSON, without saving it to a file
+    pub fn get_ast(&self) -> Result<Value, VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg("ast").arg(self.path_to_code);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let json = serde_json::from_str::<Value>(&String::from_utf8_lossy(
-                &compiler_output.stdout,
-            ))?;
-            let file: File = File::create("./ast.json")?;
-            to_writer_pretty(file, &json)?;
-            Ok(())
+            Ok(serde_json::from_slice(&compiler_output.stdout)?)
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+            Err(self.compilation_failed(&cmd, &compiler_output))?
         }
     }
-    /// Generates an external interface for your vyper contract to be called with
-    pub fn interface(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("external_interface")
-            .arg(self.path_to_code.to_string_lossy().to_string())
-            .output()?;
+    /// The compiler's metadata of the contract (`-f metadata`): settings, source hashes and
+    /// more, as used by verification and provenance tools
+    pub fn metadata(&self) -> Result<Value, VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg("metadata").arg(self.path_to_code);
+        self.settings_args(&mut cmd);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let mut buffer = BufWriter::new(File::create("./interface.vy")?);
-            buffer.write_all(&compiler_output.stdout)?;
-            Ok(())
+            Ok(serde_json::from_slice(&compiler_output.stdout)?)
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+            Err(self.compilation_failed(&cmd, &compiler_output))?
         }
     }
-    /// Generates the opcodes produced by your vyper contract, saves it as a text file
-    pub fn opcodes(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("opcodes")
-            .arg(self.path_to_code.to_string_lossy().to_string())
-            .output()?;
-
+    /// Runtime bytecode of the contract and the compiler's source map for it (`-f source_map`),
+    /// from a single compiler invocation, see `coverage::CoverageMap`
+    pub fn runtime_source_map(&self) -> Result<(Bytecode, Value), VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f")
+            .arg("bytecode_runtime,source_map")
+            .arg(self.path_to_code);
+        self.settings_args(&mut cmd);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
+        if !compiler_output.status.success() {
+            Err(self.compilation_failed(&cmd, &compiler_output))?
+        }
+        let stdout = String::from_utf8_lossy(&compiler_output.stdout);
+        let mut lines = stdout.lines().map(str::trim).filter(|l| !l.is_empty());
+        let mut next = || lines.next().ok_or(VyperErrors::StringParsingError);
+        let runtime = next()?.parse()?;
+        let source_map = serde_json::from_str(next()?)?;
+        Ok((runtime, source_map))
+    }
+    /// Assembly of the contract (`-f asm`), split into constructor, runtime and data, see the
+    /// `assembly` module
+    pub fn assembly(&self) -> Result<Assembly, VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg("asm").arg(self.path_to_code);
+        self.settings_args(&mut cmd);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let mut buffer = BufWriter::new(File::create("./opcodes.txt")?);
-            buffer.write_all(&compiler_output.stdout)?;
-            Ok(())
+            Ok(Assembly::parse(&String::from_utf8_lossy(
+                &compiler_output.stdout,
+            )))
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+            Err(self.compilation_failed(&cmd, &compiler_output))?
         }
     }
-    /// Generates the opcodes produced by your vyper contract at runtime, saves it as a text file
-    pub fn opcodes_runtime(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("opcodes_runtime")
-            .arg(self.path_to_code.to_string_lossy().to_string())
-            .output()?;
-
+    /// Attributes the runtime bytecode to the external functions and flags unreachable code,
+    /// see the `code_size` module
+    pub fn code_size_report(&self) -> Result<CodeSizeReport, VyperErrors> {
+        let compiled = self.build()?;
+        let runtime: Bytecode = compiled.runtime_bytecode.parse()?;
+        Ok(CodeSizeReport::new(&runtime, &compiled.method_identifiers))
+    }
+    /// The order the dispatcher checks the selectors in and the gas spent reaching and
+    /// decoding the arguments of every external function, see the `dispatch` module
+    pub fn estimate_selector_gas_overhead(&self) -> Result<DispatchReport, VyperErrors> {
+        let compiled = self.build()?;
+        let runtime: Bytecode = compiled.runtime_bytecode.parse()?;
+        let abi = Abi::try_from(compiled.abi)?;
+        Ok(DispatchReport::new(
+            &runtime,
+            &compiled.method_identifiers,
+            &abi,
+        ))
+    }
+    /// Runtime opcodes of the contract, without saving them to a file
+    pub fn get_opcodes_runtime(&self) -> Result<String, VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.arg("-f").arg("opcodes_runtime").arg(self.path_to_code);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let mut buffer = BufWriter::new(File::create("./opcodes_runtime.txt")?);
-            buffer.write_all(&compiler_output.stdout)?;
-            Ok(())
+            Ok(String::from_utf8_lossy(&compiler_output.stdout).to_string())
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+            Err(self.compilation_failed(&cmd, &compiler_output))?
         }
     }
+    /// Estimates the gas of every external function by simulating the contract with revm, see
+    /// the `gas` module
+    #[cfg(feature = "revm")]
+    pub fn gas_report(&self) -> Result<crate::gas::GasReport, VyperErrors> {
+        crate::gas::GasReport::simulate(&self.build()?)
+    }
+    /// Runs every safety lint over the contract, see the `lints` module
+    pub fn lint(&self) -> Result<Vec<LintFinding>, VyperErrors> {
+        self.lint_with(&Lints::default())
+    }
+    /// Runs the safety lints enabled in `lints` over the contract
+    pub fn lint_with(&self, lints: &Lints) -> Result<Vec<LintFinding>, VyperErrors> {
+        let mut findings = Vec::new();
+        if lints.needs_ast() {
+            findings.extend(lints::lint_ast(self.path_to_code, &self.get_ast()?, lints));
+        }
+        if lints.needs_opcodes() {
+            findings.extend(lints::lint_opcodes(
+                self.path_to_code,
+                &self.get_opcodes_runtime()?,
+                lints,
+            ));
+        }
+        Ok(findings)
+    }
     /// Natspec user documentation for vyper contract
     pub fn userdoc(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("userdoc")
-            .arg(self.path_to_code.to_string_lossy().to_string())
-            .output()?;
+        self.write_format("userdoc", Path::new("./userdoc.txt"), false)
+    }
+    /// Natspec dev documentation for vyper contract
+    pub fn devdoc(&self) -> Result<(), VyperErrors> {
+        self.write_format("devdoc", Path::new("./devdoc.txt"), false)
+    }
+    /// Compiles output `format` (e.g. `asm`, `ir`, `bytecode`) of the contract straight to
+    /// `output` with the compiler's `-o` flag, so large outputs aren't buffered in memory.
+    /// Backends that can't write here, such as a bubblewrap `Sandbox`, have their stdout
+    /// written instead. Parent directories of `output` are created, and an existing file is
+    /// handled according to `overwrite`.
+    pub fn compile_to_file(
+        &self,
+        format: &str,
+        output: &Path,
+    ) -> Result<(), VyperErrors> {
+        if !self.overwrite.should_write(self.path_to_code, output)? {
+            return Ok(());
+        }
+        if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let compiler = self.resolve_compiler();
+        let mut cmd = compiler.command();
+        cmd.arg("-f").arg(format);
+        if compiler.writes_files() {
+            cmd.arg("-o").arg(output);
+        }
+        cmd.arg(self.path_to_code);
+        self.settings_args(&mut cmd);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let mut buffer = BufWriter::new(File::create("./userdoc.txt")?);
-            buffer.write_all(&compiler_output.stdout)?;
-            Ok(())
+            if !compiler.writes_files() {
+                artifacts::write_output(output, &compiler_output.stdout)?;
+            }
+            self.record_outputs([output])
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
+            Err(unsupported_evm(
+                self.evm_version.as_ref(),
+                self.compilation_failed(&cmd, &compiler_output),
             ))?
         }
     }
-    /// Natspec dev documentation for vyper contract
-    pub fn devdoc(&self) -> Result<(), VyperErrors> {
-        let compiler_output = Command::new(self.get_vyper())
-            .arg("-f")
-            .arg("devdoc")
-            .arg(self.path_to_code.to_string_lossy().to_string())
-            .output()?;
+
+    /// Runs the compiler's front end on the contract without generating bytecode, surfacing
+    /// syntax and type errors as quickly as possible. Nothing is written to disk.
+    pub fn check(&self) -> Result<(), VyperErrors> {
+        let mut cmd = self.resolve_compiler().command();
+        cmd.args(CHECK_ARGS).arg(self.path_to_code);
+        let compiler_output = cmd.output_retry(self.timeout, &self.retry)?;
         if compiler_output.status.success() {
-            let mut buffer = BufWriter::new(File::create("./devdoc.txt")?);
-            buffer.write_all(&compiler_output.stdout)?;
             Ok(())
         } else {
-            Err(VyperErrors::CompilerError(
-                String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-            ))?
+            Err(self.compilation_failed(&cmd, &compiler_output))?
+        }
+    }
+
+    /// Compiles the contract with a compiler matching each of `reqs`, for validating a
+    /// compiler upgrade. Each compiler is provisioned in its own venv with `Venv::ensure_venv`,
+    /// reusing a matching venv in `dir` or else installing one at `dir/vyper-<req>`, never
+    /// the global compiler. Every result is compared to the first one that compiled.
+    pub fn compile_with_versions(
+        &self,
+        dir: &Path,
+        reqs: &[VersionReq],
+    ) -> Vec<CompilationResult> {
+        let installed = venv::Venv::list(dir).unwrap_or_default();
+        let mut results: Vec<CompilationResult> = reqs
+            .iter()
+            .map(|req| self.compile_with_version(dir, &installed, req))
+            .collect();
+        let Some(baseline) = results.iter().position(|r| r.bytecode.is_ok()) else {
+            return results;
+        };
+        let (before, after) = results.split_at_mut(baseline + 1);
+        for result in after {
+            result.compare(&before[baseline]);
+        }
+        results
+    }
+
+    fn compile_with_version(
+        &self,
+        dir: &Path,
+        installed: &[InstalledCompiler],
+        req: &VersionReq,
+    ) -> CompilationResult {
+        let venv_path = installed
+            .iter()
+            .filter_map(|c| Some((utils::parse_version(&c.version).ok()?, &c.venv)))
+            .filter(|(version, _)| req.matches(version))
+            .max()
+            .map_or_else(|| dir.join(venv_dir_name(req)), |(_, venv)| venv.clone());
+        let compiled = venv::Venv::new(&venv_path)
+            .with_retry(self.retry)
+            .ensure_venv(req)
+            .and_then(|venv| {
+                let mut vyper = venv.contract(self.path_to_code);
+                vyper.timeout = self.timeout;
+                vyper.evm_version = self.evm_version.clone();
+                vyper.optimize = self.optimize;
+                let version = vyper.get_version()?;
+                vyper.compile()?;
+                Ok((
+                    version,
+                    vyper.bytecode.unwrap_or_default(),
+                    vyper.diagnostics,
+                ))
+            });
+        let (version, bytecode, diagnostics) = match compiled {
+            Ok((version, bytecode, diagnostics)) => {
+                (Some(version.trim().to_owned()), Ok(bytecode), diagnostics)
+            }
+            Err(error) => (None, Err(error), Vec::new()),
+        };
+        CompilationResult {
+            req: req.clone(),
+            version,
+            bytecode,
+            diagnostics,
+            diff: None,
+            new_warnings: Vec::new(),
         }
     }
 }
 
+/// Name of the venv `Vyper::compile_with_versions` installs a compiler matching `req` into
+fn venv_dir_name(req: &VersionReq) -> String {
+    let req: String = req
+        .to_string()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("vyper-{}", req)
+}
+
+/// Output format requested by the check-only pass. Producing the ABI requires semantic analysis
+/// but no code generation, and it's supported by every compiler version.
+const CHECK_ARGS: [&str; 2] = ["-f", "abi"];
+
 /// Represents multiple vyper contract allocated on the stack, synchronous / blocking API for
 /// multiple compilations with scoped threads
 #[derive(Debug, Hash, Default, Eq, PartialEq, Ord, PartialOrd)]
@@ -424,15 +909,138 @@ impl<'a> VyperStack<'a> {
     }
 }
 
+/// Builds a `Vyper`, see `Vyper::builder`. Settings that aren't set keep the defaults of
+/// `Vyper::new`.
+///
+/// ```rust
+/// use vyper_rs::vyper::{Evm, Optimize, Vyper};
+///
+/// let contract = Vyper::builder("./multisig.vy")
+///     .abi("./out/abi.json")
+///     .venv("./venv")
+///     .evm(Evm::Cancun)
+///     .optimize(Optimize::Codesize)
+///     .build();
+/// assert_eq!(contract.evm_version, Some(Evm::Cancun));
+/// ```
+#[derive(Debug, Eq, PartialEq)]
+pub struct VyperBuilder<'a> {
+    vyper: Vyper<'a>,
+}
+
+impl<'a> VyperBuilder<'a> {
+    /// Where the ABI is written to, `<contract>.json` by default
+    pub fn abi(mut self, abi: impl Into<PathBuf>) -> Self {
+        self.vyper.abi = abi.into();
+        self
+    }
+
+    pub fn venv<P: AsRef<Path> + ?Sized>(mut self, venv: &'a P) -> Self {
+        self.vyper.venv = Some(venv.as_ref());
+        self
+    }
+
+    /// See `Vyper::with_compiler`
+    pub fn compiler<P: AsRef<Path> + ?Sized>(mut self, compiler: &'a P) -> Self {
+        self.vyper.compiler = Some(compiler.as_ref());
+        self
+    }
+
+    pub fn evm(mut self, evm_version: Evm) -> Self {
+        self.vyper.evm_version = Some(evm_version);
+        self
+    }
+
+    pub fn optimize(mut self, optimize: Optimize) -> Self {
+        self.vyper.optimize = Some(optimize);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.vyper.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.vyper.retry = retry;
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.vyper.backend = backend;
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.vyper.overwrite = overwrite;
+        self
+    }
+
+    pub fn track_outputs(mut self, track_outputs: bool) -> Self {
+        self.vyper.track_outputs = track_outputs;
+        self
+    }
+
+    pub fn build(self) -> Vyper<'a> {
+        self.vyper
+    }
+}
+
 /// Represents multiple vyper contracts
 #[derive(
     Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Serialize, Deserialize,
 )]
 pub struct Vypers {
     pub path_to_code: Vec<PathBuf>,
-    pub bytecode: Option<Vec<String>>,
+    pub bytecode: Option<Vec<Bytecode>>,
     pub abi: Vec<PathBuf>,
     pub venv: Option<PathBuf>,
+    #[serde(default)]
+    pub compiler: Option<PathBuf>,
+    #[serde(default)]
+    pub timeout: Option<Duration>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub backend: Backend,
+    /// Optimization mode every compilation passes with `--optimize`, `None` for the
+    /// compiler's default
+    #[serde(default)]
+    pub optimize: Option<Optimize>,
+    /// Per contract overrides, keyed by the contract's path in `path_to_code`
+    #[serde(default)]
+    pub settings: BTreeMap<PathBuf, ContractSettings>,
+    /// What `gen_abi_many` and `Project::write_artifacts` do when an output file exists
+    #[serde(default)]
+    pub overwrite: OverwritePolicy,
+    /// Record the ABIs written by `gen_abi_many` in the manifest of the current directory, for
+    /// `Project::clean`
+    #[serde(default)]
+    pub track_outputs: bool,
+}
+
+/// What makes two compilations of `Vypers::compile_jobs` produce the same bytecode
+#[derive(Debug, Eq, PartialEq, Hash)]
+struct CompileJob {
+    path: PathBuf,
+    source_sha256: Option<String>,
+    evm_version: Option<String>,
+    compiler: Option<PathBuf>,
+    venv: Option<PathBuf>,
+}
+
+/// Settings of a single contract in `Vypers`, taking precedence over the ones passed to the
+/// bulk methods
+#[derive(
+    Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct ContractSettings {
+    /// EVM version to compile the contract for
+    pub evm_version: Option<Evm>,
+    /// Venv of the compiler for the contract, see `Vypers::assign_compilers`
+    pub venv: Option<PathBuf>,
+    /// Compiler binary for the contract, taking precedence over `venv`
+    pub compiler: Option<PathBuf>,
 }
 
 impl Vypers {
@@ -451,6 +1059,14 @@ impl Vypers {
             bytecode: None,
             abi: abi_paths,
             venv,
+            compiler: None,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            backend: Backend::default(),
+            optimize: None,
+            settings: BTreeMap::new(),
+            overwrite: OverwritePolicy::default(),
+            track_outputs: false,
         }
     }
 
@@ -461,23 +1077,135 @@ impl Vypers {
             bytecode: None,
             abi: np,
             venv: None,
+            compiler: None,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            backend: Backend::default(),
+            optimize: None,
+            settings: BTreeMap::new(),
+            overwrite: OverwritePolicy::default(),
+            track_outputs: false,
         }
     }
 
-    pub fn in_dir(path: PathBuf) -> Option<Vypers> {
-        if let Ok(contracts) = get_contracts_in_dir(path) {
-            Some(Vypers::new(contracts))
-        } else {
-            None
+    /// Collects `contracts`, keeping each one's ABI path, bytecode, EVM version, compiler and
+    /// venv. See `push` for how their settings are merged.
+    pub fn try_from_contracts(contracts: Vec<Vyper>) -> Result<Vypers, VyperErrors> {
+        let mut vypers = Vypers::new(vec![]);
+        for contract in contracts {
+            vypers.push(contract)?;
         }
+        Ok(vypers)
     }
 
-    pub async fn in_workspace(path: PathBuf) -> Option<Vypers> {
-        if let Ok(contracts) = utils::scan_workspace(path).await {
-            Some(Vypers::new(contracts))
+    /// Appends `contract`. Its compiler, venv and EVM version are kept as its own
+    /// `ContractSettings`, so a contract without them uses the shared ones of the `Vypers`
+    /// rather than another contract's. The first contract pushed sets the timeout, retry
+    /// policy, backend, optimization mode, overwrite policy and output tracking shared by all
+    /// of them. Fails with `ConflictingSettings` when a later contract sets a different one, or
+    /// when its path was pushed before with a different compiler, venv or EVM version.
+    pub fn push(&mut self, contract: Vyper) -> Result<(), VyperErrors> {
+        let path = contract.path_to_code.to_path_buf();
+        let own = ContractSettings {
+            evm_version: contract.evm_version,
+            venv: contract
+                .venv
+                .filter(|venv| self.venv.as_deref() != Some(*venv))
+                .map(Path::to_path_buf),
+            compiler: contract
+                .compiler
+                .filter(|compiler| self.compiler.as_deref() != Some(*compiler))
+                .map(Path::to_path_buf),
+        };
+        let conflict = |setting: &'static str| VyperErrors::ConflictingSettings {
+            path: path.clone(),
+            setting,
+        };
+        if self.path_to_code.contains(&path) {
+            let pushed = self.settings.get(&path).cloned().unwrap_or_default();
+            if pushed.evm_version != own.evm_version {
+                return Err(conflict("EVM version"));
+            }
+            if pushed.venv != own.venv {
+                return Err(conflict("venv"));
+            }
+            if pushed.compiler != own.compiler {
+                return Err(conflict("compiler"));
+            }
+        }
+        if self.path_to_code.is_empty() {
+            self.timeout = contract.timeout;
+            self.retry = contract.retry;
+            self.backend = contract.backend;
+            self.optimize = contract.optimize;
+            self.overwrite = contract.overwrite;
+            self.track_outputs = contract.track_outputs;
+            self.bytecode = contract.bytecode.map(|bytecode| vec![bytecode]);
         } else {
-            None
+            let shared = [
+                ("timeout", self.timeout != contract.timeout),
+                ("retry policy", self.retry != contract.retry),
+                ("backend", self.backend != contract.backend),
+                ("optimization mode", self.optimize != contract.optimize),
+                ("overwrite policy", self.overwrite != contract.overwrite),
+                (
+                    "output tracking",
+                    self.track_outputs != contract.track_outputs,
+                ),
+            ];
+            if let Some((setting, _)) = shared.into_iter().find(|(_, differs)| *differs) {
+                return Err(conflict(setting));
+            }
+            self.bytecode = match (self.bytecode.take(), contract.bytecode) {
+                (Some(mut all), Some(bytecode)) => {
+                    all.push(bytecode);
+                    Some(all)
+                }
+                _ => None,
+            };
+        }
+        if own != ContractSettings::default() {
+            self.settings.insert(path.clone(), own);
+        }
+        self.path_to_code.push(path);
+        self.abi.push(contract.abi);
+        Ok(())
+    }
+
+    /// Recursively collects every contract below `path`, skipping files ignored by git. Fails
+    /// with `NoContractsFound` when there are none, unless `allow_empty` is set.
+    pub fn in_dir(path: PathBuf, allow_empty: bool) -> Result<Vypers, VyperErrors> {
+        Vypers::in_dir_filtered(
+            path,
+            &DiscoveryOptions::default().allow_empty(allow_empty),
+        )
+    }
+
+    /// Like `in_dir`, collecting only the contracts selected by `options`
+    pub fn in_dir_filtered(
+        path: PathBuf,
+        options: &DiscoveryOptions,
+    ) -> Result<Vypers, VyperErrors> {
+        check_dir(&path)?;
+        let contracts = find_contracts_within(&path, &options.glob, options.max_depth)?;
+        if contracts.is_empty() && !options.allow_empty {
+            Err(VyperErrors::NoContractsFound(path))?
+        }
+        Ok(Vypers::new(contracts))
+    }
+
+    /// Collects every contract found by `utils::scan_workspace`, failing like `in_dir`
+    #[cfg(feature = "async")]
+    pub async fn in_workspace(
+        path: PathBuf,
+        allow_empty: bool,
+    ) -> Result<Vypers, VyperErrors> {
+        check_dir(&path)?;
+        let workspace = utils::scan_workspace(path.clone()).await?;
+        if workspace.contracts.is_empty() && !allow_empty {
+            Err(VyperErrors::NoContractsFound(path))?
         }
+        Ok(Vypers::new(workspace.contracts))
     }
 
     pub fn with_venv(paths: Vec<PathBuf>, venv: &Path) -> Self {
@@ -488,6 +1216,34 @@ impl Vypers {
             bytecode: None,
             abi: abis,
             venv: Some(venv.to_path_buf()),
+            compiler: None,
+            timeout: None,
+            retry: RetryPolicy::default(),
+            backend: Backend::default(),
+            optimize: None,
+            settings: BTreeMap::new(),
+            overwrite: OverwritePolicy::default(),
+            track_outputs: false,
+        }
+    }
+
+    /// Uses the compiler binary at `compiler` instead of looking one up in a venv or PATH
+    pub fn with_compiler(paths: Vec<PathBuf>, compiler: &Path) -> Self {
+        let abis = paths.iter().map(|e| e.with_extension("json")).collect();
+
+        Self {
+            path_to_code: paths,
+            bytecode: None,
+            abi: abis,
+            venv: None,
+            compiler: Some(compiler.to_path_buf()),
+            timeout: None,
+            retry: RetryPolicy::default(),
+            backend: Backend::default(),
+            optimize: None,
+            settings: BTreeMap::new(),
+            overwrite: OverwritePolicy::default(),
+            track_outputs: false,
         }
     }
 
@@ -495,148 +1251,447 @@ impl Vypers {
         self.venv = Some(venv);
         self
     }
+
+    pub fn set_compiler(mut self, compiler: PathBuf) -> Vypers {
+        self.compiler = Some(compiler);
+        self
+    }
+
+    /// Kills any compiler invocation that runs for longer than `timeout`
+    pub fn set_timeout(mut self, timeout: Duration) -> Vypers {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries compiler invocations that couldn't be run or timed out
+    pub fn set_retry(mut self, retry: RetryPolicy) -> Vypers {
+        self.retry = retry;
+        self
+    }
+
+    pub fn set_backend(mut self, backend: Backend) -> Vypers {
+        self.backend = backend;
+        self
+    }
+
+    /// Compiles every contract with `--optimize`
+    pub fn set_optimize(mut self, optimize: Optimize) -> Vypers {
+        self.optimize = Some(optimize);
+        self
+    }
+
+    /// Sets what `gen_abi_many` does when an ABI file exists
+    pub fn set_overwrite(mut self, overwrite: OverwritePolicy) -> Vypers {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Records the ABIs written by `gen_abi_many` in the manifest of the current directory,
+    /// so `Project::clean` can delete them
+    pub fn set_track_outputs(mut self, track_outputs: bool) -> Vypers {
+        self.track_outputs = track_outputs;
+        self
+    }
+
+    /// Overrides the settings of the contract at `path`
+    pub fn set_settings(mut self, path: PathBuf, settings: ContractSettings) -> Vypers {
+        self.settings.insert(path, settings);
+        self
+    }
+
+    /// Compiles the contract at `path` with the compiler installed in `venv`
+    pub fn set_contract_venv(mut self, path: PathBuf, venv: PathBuf) -> Vypers {
+        self.settings.entry(path).or_default().venv = Some(venv);
+        self
+    }
+
+    /// Compiles the contract at `path` with the compiler binary at `compiler`
+    pub fn set_contract_compiler(mut self, path: PathBuf, compiler: PathBuf) -> Vypers {
+        self.settings.entry(path).or_default().compiler = Some(compiler);
+        self
+    }
+
+    /// Assigns each contract the newest of the `installed` compilers satisfying its version
+    /// pragma, so legacy and new contracts can be compiled in one go. Contracts without a
+    /// pragma keep using the shared compiler.
+    pub fn assign_compilers(
+        &mut self,
+        installed: &[InstalledCompiler],
+    ) -> Result<(), VyperErrors> {
+        let mut installed: Vec<(Version, &Path)> = installed
+            .iter()
+            .filter_map(|c| {
+                Some((utils::parse_version(&c.version).ok()?, c.venv.as_path()))
+            })
+            .collect();
+        installed.sort();
+        for path in &self.path_to_code {
+            let Some(pragma) = SourceUnit::parse(path)?.pragma else {
+                continue;
+            };
+            let req = utils::version_req(&pragma)?;
+            let Some((_, venv)) = installed.iter().rev().find(|(v, _)| req.matches(v))
+            else {
+                Err(VyperErrors::NoMatchingCompiler {
+                    contract: path.clone(),
+                    pragma,
+                })?
+            };
+            self.settings.entry(path.clone()).or_default().venv =
+                Some(venv.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Compiles the contract at `path` for `evm_version`, whatever the bulk method is given
+    pub fn set_evm_version(mut self, path: PathBuf, evm_version: Evm) -> Vypers {
+        self.settings.entry(path).or_default().evm_version = Some(evm_version);
+        self
+    }
+
+    /// The EVM version of each contract, in the order of `path_to_code`: its override, or else
+    /// `default`. `None` leaves the target to the compiler.
+    pub(crate) fn evm_versions(&self, default: Option<&Evm>) -> Vec<Option<String>> {
+        self.path_to_code
+            .iter()
+            .map(|path| {
+                self.settings
+                    .get(path)
+                    .and_then(|settings| settings.evm_version.as_ref())
+                    .or(default)
+                    .map(Evm::to_string)
+            })
+            .collect()
+    }
+    /// Path to the compiler binary, resolved the same way as `Vyper::get_vyper`
     pub fn get_vyper(&self) -> String {
-        if let Some(venv) = &self.venv {
-            if cfg!(target_os = "windows") {
-                format!("{}/scripts/vyper", venv.to_string_lossy())
-            } else {
-                format!("{}/bin/vyper", venv.to_string_lossy())
-            }
-        } else {
-            "vyper".to_owned()
+        vyper_bin(self.compiler.as_deref(), self.venv.as_deref())
+    }
+
+    /// Path to the compiler binary for the contract at `path`, taking its `settings` into
+    /// account
+    pub fn get_vyper_for(&self, path: &Path) -> String {
+        match self.settings.get(path) {
+            Some(ContractSettings {
+                compiler: Some(compiler),
+                ..
+            }) => vyper_bin(Some(compiler), None),
+            Some(ContractSettings {
+                venv: Some(venv), ..
+            }) => vyper_bin(None, Some(venv)),
+            _ => self.get_vyper(),
         }
     }
 
+    /// The backend every compiler invocation for these contracts goes through, unless a
+    /// contract has its own compiler in `settings`
+    pub(crate) fn resolve_compiler(&self) -> Arc<dyn CompilerBackend> {
+        self.backend.resolve(self.get_vyper())
+    }
+
+    /// The backend compiling the contract at `path`
+    pub(crate) fn resolve_compiler_for(&self, path: &Path) -> Arc<dyn CompilerBackend> {
+        self.backend.resolve(self.get_vyper_for(path))
+    }
+
+    /// `resolve_compiler_for` of each contract, in the order of `path_to_code`
+    pub(crate) fn resolve_compilers(&self) -> Vec<Arc<dyn CompilerBackend>> {
+        let shared = self.resolve_compiler();
+        self.path_to_code
+            .iter()
+            .map(|path| match self.settings.get(path) {
+                Some(settings)
+                    if settings.compiler.is_some() || settings.venv.is_some() =>
+                {
+                    self.resolve_compiler_for(path)
+                }
+                _ => Arc::clone(&shared),
+            })
+            .collect()
+    }
+
+    /// check the version of the vyper compiler used for these contracts
+    pub fn get_version(&self) -> Result<String, VyperErrors> {
+        compiler_version(&*self.resolve_compiler(), self.timeout, &self.retry)
+    }
+
+    /// check the version of the vyper compiler used for the contract at `path`
+    pub fn get_version_for(&self, path: &Path) -> Result<String, VyperErrors> {
+        compiler_version(&*self.resolve_compiler_for(path), self.timeout, &self.retry)
+    }
+
     pub fn get_pip(&self) -> String {
         if let Some(venv) = &self.venv {
-            if cfg!(target_os = "windows") {
-                format!("{}/scripts/pip3", venv.to_string_lossy())
-            } else {
-                format!("{}/bin/pip3", venv.to_string_lossy())
-            }
+            venv::executable(venv, "pip3").to_string_lossy().to_string()
         } else {
             "pip3".to_owned()
         }
     }
 
     /// Compile multiple vyper contracts concurrently on new threads, updates the ABI field in Vypers
+    #[cfg(feature = "async")]
     pub async fn compile_many(&mut self) -> Result<(), VyperErrors> {
+        self.compile_many_cancellable(CancellationToken::new())
+            .await
+    }
+
+    /// Compiles only the contracts changed since the git revision `git_ref` and the contracts
+    /// importing them, imports resolved from the current directory. See
+    /// `Project::compile_changed_since`.
+    #[cfg(all(feature = "async", feature = "git"))]
+    pub async fn compile_changed_since(
+        &mut self,
+        git_ref: &str,
+    ) -> Result<Vec<PathBuf>, VyperErrors> {
+        let mut project = crate::project::Project::new(PathBuf::from("."), self.clone());
+        let compiled = project.compile_changed_since(git_ref).await?;
+        self.bytecode = project.contracts.bytecode;
+        Ok(compiled)
+    }
+
+    /// Same as `compile_many`, but every running compiler is killed once `cancel` is triggered
+    #[cfg(feature = "async")]
+    pub async fn compile_many_cancellable(
+        &mut self,
+        cancel: CancellationToken,
+    ) -> Result<(), VyperErrors> {
+        let bytecode = self.compile_jobs(self.evm_versions(None), cancel).await?;
+        self.bytecode = Some(bytecode);
+        Ok(())
+    }
+
+    /// Same as `compile_many`, but also measures how long each contract took to compile and
+    /// how many compilers were running at once.
+    #[cfg(feature = "async")]
+    pub async fn compile_many_with_metrics(
+        &mut self,
+    ) -> Result<BuildMetrics, VyperErrors> {
         let path = Arc::new(self.path_to_code.clone());
-        let mut out_vec: Vec<String> = Vec::with_capacity(self.path_to_code.len());
-        let mut threads: Vec<JoinHandle<Result<String, VyperErrors>>> = vec![];
-        let vy: Arc<String> = Arc::new(self.get_vyper());
-        for i in 0..self.path_to_code.len() {
+        let mut out_vec: Vec<Bytecode> = Vec::with_capacity(self.path_to_code.len());
+        let mut threads: Vec<
+            JoinHandle<Result<(Bytecode, ContractMetrics), VyperErrors>>,
+        > = vec![];
+        let vy = self.resolve_compilers();
+        let timeout = self.timeout;
+        let retry = self.retry;
+        let optimize = self.optimize;
+        let cancel = CancellationToken::new();
+        let build_start = Instant::now();
+        for (i, evm_version) in self.evm_versions(None).into_iter().enumerate() {
             let paths = Arc::clone(&path);
-            let bin = Arc::clone(&vy);
+            let bin = Arc::clone(&vy[i]);
+            let cancel = cancel.clone();
             let cthread = tokio::spawn(async move {
-                let compiler_output =
-                    Command::new(bin.as_str()).arg(&paths[i]).output()?;
-                if compiler_output.status.success() {
-                    let mut out =
-                        String::from_utf8_lossy(&compiler_output.stdout).to_string();
-
-                    for _ in 0..1 {
-                        out.pop();
-                    }
-                    if !out.starts_with("0x") {
-                        if let Some(e) = out.split(":").last() {
-                            Ok(e.to_owned())
-                        } else {
-                            Err(VyperErrors::StringParsingError)
-                        }
-                    } else {
-                       Ok(out) 
-                    }
-                } else {
-                    Err(VyperErrors::CompilerError(
-                        String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-                    ))?
-                }
+                let started_at = build_start.elapsed();
+                let evm_version = evm_version.as_deref();
+                let bytecode = compile_async(
+                    &*bin,
+                    &paths[i],
+                    evm_version,
+                    optimize.as_ref(),
+                    timeout,
+                    &retry,
+                    &cancel,
+                )
+                .await?;
+                let metrics = ContractMetrics {
+                    path: paths[i].clone(),
+                    started_at,
+                    wall_time: build_start.elapsed().saturating_sub(started_at),
+                };
+                Ok((bytecode, metrics))
             });
             threads.push(cthread);
         }
+        let mut contracts = Vec::with_capacity(threads.len());
         for child_thread in threads {
-            let x = child_thread.await??;
-            out_vec.push(x);
+            let (bytecode, metrics) = child_thread.await??;
+            out_vec.push(bytecode);
+            contracts.push(metrics);
         }
         self.bytecode = Some(out_vec);
-        Ok(())
+        Ok(BuildMetrics::new(contracts, build_start.elapsed()))
     }
 
-    /// Compile multiple vyper contracts concurrently on new threads, updates the ABI field in Vypers. `Ver` arg is for specifying EVM version to compile each contract to.
+    /// Compile multiple vyper contracts concurrently on new threads, updates the ABI field in Vypers. `Ver` arg is for specifying EVM version to compile each contract to, unless the contract has its own in `settings`.
+    #[cfg(feature = "async")]
     pub async fn compile_many_ver(&mut self, ver: Evm) -> Result<(), VyperErrors> {
-        let path = Arc::new(self.path_to_code.clone());
-        let vy = Arc::new(self.get_vyper());
-        let mut out_vec: Vec<String> = Vec::with_capacity(self.path_to_code.len());
-        let version = ver.to_string();
-        let mut threads: Vec<JoinHandle<Result<String, VyperErrors>>> = vec![];
-        for i in 0..self.path_to_code.len() {
-            let paths = Arc::clone(&path);
-            let bin = Arc::clone(&vy);
-            let cver = version.clone();
+        self.compile_many_ver_cancellable(ver, CancellationToken::new())
+            .await
+    }
+
+    /// Same as `compile_many_ver`, but every running compiler is killed once `cancel` is
+    /// triggered
+    #[cfg(feature = "async")]
+    pub async fn compile_many_ver_cancellable(
+        &mut self,
+        ver: Evm,
+        cancel: CancellationToken,
+    ) -> Result<(), VyperErrors> {
+        let evm_versions = self.evm_versions(Some(&ver));
+        let bytecode = self.compile_jobs(evm_versions, cancel).await?;
+        self.bytecode = Some(bytecode);
+        Ok(())
+    }
+
+    /// Groups the contracts into `CompileJob`s. Returns the first contract and EVM version of
+    /// every job, and the job of every contract.
+    fn dedup_jobs(
+        &self,
+        evm_versions: Vec<Option<String>>,
+    ) -> (Vec<(usize, Option<String>)>, Vec<usize>) {
+        let mut jobs: HashMap<CompileJob, usize> = HashMap::new();
+        let mut unique = vec![];
+        let mut job_of = Vec::with_capacity(self.path_to_code.len());
+        for (i, evm_version) in evm_versions.into_iter().enumerate() {
+            let path = &self.path_to_code[i];
+            let settings = self.settings.get(path);
+            let job = CompileJob {
+                source_sha256: utils::sha256_file(path).ok(),
+                path: path.clone(),
+                evm_version: evm_version.clone(),
+                compiler: settings.and_then(|s| s.compiler.clone()),
+                venv: settings.and_then(|s| s.venv.clone()),
+            };
+            let next = unique.len();
+            let job = *jobs.entry(job).or_insert(next);
+            if job == next {
+                unique.push((i, evm_version));
+            }
+            job_of.push(job);
+        }
+        (unique, job_of)
+    }
+
+    /// Compiles every contract for its entry in `evm_versions` concurrently. Identical jobs,
+    /// the same source compiled by the same compiler for the same target, only run once and
+    /// share their bytecode.
+    #[cfg(feature = "async")]
+    async fn compile_jobs(
+        &self,
+        evm_versions: Vec<Option<String>>,
+        cancel: CancellationToken,
+    ) -> Result<Vec<Bytecode>, VyperErrors> {
+        let vy = self.resolve_compilers();
+        let timeout = self.timeout;
+        let retry = self.retry;
+        let optimize = self.optimize;
+        let (jobs, job_of) = self.dedup_jobs(evm_versions);
+        let mut threads: Vec<JoinHandle<Result<Bytecode, VyperErrors>>> = vec![];
+        for (i, evm_version) in jobs {
+            let path = self.path_to_code[i].clone();
+            let bin = Arc::clone(&vy[i]);
+            let cancel = cancel.clone();
             let cthread = tokio::spawn(async move {
-                let compiler_output = Command::new(bin.as_str())
-                    .arg(&paths[i])
-                    .arg("--evm-version")
-                    .arg(cver)
-                    .output()?;
-                if compiler_output.status.success() {
-                    let mut out =
-                        String::from_utf8_lossy(&compiler_output.stdout).to_string();
-                    for _ in 0..1 {
-                        out.pop();
-                    }
-                    if !out.starts_with("0x") {
-                        if let Some(e) = out.split(":").last() {
-                            Ok(e.to_owned())
-                        } else {
-                            Err(VyperErrors::StringParsingError)
-                        }
-                    } else {
-                       Ok(out) 
-                    }
-                } else {
-                    Err(VyperErrors::CompilerError(
-                        String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-                    ))?
-                }
+                let evm_version = evm_version.as_deref();
+                let optimize = optimize.as_ref();
+                compile_async(
+                    &*bin,
+                    &path,
+                    evm_version,
+                    optimize,
+                    timeout,
+                    &retry,
+                    &cancel,
+                )
+                .await
             });
             threads.push(cthread);
         }
+        let mut out_vec: Vec<Bytecode> = Vec::with_capacity(threads.len());
         for child_thread in threads {
             let x = child_thread.await??;
             out_vec.push(x);
         }
-        self.bytecode = Some(out_vec);
-        Ok(())
+        Ok(job_of.into_iter().map(|job| out_vec[job].clone()).collect())
+    }
+
+    /// Runs `Vyper::check` on every contract concurrently and returns a diagnostic for each
+    /// contract that failed, making it cheap to validate a whole workspace before committing.
+    #[cfg(feature = "async")]
+    pub async fn check_many(&self) -> Result<Vec<Diagnostic>, VyperErrors> {
+        let paths = Arc::new(self.path_to_code.clone());
+        let vy = self.resolve_compilers();
+        let timeout = self.timeout;
+        let retry = self.retry;
+        let cancel = CancellationToken::new();
+        let mut threads: Vec<JoinHandle<Option<Diagnostic>>> = vec![];
+        for i in 0..paths.len() {
+            let paths = Arc::clone(&paths);
+            let bin = Arc::clone(&vy[i]);
+            let cancel = cancel.clone();
+            let cthread = tokio::spawn(async move {
+                check_async(&*bin, &paths[i], timeout, &retry, &cancel)
+                    .await
+                    .err()
+                    .map(|e| Diagnostic::from(&e))
+            });
+            threads.push(cthread);
+        }
+        let mut out = vec![];
+        for child_thread in threads {
+            out.extend(child_thread.await?);
+        }
+        Ok(out)
+    }
+
+    /// Reads the ABIs previously written to the ABI paths by `gen_abi_many`
+    pub fn read_abi_many(&self) -> Result<Vec<Value>, VyperErrors> {
+        self.abi
+            .iter()
+            .map(|abi| Ok(serde_json::from_reader(File::open(abi)?)?))
+            .collect()
     }
 
     /// Generates ABIs for each vyper contract concurrently
+    #[cfg(feature = "async")]
     pub async fn gen_abi_many(&mut self) -> Result<(), VyperErrors> {
+        self.gen_abi_many_cancellable(CancellationToken::new())
+            .await
+    }
+
+    /// Same as `gen_abi_many`, but every running compiler is killed once `cancel` is triggered
+    #[cfg(feature = "async")]
+    pub async fn gen_abi_many_cancellable(
+        &mut self,
+        cancel: CancellationToken,
+    ) -> Result<(), VyperErrors> {
         let abi_path = Arc::new(self.abi.clone());
-        let vy = Arc::new(self.get_vyper());
+        let vy = self.resolve_compilers();
         let c_path = Arc::new(self.path_to_code.clone());
+        let timeout = self.timeout;
+        let retry = self.retry;
+        let overwrite = self.overwrite;
         let mut threads: Vec<JoinHandle<Result<(), VyperErrors>>> = vec![];
         for i in 0..c_path.len() {
             let c = Arc::clone(&c_path);
             let abi = Arc::clone(&abi_path);
-            let bin = Arc::clone(&vy);
+            let bin = Arc::clone(&vy[i]);
+            let cancel = cancel.clone();
             let cthread = tokio::spawn(async move {
-                let compiler_output = Command::new(bin.as_str())
-                    .arg("-f")
-                    .arg("abi")
-                    .arg(&c[i])
-                    .output()?;
+                if !overwrite.should_write(&c[i], &abi[i])? {
+                    return Ok(());
+                }
+                let mut cmd = tokio::process::Command::from(bin.command());
+                cmd.arg("-f").arg("abi").arg(&c[i]);
+                let command = process::command_line(cmd.as_std());
+                let compiler_output =
+                    process::output_async(cmd, timeout, &retry, &cancel).await?;
                 if compiler_output.status.success() {
                     let json = serde_json::from_str::<Value>(&String::from_utf8_lossy(
                         &compiler_output.stdout,
                     ))?;
-                    let file = File::create(&abi[i])?;
-                    to_writer_pretty(file, &json)?;
+                    artifacts::write_output(&abi[i], &serde_json::to_vec_pretty(&json)?)?;
                 } else {
-                    Err(VyperErrors::CompilerError(
-                        String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-                    ))?
+                    Err(compilation_failed(
+                        &*bin,
+                        &c[i],
+                        command,
+                        &compiler_output,
+                        timeout,
+                        &cancel,
+                    )
+                    .await)?
                 }
                 Ok(())
             });
@@ -645,31 +1700,120 @@ impl Vypers {
         for child_thread in threads {
             child_thread.await??
         }
-        Ok(())
+        self.record_abis()
     }
 
+    #[cfg(feature = "async")]
     pub async fn get_abi_many(&self) -> Result<Vec<Value>, VyperErrors> {
+        self.get_abi_many_cancellable(CancellationToken::new())
+            .await
+    }
+
+    /// Same as `get_abi_many`, but every running compiler is killed once `cancel` is triggered
+    #[cfg(feature = "async")]
+    pub async fn get_abi_many_cancellable(
+        &self,
+        cancel: CancellationToken,
+    ) -> Result<Vec<Value>, VyperErrors> {
+        self.output_many("abi", self.evm_versions(None), cancel)
+            .await
+    }
+
+    /// The metadata of every contract, see `Vyper::metadata`. Describes the build of
+    /// `compile_many`.
+    #[cfg(feature = "async")]
+    pub async fn get_metadata_many(&self) -> Result<Vec<Value>, VyperErrors> {
+        self.output_many(
+            "metadata",
+            self.evm_versions(None),
+            CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// The metadata of every contract built for `ver` like by `compile_many_ver`
+    #[cfg(feature = "async")]
+    pub async fn get_metadata_many_ver(
+        &self,
+        ver: Evm,
+    ) -> Result<Vec<Value>, VyperErrors> {
+        let evm_versions = self.evm_versions(Some(&ver));
+        self.output_many("metadata", evm_versions, CancellationToken::new())
+            .await
+    }
+
+    /// Collects the method identifiers of every contract and flags function selectors that
+    /// collide, see `SelectorReport`
+    #[cfg(feature = "async")]
+    pub async fn selector_report(&self) -> Result<SelectorReport, VyperErrors> {
+        let identifiers = self
+            .output_many(
+                "method_identifiers",
+                self.evm_versions(None),
+                CancellationToken::new(),
+            )
+            .await?;
+        self.selector_report_of(identifiers)
+    }
+
+    /// `SelectorReport` of the method identifiers of every contract
+    fn selector_report_of(
+        &self,
+        identifiers: Vec<Value>,
+    ) -> Result<SelectorReport, VyperErrors> {
+        let mut contracts = BTreeMap::new();
+        for (contract, identifiers) in self.path_to_code.iter().zip(identifiers) {
+            contracts.insert(contract.clone(), serde_json::from_value(identifiers)?);
+        }
+        Ok(SelectorReport::new(contracts))
+    }
+
+    /// Runs the compiler with output `format` on every contract concurrently, for its entry in
+    /// `evm_versions`, parsing each output as JSON
+    #[cfg(feature = "async")]
+    async fn output_many(
+        &self,
+        format: &'static str,
+        evm_versions: Vec<Option<String>>,
+        cancel: CancellationToken,
+    ) -> Result<Vec<Value>, VyperErrors> {
         let c_path = Arc::new(self.path_to_code.clone());
         let mut threads: Vec<JoinHandle<Result<Value, VyperErrors>>> = vec![];
-        let vy = Arc::new(self.get_vyper());
-        for i in 0..self.path_to_code.len() {
+        let vy = self.resolve_compilers();
+        let timeout = self.timeout;
+        let retry = self.retry;
+        let optimize = self.optimize;
+        for (i, evm_version) in evm_versions.into_iter().enumerate() {
             let c = Arc::clone(&c_path);
-            let bin = Arc::clone(&vy);
+            let bin = Arc::clone(&vy[i]);
+            let cancel = cancel.clone();
             let cthread = tokio::spawn(async move {
-                let compiler_output = Command::new(bin.as_str())
-                    .arg("-f")
-                    .arg("abi")
-                    .arg(&c[i])
-                    .output()?;
+                let mut cmd = tokio::process::Command::from(bin.command());
+                cmd.arg("-f").arg(format).arg(&c[i]);
+                if let Some(evm_version) = evm_version {
+                    cmd.arg("--evm-version").arg(evm_version);
+                }
+                if let Some(optimize) = optimize {
+                    cmd.arg("--optimize").arg(optimize.to_string());
+                }
+                let command = process::command_line(cmd.as_std());
+                let compiler_output =
+                    process::output_async(cmd, timeout, &retry, &cancel).await?;
                 if compiler_output.status.success() {
                     let json = serde_json::from_str::<Value>(&String::from_utf8_lossy(
                         &compiler_output.stdout,
                     ))?;
                     Ok(json)
                 } else {
-                    Err(VyperErrors::CompilerError(
-                        String::from_utf8_lossy(&compiler_output.stderr).to_string(),
-                    ))?
+                    Err(compilation_failed(
+                        &*bin,
+                        &c[i],
+                        command,
+                        &compiler_output,
+                        timeout,
+                        &cancel,
+                    )
+                    .await)?
                 }
             });
             threads.push(cthread);
@@ -680,54 +1824,525 @@ impl Vypers {
         }
         Ok(res_vec)
     }
+
+    /// Blocking counterpart of `compile_many`, compiling on scoped threads instead of the
+    /// tokio runtime
+    pub fn compile_many_blocking(&mut self) -> Result<(), VyperErrors> {
+        let bytecode = self.compile_jobs_blocking(self.evm_versions(None))?;
+        self.bytecode = Some(bytecode);
+        Ok(())
+    }
+
+    /// Blocking counterpart of `compile_many_ver`
+    pub fn compile_many_ver_blocking(&mut self, ver: Evm) -> Result<(), VyperErrors> {
+        let evm_versions = self.evm_versions(Some(&ver));
+        let bytecode = self.compile_jobs_blocking(evm_versions)?;
+        self.bytecode = Some(bytecode);
+        Ok(())
+    }
+
+    /// Blocking counterpart of `compile_jobs`
+    fn compile_jobs_blocking(
+        &self,
+        evm_versions: Vec<Option<String>>,
+    ) -> Result<Vec<Bytecode>, VyperErrors> {
+        let vy = self.resolve_compilers();
+        let (jobs, job_of) = self.dedup_jobs(evm_versions);
+        let bytecode = scoped(jobs.len(), |job| {
+            let (i, evm_version) = &jobs[job];
+            compile_blocking(
+                &*vy[*i],
+                &self.path_to_code[*i],
+                evm_version.as_deref(),
+                self.optimize.as_ref(),
+                self.timeout,
+                &self.retry,
+            )
+        })?;
+        Ok(job_of
+            .into_iter()
+            .map(|job| bytecode[job].clone())
+            .collect())
+    }
+
+    /// Blocking counterpart of `check_many`
+    pub fn check_many_blocking(&self) -> Result<Vec<Diagnostic>, VyperErrors> {
+        let vy = self.resolve_compilers();
+        let diagnostics = scoped(self.path_to_code.len(), |i| {
+            let path = &self.path_to_code[i];
+            let mut cmd = vy[i].command();
+            cmd.args(CHECK_ARGS).arg(path);
+            Ok(
+                output_blocking(&*vy[i], path, cmd, self.timeout, &self.retry)
+                    .err()
+                    .map(|e| Diagnostic::from(&e)),
+            )
+        })?;
+        Ok(diagnostics.into_iter().flatten().collect())
+    }
+
+    /// Blocking counterpart of `gen_abi_many`
+    pub fn gen_abi_many_blocking(&mut self) -> Result<(), VyperErrors> {
+        let vy = self.resolve_compilers();
+        scoped(self.path_to_code.len(), |i| {
+            let (path, abi) = (&self.path_to_code[i], &self.abi[i]);
+            if !self.overwrite.should_write(path, abi)? {
+                return Ok(());
+            }
+            let mut cmd = vy[i].command();
+            cmd.arg("-f").arg("abi").arg(path);
+            let stdout = output_blocking(&*vy[i], path, cmd, self.timeout, &self.retry)?;
+            let json: Value = serde_json::from_slice(&stdout)?;
+            artifacts::write_output(abi, &serde_json::to_vec_pretty(&json)?)
+        })?;
+        self.record_abis()
+    }
+
+    /// Records the ABIs in the manifest of the current directory, with `track_outputs`
+    fn record_abis(&self) -> Result<(), VyperErrors> {
+        if !self.track_outputs {
+            return Ok(());
+        }
+        for (contract, abi) in self.path_to_code.iter().zip(&self.abi) {
+            Manifest::record(Path::new("."), contract, [abi.as_path()])?;
+        }
+        Ok(())
+    }
+
+    /// Blocking counterpart of `get_abi_many`
+    pub fn get_abi_many_blocking(&self) -> Result<Vec<Value>, VyperErrors> {
+        self.output_many_blocking("abi", self.evm_versions(None))
+    }
+
+    /// Blocking counterpart of `get_metadata_many`
+    pub fn get_metadata_many_blocking(&self) -> Result<Vec<Value>, VyperErrors> {
+        self.output_many_blocking("metadata", self.evm_versions(None))
+    }
+
+    /// Blocking counterpart of `get_metadata_many_ver`
+    pub fn get_metadata_many_ver_blocking(
+        &self,
+        ver: Evm,
+    ) -> Result<Vec<Value>, VyperErrors> {
+        self.output_many_blocking("metadata", self.evm_versions(Some(&ver)))
+    }
+
+    /// Blocking counterpart of `selector_report`
+    pub fn selector_report_blocking(&self) -> Result<SelectorReport, VyperErrors> {
+        let identifiers =
+            self.output_many_blocking("method_identifiers", self.evm_versions(None))?;
+        self.selector_report_of(identifiers)
+    }
+
+    /// Blocking counterpart of `output_many`
+    fn output_many_blocking(
+        &self,
+        format: &str,
+        evm_versions: Vec<Option<String>>,
+    ) -> Result<Vec<Value>, VyperErrors> {
+        let vy = self.resolve_compilers();
+        scoped(self.path_to_code.len(), |i| {
+            let path = &self.path_to_code[i];
+            let mut cmd = vy[i].command();
+            cmd.arg("-f").arg(format).arg(path);
+            if let Some(evm_version) = &evm_versions[i] {
+                cmd.arg("--evm-version").arg(evm_version);
+            }
+            if let Some(optimize) = &self.optimize {
+                cmd.arg("--optimize").arg(optimize.to_string());
+            }
+            let stdout = output_blocking(&*vy[i], path, cmd, self.timeout, &self.retry)?;
+            Ok(serde_json::from_slice(&stdout)?)
+        })
+    }
 }
 
-impl<'a> From<Vec<Vyper<'a>>> for Vypers {
-    fn from(value: Vec<Vyper>) -> Vypers {
-        let mut paths = vec![];
-        let mut abis = vec![];
-        let mut venv: Option<&Path> = None;
+/// Runs `job` for `0..n` on scoped threads, collecting the results in order
+fn scoped<T: Send>(
+    n: usize,
+    job: impl Fn(usize) -> Result<T, VyperErrors> + Sync,
+) -> Result<Vec<T>, VyperErrors> {
+    let job = &job;
+    thread::scope(|s| {
+        let handles: Vec<_> = (0..n).map(|i| s.spawn(move || job(i))).collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
 
-        value.into_iter().for_each(|x| {
-            paths.push(x.path_to_code.to_path_buf());
-            abis.push(x.abi);
-            venv = x.venv;
-        });
+/// Blocking counterpart of `compile_async`
+fn compile_blocking(
+    compiler: &dyn CompilerBackend,
+    path: &Path,
+    evm_version: Option<&str>,
+    optimize: Option<&Optimize>,
+    timeout: Option<Duration>,
+    retry: &RetryPolicy,
+) -> Result<Bytecode, VyperErrors> {
+    let mut cmd = compiler.command();
+    cmd.arg(path);
+    if let Some(ver) = evm_version {
+        cmd.arg("--evm-version").arg(ver);
+    }
+    if let Some(optimize) = optimize {
+        cmd.arg("--optimize").arg(optimize.to_string());
+    }
+    match output_blocking(compiler, path, cmd, timeout, retry) {
+        Ok(stdout) => {
+            let output = utils::parse_bytecode_output(&String::from_utf8_lossy(&stdout))?;
+            #[cfg(feature = "tracing")]
+            for line in &output.diagnostics {
+                process::debug_event!(contract = %path.display(), "{}", line);
+            }
+            Ok(output.bytecode)
+        }
+        Err(err) => {
+            let evm = evm_version.and_then(|ver| ver.parse::<Evm>().ok());
+            Err(unsupported_evm(evm.as_ref(), err))
+        }
+    }
+}
+
+/// Runs `cmd` for the contract at `path`, returning its stdout or the compilation failure
+fn output_blocking(
+    compiler: &dyn CompilerBackend,
+    path: &Path,
+    mut cmd: Command,
+    timeout: Option<Duration>,
+    retry: &RetryPolicy,
+) -> Result<Vec<u8>, VyperErrors> {
+    let output = cmd.output_retry(timeout, retry)?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+    let version = compiler_version(compiler, timeout, &RetryPolicy::none())
+        .ok()
+        .map(|version| version.trim().to_owned());
+    Err(VyperErrors::CompilationFailed {
+        contract: path.to_path_buf(),
+        command: process::command_line(&cmd),
+        version,
+        exit: (&output.status).into(),
+        stdout: String::from_utf8_lossy(&output.stdout).into(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    })
+}
 
-        match venv {
-            Some(v) => Vypers::with_venv(paths, v),
-            None => Vypers::new(paths),
+/// Compiles a single contract on the tokio runtime and reads the bytecode from stdout.
+/// `optimize` is `None` for the compiler's default.
+#[cfg(feature = "async")]
+pub(crate) async fn compile_async(
+    compiler: &dyn CompilerBackend,
+    path: &Path,
+    evm_version: Option<&str>,
+    optimize: Option<&Optimize>,
+    timeout: Option<Duration>,
+    retry: &RetryPolicy,
+    cancel: &CancellationToken,
+) -> Result<Bytecode, VyperErrors> {
+    let mut cmd = tokio::process::Command::from(compiler.command());
+    cmd.arg(path);
+    if let Some(ver) = evm_version {
+        cmd.arg("--evm-version").arg(ver);
+    }
+    if let Some(optimize) = optimize {
+        cmd.arg("--optimize").arg(optimize.to_string());
+    }
+    let command = process::command_line(cmd.as_std());
+    let compiler_output = process::output_async(cmd, timeout, retry, cancel).await?;
+    if compiler_output.status.success() {
+        let output = utils::parse_bytecode_output(&String::from_utf8_lossy(
+            &compiler_output.stdout,
+        ))?;
+        #[cfg(feature = "tracing")]
+        for line in &output.diagnostics {
+            process::debug_event!(contract = %path.display(), "{}", line);
         }
+        Ok(output.bytecode)
+    } else {
+        let err = compilation_failed(
+            compiler,
+            path,
+            command,
+            &compiler_output,
+            timeout,
+            cancel,
+        )
+        .await;
+        let evm = evm_version.and_then(|ver| ver.parse::<Evm>().ok());
+        Err(unsupported_evm(evm.as_ref(), err))?
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
-pub enum Evm {
-    Byzantium,
-    Constantinople,
-    Petersberg,
-    Istanbul,
-    Berlin,
-    Paris,
-    Shanghai,
-    Cancun,
-    Atlantis,
-    Agharta,
+/// Checks that contracts can be collected from `path`, telling a missing directory apart from
+/// an unreadable one
+fn check_dir(path: &Path) -> Result<(), VyperErrors> {
+    if !path.is_dir() {
+        Err(VyperErrors::DirectoryNotFound(path.to_path_buf()))?
+    }
+    match fs::read_dir(path) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(VyperErrors::DirectoryUnreadable {
+            path: path.to_path_buf(),
+            error,
+        }),
+    }
 }
 
-impl Display for Evm {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Evm::Byzantium => write!(f, "{}", "byzantium".to_owned()),
-            Evm::Constantinople => write!(f, "{}", "constantinople".to_owned()),
-            Evm::Petersberg => write!(f, "{}", "petersberg".to_owned()),
-            Evm::Istanbul => write!(f, "{}", "istanbul".to_owned()),
-            Evm::Berlin => write!(f, "{}", "berlin".to_owned()),
-            Evm::Paris => write!(f, "{}", "paris".to_owned()),
-            Evm::Shanghai => write!(f, "{}", "shanghai".to_owned()),
-            Evm::Cancun => write!(f, "{}", "cancun".to_owned()),
-            Evm::Atlantis => write!(f, "{}", "atlantis".to_owned()),
-            Evm::Agharta => write!(f, "{}", "agharta".to_owned()),
+/// Replaces a compilation failure with `UnsupportedEvm` when the compiler is known not to
+/// support `evm`, since the compiler's own message doesn't point at the cause
+fn unsupported_evm(evm: Option<&Evm>, err: VyperErrors) -> VyperErrors {
+    if let (
+        Some(evm),
+        VyperErrors::CompilationFailed {
+            version: Some(version),
+            ..
+        },
+    ) = (evm, &err)
+    {
+        if let Ok(compiler) = utils::parse_version(version) {
+            if !evm.supported_by(&compiler) {
+                return VyperErrors::UnsupportedEvm {
+                    evm: evm.clone(),
+                    compiler,
+                };
+            }
+        }
+    }
+    err
+}
+
+/// Runs the check-only pass on a single contract on the tokio runtime
+#[cfg(feature = "async")]
+pub(crate) async fn check_async(
+    compiler: &dyn CompilerBackend,
+    path: &Path,
+    timeout: Option<Duration>,
+    retry: &RetryPolicy,
+    cancel: &CancellationToken,
+) -> Result<(), VyperErrors> {
+    let mut cmd = tokio::process::Command::from(compiler.command());
+    cmd.args(CHECK_ARGS).arg(path);
+    let command = process::command_line(cmd.as_std());
+    let compiler_output = process::output_async(cmd, timeout, retry, cancel).await?;
+    if compiler_output.status.success() {
+        Ok(())
+    } else {
+        Err(compilation_failed(
+            compiler,
+            path,
+            command,
+            &compiler_output,
+            timeout,
+            cancel,
+        )
+        .await)?
+    }
+}
+
+/// Async counterpart of `Vyper::compilation_failed`
+#[cfg(feature = "async")]
+async fn compilation_failed(
+    compiler: &dyn CompilerBackend,
+    contract: &Path,
+    command: String,
+    output: &Output,
+    timeout: Option<Duration>,
+    cancel: &CancellationToken,
+) -> VyperErrors {
+    let mut cmd = tokio::process::Command::from(compiler.command());
+    cmd.arg("--version");
+    let version = process::output_async(cmd, timeout, &RetryPolicy::none(), cancel)
+        .await
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_owned());
+    VyperErrors::CompilationFailed {
+        contract: contract.to_path_buf(),
+        command,
+        version,
+        exit: (&output.status).into(),
+        stdout: String::from_utf8_lossy(&output.stdout).into(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    }
+}
+
+/// Timing of a single contract in a build
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ContractMetrics {
+    pub path: PathBuf,
+    /// When the compiler was started, relative to the start of the build
+    pub started_at: Duration,
+    /// How long the compiler ran for
+    pub wall_time: Duration,
+}
+
+/// Timing and concurrency statistics of a build, returned by
+/// `Vypers::compile_many_with_metrics`. Serializes to JSON for CI dashboards.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildMetrics {
+    pub contracts: Vec<ContractMetrics>,
+    /// Wall time of the whole build
+    pub total_time: Duration,
+    /// Sum of the wall time of every contract
+    pub compile_time: Duration,
+    /// Largest number of compilers that were running at the same time
+    pub peak_concurrency: usize,
+    /// `compile_time / total_time`, the average number of compilers running at once
+    pub mean_concurrency: f64,
+}
+
+impl BuildMetrics {
+    pub fn new(contracts: Vec<ContractMetrics>, total_time: Duration) -> BuildMetrics {
+        let compile_time = contracts.iter().map(|c| c.wall_time).sum::<Duration>();
+        // +1 when a compiler starts and -1 when it stops, ends sort before starts
+        let mut events: Vec<(Duration, i64)> = contracts
+            .iter()
+            .flat_map(|c| [(c.started_at, 1), (c.started_at + c.wall_time, -1)])
+            .collect();
+        events.sort();
+        let (_, peak_concurrency) = events
+            .iter()
+            .fold((0i64, 0i64), |(running, peak), (_, delta)| {
+                (running + delta, peak.max(running + delta))
+            });
+        let mean_concurrency = if total_time.is_zero() {
+            0.0
+        } else {
+            compile_time.as_secs_f64() / total_time.as_secs_f64()
+        };
+        BuildMetrics {
+            contracts,
+            total_time,
+            compile_time,
+            peak_concurrency: peak_concurrency as usize,
+            mean_concurrency,
+        }
+    }
+
+    /// The contract that took the longest to compile
+    pub fn slowest(&self) -> Option<&ContractMetrics> {
+        self.contracts.iter().max_by_key(|c| c.wall_time)
+    }
+}
+
+/// A build of `Vyper::compile_with_versions` with one of the requested compilers
+#[derive(Debug)]
+pub struct CompilationResult {
+    pub req: VersionReq,
+    /// Output of `vyper --version` of the compiler that matched `req`
+    pub version: Option<String>,
+    /// The bytecode, or why the compiler couldn't be provisioned or the contract compiled
+    pub bytecode: Result<Bytecode, VyperErrors>,
+    /// Warnings and other lines the compiler printed besides the bytecode
+    pub diagnostics: Vec<String>,
+    /// How the bytecode differs from the baseline's, `None` when it's the same or either
+    /// failed. The baseline is the first result that compiled.
+    pub diff: Option<BytecodeDiff>,
+    /// Diagnostics the baseline's compiler didn't print
+    pub new_warnings: Vec<String>,
+}
+
+impl CompilationResult {
+    fn compare(&mut self, baseline: &CompilationResult) {
+        if let (Ok(bytecode), Ok(base)) = (&self.bytecode, &baseline.bytecode) {
+            self.diff =
+                BytecodeDiff::new(base.without_metadata(), bytecode.without_metadata());
         }
+        self.new_warnings = self
+            .diagnostics
+            .iter()
+            .filter(|line| !baseline.diagnostics.contains(line))
+            .cloned()
+            .collect();
     }
 }
+
+/// Where two builds of a contract diverge, metadata excluded
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct BytecodeDiff {
+    /// Offset of the first byte that differs
+    pub first_difference: usize,
+    pub baseline_size: usize,
+    pub size: usize,
+}
+
+impl BytecodeDiff {
+    /// `None` when `bytecode` is the same as `baseline`
+    pub fn new(baseline: &[u8], bytecode: &[u8]) -> Option<BytecodeDiff> {
+        if baseline == bytecode {
+            return None;
+        }
+        let first_difference = baseline
+            .iter()
+            .zip(bytecode)
+            .take_while(|(a, b)| a == b)
+            .count();
+        Some(BytecodeDiff {
+            first_difference,
+            baseline_size: baseline.len(),
+            size: bytecode.len(),
+        })
+    }
+
+    /// Bytes gained, negative when the bytecode shrank
+    pub fn size_delta(&self) -> isize {
+        self.size as isize - self.baseline_size as isize
+    }
+}
+
+/// Environment variable naming the compiler binary invoked for contracts without an explicit
+/// compiler or venv, instead of `vyper` in PATH.
+pub const VYPER_PATH: &str = "VYPER_PATH";
+
+/// Runs `--version` on `compiler`
+fn compiler_version(
+    compiler: &dyn CompilerBackend,
+    timeout: Option<Duration>,
+    retry: &RetryPolicy,
+) -> Result<String, VyperErrors> {
+    let out = compiler
+        .command()
+        .arg("--version")
+        .output_retry(timeout, retry)?;
+    if !out.status.success() {
+        Err(VyperErrors::CompilerError(
+            "Couldn't locate version info, installation does not exist".to_string(),
+        ))?
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+pub(crate) fn vyper_bin(compiler: Option<&Path>, venv: Option<&Path>) -> String {
+    resolve_bin(compiler, venv, std::env::var_os(VYPER_PATH))
+}
+
+/// `vyper_bin` with the value of `VYPER_PATH`, which only replaces `vyper` in PATH: an
+/// explicitly configured compiler or venv always wins
+pub(crate) fn resolve_bin(
+    compiler: Option<&Path>,
+    venv: Option<&Path>,
+    vyper_path: Option<std::ffi::OsString>,
+) -> String {
+    if let Some(compiler) = compiler {
+        compiler.to_string_lossy().to_string()
+    } else if let Some(venv) = venv {
+        venv::executable(venv, "vyper")
+            .to_string_lossy()
+            .to_string()
+    } else if let Some(path) = vyper_path {
+        path.to_string_lossy().to_string()
+    } else {
+        "vyper".to_owned()
+    }
+}
+
+pub use semver::{Version, VersionReq};
+
+pub use crate::settings::{Evm, Optimize};
@@ -1,19 +1,223 @@
 //! This module contains the main error type returned when there's some issue with the compiler in
 //! the Vyper module.
-use std::{error::Error, fmt::Display, io, num::ParseIntError};
+use crate::{abi::Standard, lock::Drift, settings::Evm};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error, fmt::Display, io, num::ParseIntError, path::PathBuf,
+    process::ExitStatus, sync::Arc, time::Duration,
+};
 
+/// Variants only depending on optional crates, like `ConcurrencyError` on tokio, are gated
+/// behind their feature, so the enum is non exhaustive to keep features additive.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum VyperErrors {
     IoError(io::Error),
     CompilerError(String),
+    /// The compiler exited unsuccessfully on `contract`. `command` is the full command line and
+    /// `version` the output of `vyper --version`, when it could be determined. `exit` tells an
+    /// error in the contract apart from a compiler that crashed or was killed, e.g. by the OOM
+    /// killer. `stdout` is boxed to keep `VyperErrors` small.
+    CompilationFailed {
+        contract: PathBuf,
+        command: String,
+        version: Option<String>,
+        exit: Exit,
+        stdout: Box<str>,
+        stderr: String,
+    },
     SerializationError(serde_json::Error),
+    #[cfg(feature = "async")]
     ConcurrencyError(tokio::task::JoinError),
-    PipError(String),
-    DirError(String),
-    VenvError(String),
+    /// Creating the venv directory or running `python3 -m venv` failed
+    VenvCreationFailed {
+        path: PathBuf,
+        stderr: String,
+    },
+    /// No python interpreter could be found to create a venv with
+    PythonNotFound,
+    /// The python interpreter is older than `minimum`, `venv::MIN_PYTHON_VERSION`
+    UnsupportedPython {
+        python: PathBuf,
+        version: String,
+        minimum: (u32, u32),
+    },
+    /// The venv path exists, but doesn't contain a python venv
+    InvalidVenv {
+        path: PathBuf,
+    },
+    /// `Venv::recreate` refused to delete a directory that wasn't created by this library
+    UnmanagedVenv {
+        path: PathBuf,
+    },
+    /// Creating the venv directory or writing a file into the venv failed
+    VenvIoError {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The compiler installed in `venv` isn't the requested version. `found` is `None` when it
+    /// couldn't be invoked.
+    VyperVersionMismatch {
+        venv: PathBuf,
+        expected: String,
+        found: Option<String>,
+    },
+    /// `pip install vyper` failed. `version` is `None` when installing the latest version.
+    PipInstallFailed {
+        version: Option<String>,
+        stderr: String,
+    },
+    /// The package index has no vyper release matching `version`. `available` lists the
+    /// releases pip found, empty when it found none.
+    PipVersionNotFound {
+        version: Option<String>,
+        available: Vec<String>,
+    },
+    /// pip couldn't reach the package index
+    PipNetworkError {
+        version: Option<String>,
+        stderr: String,
+    },
+    /// pip wasn't allowed to write the installation, e.g. a global install without root
+    PipPermissionDenied {
+        version: Option<String>,
+        stderr: String,
+    },
+    /// Installing the requirements `tools` into a venv failed, see `Venv::install_tools`
+    ToolInstallFailed {
+        tools: Vec<String>,
+        stderr: String,
+    },
+    /// `pip uninstall vyper` failed in `venv`
+    PipUninstallFailed {
+        venv: PathBuf,
+        stderr: String,
+    },
+    /// A file's sha256 didn't match the expected one. `found` is `None` when the file is
+    /// missing.
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        found: Option<String>,
+    },
+    /// The compiler couldn't be found in the venv, or globally when `venv` is `None`
+    VyperNotInstalled {
+        venv: Option<PathBuf>,
+    },
     BlueprintError(String),
     IntParseError(ParseIntError),
     StringParsingError,
+    GlobError(globset::Error),
+    WalkError(ignore::Error),
+    TimeoutError(Duration),
+    /// Not the CLI name of an EVM version
+    UnknownEvm(String),
+    /// The compiler doesn't know the requested EVM version
+    UnsupportedEvm {
+        evm: Evm,
+        compiler: semver::Version,
+    },
+    VersionParseError(semver::Error),
+    /// None of the installed compilers satisfies the version pragma of `contract`
+    NoMatchingCompiler {
+        contract: PathBuf,
+        pragma: String,
+    },
+    /// Sending the deployment of a contract failed, see `deploy::deploy`
+    DeploymentFailed(String),
+    /// The ABI lacks members of a standard interface, see `Abi::check`
+    NonConformingInterface {
+        standard: Standard,
+        missing: Vec<String>,
+    },
+    /// Simulating the contract with revm failed, see `gas::GasReport::simulate`
+    SimulationFailed(String),
+    /// The directory to collect contracts from doesn't exist, see `Vypers::in_dir`
+    DirectoryNotFound(PathBuf),
+    /// The directory to collect contracts from can't be read
+    DirectoryUnreadable {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// No contract was found below the directory, see `Vypers::in_dir`
+    NoContractsFound(PathBuf),
+    /// Contracts of a project import each other, see `Project::build_order`
+    ImportCycle(Vec<PathBuf>),
+    /// A job of `queue::CompileQueue` failed. Shared by every caller that submitted the job.
+    QueuedJobFailed(Arc<VyperErrors>),
+    /// The project no longer matches `vyper.lock`
+    LockDrift(Vec<Drift>),
+    /// Values given for a `codegen::Template` don't match its placeholders
+    TemplateError {
+        missing: Vec<String>,
+        unknown: Vec<String>,
+    },
+    /// A JSON-RPC request failed, or the node answered with an error
+    RpcError(String),
+    /// An output file exists and `OverwritePolicy::Error` forbids replacing it
+    OutputExists(PathBuf),
+    /// Data doesn't match the ABI types it's decoded as
+    AbiDecodeError(String),
+    /// None of the EIP-1967 and EIP-1822 slots of the contract at this address are set
+    NotAProxy(String),
+    /// A request to a `registry::Registry` failed
+    RegistryError(String),
+    /// A registry entry's contract doesn't hash to the digest it was stored or requested under
+    DigestMismatch {
+        expected: String,
+        found: String,
+    },
+    /// A signing key, public key or signature isn't valid hex or not a valid point or scalar
+    SigningKeyError(String),
+    /// None of the signatures of an attestation or registry entry is a valid one by `signer`
+    InvalidSignature {
+        signer: String,
+    },
+    /// A git command failed, with its stderr
+    GitError(String),
+    /// No tool allowed to sandbox the compiler with is installed, see `Backend::sandbox`
+    SandboxUnavailable,
+    /// A contract pushed to `Vypers` sets `setting` differently from the contracts before it,
+    /// or from an earlier contract with the same path
+    ConflictingSettings {
+        path: PathBuf,
+        setting: &'static str,
+    },
+    Cancelled,
+}
+
+/// How a subprocess ended
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Exit {
+    /// Exited with the code
+    Code(i32),
+    /// Killed by the signal, unix only, e.g. `9` (`SIGKILL`) for the OOM killer
+    Signal(i32),
+    /// Neither is known, e.g. for a compiler worker that reports errors and keeps running
+    Unknown,
+}
+
+impl From<&ExitStatus> for Exit {
+    fn from(status: &ExitStatus) -> Self {
+        #[cfg(unix)]
+        if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(status) {
+            return Exit::Signal(signal);
+        }
+        status.code().map_or(Exit::Unknown, Exit::Code)
+    }
+}
+
+impl Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exit::Code(code) => write!(f, "exit code {}", code),
+            Exit::Signal(signal) => write!(f, "killed by signal {}", signal),
+            Exit::Unknown => write!(f, "unknown exit status"),
+        }
+    }
 }
 
 impl Display for VyperErrors {
@@ -28,18 +232,255 @@ impl Display for VyperErrors {
                 s,
             ),
             VyperErrors::CompilerError(msg) => write!(f, "{}", msg),
-            VyperErrors::PipError(msg) => write!(f, "{}", msg),
+            VyperErrors::CompilationFailed {
+                contract,
+                command,
+                version,
+                exit,
+                stderr,
+                ..
+            } => write!(
+                f,
+                "Failed to compile {} with vyper {} (`{}`, {}): {}",
+                contract.display(),
+                version.as_deref().unwrap_or("(unknown version)"),
+                command,
+                exit,
+                stderr
+            ),
+            VyperErrors::VenvCreationFailed { path, stderr } => write!(
+                f,
+                "Failed to create a venv at {}: {}",
+                path.display(),
+                stderr
+            ),
+            VyperErrors::PythonNotFound => write!(
+                f,
+                "No python interpreter found, install python3 or pass one to Venv::with_python()"
+            ),
+            VyperErrors::UnsupportedPython {
+                python,
+                version,
+                minimum,
+            } => write!(
+                f,
+                "{} is python {}, but at least python {}.{} is required",
+                python.display(),
+                version,
+                minimum.0,
+                minimum.1
+            ),
+            VyperErrors::InvalidVenv { path } => write!(
+                f,
+                "{} exists but is not a python venv, remove it or use another path",
+                path.display()
+            ),
+            VyperErrors::UnmanagedVenv { path } => write!(
+                f,
+                "Refusing to delete {}, it wasn't created by vyper-rs",
+                path.display()
+            ),
+            VyperErrors::VenvIoError { path, error } => {
+                write!(f, "Failed to write venv {}: {}", path.display(), error)
+            }
+            VyperErrors::VyperVersionMismatch {
+                venv,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Expected vyper {} in venv {}, found {}",
+                expected,
+                venv.display(),
+                found.as_deref().unwrap_or("no working compiler")
+            ),
+            VyperErrors::PipInstallFailed {
+                version: Some(version),
+                stderr,
+            } => write!(
+                f,
+                "Failed to install vyper {} with pip: {}",
+                version, stderr
+            ),
+            VyperErrors::PipInstallFailed {
+                version: None,
+                stderr,
+            } => write!(f, "Failed to install vyper with pip: {}", stderr),
+            VyperErrors::PipVersionNotFound { version, available } => {
+                write!(
+                    f,
+                    "No release of vyper matches {}",
+                    version.as_deref().unwrap_or("(latest)")
+                )?;
+                match available.last() {
+                    Some(latest) => write!(
+                        f,
+                        ", {} releases are available up to {}",
+                        available.len(),
+                        latest
+                    ),
+                    None => write!(f, ", the package index has none"),
+                }
+            }
+            VyperErrors::PipNetworkError { version, stderr } => write!(
+                f,
+                "Failed to reach the package index to install vyper {}: {}",
+                version.as_deref().unwrap_or("(latest)"),
+                stderr
+            ),
+            VyperErrors::PipPermissionDenied { version, stderr } => write!(
+                f,
+                "Permission denied installing vyper {}, use a venv: {}",
+                version.as_deref().unwrap_or("(latest)"),
+                stderr
+            ),
+            VyperErrors::ToolInstallFailed { tools, stderr } => write!(
+                f,
+                "Failed to install {} with pip: {}",
+                tools.join(" "),
+                stderr
+            ),
+            VyperErrors::PipUninstallFailed { venv, stderr } => write!(
+                f,
+                "Failed to uninstall vyper from venv {}: {}",
+                venv.display(),
+                stderr
+            ),
+            VyperErrors::ChecksumMismatch {
+                path,
+                expected,
+                found: Some(found),
+            } => write!(
+                f,
+                "Checksum mismatch for {}: expected {}, found {}",
+                path.display(),
+                expected,
+                found
+            ),
+            VyperErrors::ChecksumMismatch {
+                path,
+                expected,
+                found: None,
+            } => write!(
+                f,
+                "{} is missing, expected a file with checksum {}",
+                path.display(),
+                expected
+            ),
+            VyperErrors::VyperNotInstalled { venv: Some(venv) } => {
+                write!(f, "Vyper was not installed in venv {}", venv.display())
+            }
+            VyperErrors::VyperNotInstalled { venv: None } => {
+                write!(f, "Vyper not installed")
+            }
+            #[cfg(feature = "async")]
             VyperErrors::ConcurrencyError(je) => {
                 write!(f, "Failed to join async tasks: {}", je)
             }
-            VyperErrors::DirError(msg) => write!(f, "{}", msg),
-            VyperErrors::VenvError(msg) => write!(f, "{}", msg),
             VyperErrors::BlueprintError(msg) => write!(f, "{}", msg),
             VyperErrors::IntParseError(e) => write!(f, "{}", e),
             VyperErrors::StringParsingError => write!(
                 f,
                 "An error occurred while parsing bytecode from vyper compiler output"
             ),
+            VyperErrors::GlobError(e) => write!(f, "Invalid glob pattern: {}", e),
+            VyperErrors::WalkError(e) => {
+                write!(f, "An error occurred while searching for contracts: {}", e)
+            }
+            VyperErrors::TimeoutError(t) => {
+                write!(f, "The vyper compiler did not finish within {:?}", t)
+            }
+            VyperErrors::UnknownEvm(name) => write!(f, "Unknown EVM version: {}", name),
+            VyperErrors::UnsupportedEvm { evm, compiler } => write!(
+                f,
+                "EVM version {} is not supported by vyper {}, {}",
+                evm,
+                compiler,
+                evm.requirement()
+            ),
+            VyperErrors::VersionParseError(e) => {
+                write!(f, "Couldn't parse the compiler version: {}", e)
+            }
+            VyperErrors::NoMatchingCompiler { contract, pragma } => write!(
+                f,
+                "No installed compiler satisfies the version pragma {} of {}",
+                pragma,
+                contract.display()
+            ),
+            VyperErrors::DeploymentFailed(e) => write!(f, "Deployment failed: {}", e),
+            VyperErrors::NonConformingInterface { standard, missing } => write!(
+                f,
+                "The contract doesn't implement {}, missing: {}",
+                standard,
+                missing.join(", ")
+            ),
+            VyperErrors::SimulationFailed(e) => write!(f, "Simulation failed: {}", e),
+            VyperErrors::DirectoryNotFound(path) => {
+                write!(f, "No such directory: {}", path.display())
+            }
+            VyperErrors::DirectoryUnreadable { path, error } => {
+                write!(f, "Failed to read directory {}: {}", path.display(), error)
+            }
+            VyperErrors::NoContractsFound(path) => {
+                write!(f, "No contracts found in {}", path.display())
+            }
+            VyperErrors::ImportCycle(contracts) => {
+                write!(f, "Contracts import each other:")?;
+                for contract in contracts {
+                    write!(f, "\n  {}", contract.display())?;
+                }
+                Ok(())
+            }
+            VyperErrors::QueuedJobFailed(err) => write!(f, "{}", err),
+            VyperErrors::TemplateError { missing, unknown } => {
+                write!(f, "Template values don't match its placeholders")?;
+                if !missing.is_empty() {
+                    write!(f, ", missing: {}", missing.join(", "))?;
+                }
+                if !unknown.is_empty() {
+                    write!(f, ", unknown: {}", unknown.join(", "))?;
+                }
+                Ok(())
+            }
+            VyperErrors::RpcError(msg) => write!(f, "RPC request failed: {}", msg),
+            VyperErrors::OutputExists(path) => {
+                write!(f, "Refusing to overwrite {}", path.display())
+            }
+            VyperErrors::AbiDecodeError(msg) => write!(f, "Failed to ABI decode: {}", msg),
+            VyperErrors::NotAProxy(address) => {
+                write!(f, "{} isn't an EIP-1967 or EIP-1822 proxy", address)
+            }
+            VyperErrors::RegistryError(msg) => {
+                write!(f, "Registry request failed: {}", msg)
+            }
+            VyperErrors::DigestMismatch { expected, found } => write!(
+                f,
+                "Registry entry digest mismatch: expected {}, found {}",
+                expected, found
+            ),
+            VyperErrors::SigningKeyError(msg) => write!(f, "Invalid key: {}", msg),
+            VyperErrors::InvalidSignature { signer } => {
+                write!(f, "No valid signature by {}", signer)
+            }
+            VyperErrors::GitError(msg) => write!(f, "git failed: {}", msg),
+            VyperErrors::SandboxUnavailable => write!(
+                f,
+                "Can't sandbox the compiler: bwrap isn't installed"
+            ),
+            VyperErrors::ConflictingSettings { path, setting } => write!(
+                f,
+                "{} has a different {} than the contracts pushed before it",
+                path.display(),
+                setting
+            ),
+            VyperErrors::Cancelled => write!(f, "Compilation was cancelled"),
+            VyperErrors::LockDrift(drift) => {
+                write!(f, "The project doesn't match vyper.lock:")?;
+                for d in drift {
+                    write!(f, "\n  {}", d)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -58,8 +499,39 @@ impl From<serde_json::Error> for VyperErrors {
     }
 }
 
+#[cfg(feature = "async")]
 impl From<tokio::task::JoinError> for VyperErrors {
     fn from(value: tokio::task::JoinError) -> Self {
         VyperErrors::ConcurrencyError(value)
     }
 }
+
+impl From<semver::Error> for VyperErrors {
+    fn from(value: semver::Error) -> Self {
+        VyperErrors::VersionParseError(value)
+    }
+}
+
+impl From<globset::Error> for VyperErrors {
+    fn from(value: globset::Error) -> Self {
+        VyperErrors::GlobError(value)
+    }
+}
+
+impl From<ignore::Error> for VyperErrors {
+    fn from(value: ignore::Error) -> Self {
+        VyperErrors::WalkError(value)
+    }
+}
+
+impl From<hex::FromHexError> for VyperErrors {
+    fn from(_: hex::FromHexError) -> Self {
+        VyperErrors::StringParsingError
+    }
+}
+
+impl From<crate::blueprint::BlueprintError> for VyperErrors {
+    fn from(value: crate::blueprint::BlueprintError) -> Self {
+        VyperErrors::BlueprintError(value.to_string())
+    }
+}